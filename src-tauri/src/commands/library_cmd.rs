@@ -1,13 +1,31 @@
 use crate::db;
-use crate::library;
-use crate::persistent_entities::{LibraryStats, PersistentAlbum, PersistentArtist, PersistentConfig, PersistentTrack};
-use crate::state::AppState;
-use tauri::{AppHandle, State};
+use crate::library::{self, LyricsValidationIssue};
+use crate::logging;
+use crate::lrclib;
+use crate::persistent_entities::{DirectoryStats, LibraryStats, PersistentAlbum, PersistentArtist, PersistentConfig, PersistentTrack, PersistentTrackSummary};
+use crate::player::Player;
+use crate::state::{AppState, ServiceAccess};
+use tauri::{AppHandle, Emitter, State};
+use tokio_util::sync::CancellationToken;
+
+const DEFAULT_RECENTLY_ADDED_LIMIT: usize = 50;
+
+/// Sort keys the track list commands accept, checked before they reach the ORDER BY clause.
+const TRACK_SORT_BY_VALUES: &[&str] = &["title", "duration", "track_number", "disc_track", "year", "added_at", "lyrics_status"];
+
+fn validate_track_sort(sort_by: &str, sort_order: &str) -> Result<(), String> {
+    if !TRACK_SORT_BY_VALUES.contains(&sort_by) {
+        return Err("Invalid sort_by value".to_owned());
+    }
+    if !matches!(sort_order, "asc" | "desc") {
+        return Err("Invalid sort_order value".to_owned());
+    }
+    Ok(())
+}
 
 #[tauri::command]
 pub async fn get_directories(app_state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let directories = db::get_directories(conn);
     match directories {
         Ok(directories) => Ok(directories),
@@ -23,8 +41,7 @@ pub async fn set_directories(
     directories: Vec<String>,
     app_state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     db::set_directories(directories, conn).map_err(|err| err.to_string())?;
 
     Ok(())
@@ -32,8 +49,7 @@ pub async fn set_directories(
 
 #[tauri::command]
 pub async fn get_init(app_state: State<'_, AppState>) -> Result<bool, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let init = library::get_init(conn).map_err(|err| err.to_string())?;
 
     Ok(init)
@@ -41,8 +57,7 @@ pub async fn get_init(app_state: State<'_, AppState>) -> Result<bool, String> {
 
 #[tauri::command]
 pub async fn get_config(app_state: State<'_, AppState>) -> Result<PersistentConfig, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let config = db::get_config(conn).map_err(|err| err.to_string())?;
 
     Ok(config)
@@ -50,32 +65,47 @@ pub async fn get_config(app_state: State<'_, AppState>) -> Result<PersistentConf
 
 #[tauri::command]
 pub async fn set_config(
-    skip_tracks_with_synced_lyrics: bool,
-    skip_tracks_with_plain_lyrics: bool,
-    show_line_count: bool,
-    try_embed_lyrics: bool,
-    theme_mode: &str,
-    lrclib_instance: &str,
-    lyrics_type_preference: &str,
-    duration_tolerance: f64,
-    fuzzy_search_enabled: bool,
+    mut config: PersistentConfig,
+    validate: bool,
     app_state: State<'_, AppState>,
 ) -> Result<(), String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
-    db::set_config(
-        skip_tracks_with_synced_lyrics,
-        skip_tracks_with_plain_lyrics,
-        show_line_count,
-        try_embed_lyrics,
-        theme_mode,
-        lrclib_instance,
-        lyrics_type_preference,
-        duration_tolerance,
-        fuzzy_search_enabled,
-        conn,
-    )
-    .map_err(|err| err.to_string())?;
+    config.playback_speed = Player::clamp_speed(config.playback_speed)
+        .ok_or_else(|| "Playback speed must be a finite number".to_owned())?;
+    config.max_concurrent_downloads = config
+        .max_concurrent_downloads
+        .clamp(PersistentConfig::MIN_CONCURRENT_DOWNLOADS, PersistentConfig::MAX_CONCURRENT_DOWNLOADS);
+    config.preferred_lyrics_language = config
+        .preferred_lyrics_language
+        .map(|language| language.trim().to_lowercase())
+        .filter(|language| !language.is_empty());
+    if let Some(ref language) = config.preferred_lyrics_language {
+        if language.len() != 2 || !language.chars().all(|c| c.is_ascii_lowercase()) {
+            return Err("Preferred lyrics language must be a two-letter ISO 639-1 code".to_owned());
+        }
+    }
+    let http_client = lrclib::build_http_client(config.http_proxy.as_deref());
+
+    if validate {
+        let current_instance = {
+            let conn = &app_state.db_connection()?;
+            db::get_config(conn).map_err(|err| err.to_string())?.lrclib_instance
+        };
+        if current_instance != config.lrclib_instance {
+            lrclib::validate_instance_url(&config.lrclib_instance, &http_client)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+    }
+
+    let conn = &app_state.db_connection()?;
+    db::set_config(&config, conn).map_err(|err| err.to_string())?;
+
+    *app_state.http_client.write().map_err(|e| format!("HTTP client lock error: {}", e))? = http_client;
+
+    if let Some(ref mut player) = *app_state.player.lock().map_err(|e| e.to_string())? {
+        player.set_speed(config.playback_speed);
+        player.set_replaygain_enabled(config.enable_replaygain);
+    }
 
     Ok(())
 }
@@ -85,28 +115,23 @@ pub async fn initialize_library(
     app_state: State<'_, AppState>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
-    let mut conn = app_state.db.lock()
-        .map_err(|e| format!("Database lock error: {}", e))?
-        .take()
-        .ok_or("Database not initialized")?;
-
-    let (conn, result) = tokio::task::spawn_blocking(move || {
-        let result = library::initialize_library(&mut conn, app_handle);
-        (conn, result)
-    })
-    .await
-    .map_err(|err| err.to_string())?;
+    let mut conn = app_state.db_connection()?;
+    let cancel_token = start_scan(&app_state)?;
+
+    let result = tokio::task::spawn_blocking(move || library::initialize_library(&mut conn, app_handle, &cancel_token))
+        .await
+        .map_err(|err| err.to_string())?;
 
-    *app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))? = Some(conn);
+    app_state.track_cache.lock().map_err(|e| format!("Track cache lock error: {}", e))?.clear();
     result.map_err(|err| err.to_string())
 }
 
 #[tauri::command]
 pub async fn uninitialize_library(app_state: State<'_, AppState>) -> Result<(), String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
 
     library::uninitialize_library(conn).map_err(|err| err.to_string())?;
+    app_state.track_cache.lock().map_err(|e| format!("Track cache lock error: {}", e))?.clear();
 
     Ok(())
 }
@@ -116,31 +141,75 @@ pub async fn refresh_library(
     app_state: State<'_, AppState>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
-    let mut conn = app_state.db.lock()
-        .map_err(|e| format!("Database lock error: {}", e))?
-        .take()
-        .ok_or("Database not initialized")?;
-
-    let (conn, result) = tokio::task::spawn_blocking(move || {
-        let result = library::refresh_library(&mut conn, app_handle);
-        (conn, result)
-    })
-    .await
-    .map_err(|err| err.to_string())?;
+    let mut conn = app_state.db_connection()?;
+    let cancel_token = start_scan(&app_state)?;
+
+    let result = tokio::task::spawn_blocking(move || library::refresh_library(&mut conn, app_handle, &cancel_token))
+        .await
+        .map_err(|err| err.to_string())?;
 
-    *app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))? = Some(conn);
+    app_state.track_cache.lock().map_err(|e| format!("Track cache lock error: {}", e))?.clear();
     result.map_err(|err| err.to_string())
 }
 
+#[tauri::command]
+pub async fn cancel_library_scan(app_state: State<'_, AppState>) -> Result<(), String> {
+    if let Some(cancel_token) = app_state.scan_cancel.lock().map_err(|err| err.to_string())?.as_ref() {
+        cancel_token.cancel();
+    }
+    Ok(())
+}
+
+/// Registers a fresh cancellation token for the scan about to start.
+fn start_scan(app_state: &AppState) -> Result<CancellationToken, String> {
+    let cancel_token = CancellationToken::new();
+    *app_state.scan_cancel.lock().map_err(|err| err.to_string())? = Some(cancel_token.clone());
+    Ok(cancel_token)
+}
+
+/// Deprecated: loads every track at once, with the lyrics fields left empty. Use
+/// `get_tracks_paginated` instead, and `get_track` to read a track's lyrics.
 #[tauri::command]
 pub async fn get_tracks(app_state: State<'_, AppState>) -> Result<Vec<PersistentTrack>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
-    let tracks = library::get_tracks(conn).map_err(|err| err.to_string())?;
+    let conn = &app_state.db_connection()?;
+    let tracks = library::get_tracks_without_lyrics(conn).map_err(|err| err.to_string())?;
+
+    Ok(tracks)
+}
+
+/// Every track without its lyrics text, for list views. Use `get_track` to read a track's lyrics.
+#[tauri::command]
+pub async fn get_track_summaries(app_state: State<'_, AppState>) -> Result<Vec<PersistentTrackSummary>, String> {
+    let conn = &app_state.db_connection()?;
+    let tracks = library::get_track_summaries(conn).map_err(|err| err.to_string())?;
 
     Ok(tracks)
 }
 
+#[tauri::command]
+pub async fn get_tracks_paginated(
+    offset: usize,
+    limit: usize,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<PersistentTrack>, String> {
+    let conn = &app_state.db_connection()?;
+    let sort_by = sort_by.unwrap_or_else(|| "title".to_owned());
+    let sort_order = sort_order.unwrap_or_else(|| "asc".to_owned());
+    validate_track_sort(&sort_by, &sort_order)?;
+    let tracks = library::get_tracks_paginated(offset, limit, &sort_by, &sort_order, conn)
+        .map_err(|err| err.to_string())?;
+
+    Ok(tracks)
+}
+
+#[tauri::command]
+pub async fn count_tracks_total(app_state: State<'_, AppState>) -> Result<usize, String> {
+    let conn = &app_state.db_connection()?;
+    library::count_tracks_total(conn).map_err(|err| err.to_string())
+}
+
 #[tauri::command]
 pub async fn get_track_ids(
     search_query: Option<String>,
@@ -148,21 +217,25 @@ pub async fn get_track_ids(
     plain_lyrics_tracks: Option<bool>,
     instrumental_tracks: Option<bool>,
     no_lyrics_tracks: Option<bool>,
+    favorites_only: Option<bool>,
     sort_by: Option<String>,
     sort_order: Option<String>,
     app_state: State<'_, AppState>,
 ) -> Result<Vec<i64>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let search_query = search_query.filter(|s| !s.is_empty());
     let sort_by = sort_by.unwrap_or_else(|| "title".to_owned());
     let sort_order = sort_order.unwrap_or_else(|| "asc".to_owned());
+    validate_track_sort(&sort_by, &sort_order)?;
     let track_ids = library::get_track_ids(
         search_query,
-        synced_lyrics_tracks.unwrap_or(true),
-        plain_lyrics_tracks.unwrap_or(true),
-        instrumental_tracks.unwrap_or(true),
-        no_lyrics_tracks.unwrap_or(true),
+        db::LyricsFilter {
+            synced: synced_lyrics_tracks.unwrap_or(true),
+            plain: plain_lyrics_tracks.unwrap_or(true),
+            instrumental: instrumental_tracks.unwrap_or(true),
+            missing: no_lyrics_tracks.unwrap_or(true),
+        },
+        favorites_only.unwrap_or(false),
         &sort_by,
         &sort_order,
         conn,
@@ -172,33 +245,174 @@ pub async fn get_track_ids(
     Ok(track_ids)
 }
 
+#[tauri::command]
+pub async fn set_track_favorite(
+    track_id: i64,
+    favorite: bool,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    app_handle
+        .db_write_track(track_id, |db| library::set_track_favorite(track_id, favorite, db))
+        .map_err(|err| err.to_string())?;
+
+    let _ = app_handle.emit("reload-track-id", track_id);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_favorite_track_ids(
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let sort_by = sort_by.unwrap_or_else(|| "title".to_owned());
+    let sort_order = sort_order.unwrap_or_else(|| "asc".to_owned());
+    validate_track_sort(&sort_by, &sort_order)?;
+    let track_ids = library::get_favorite_track_ids(&sort_by, &sort_order, conn)
+        .map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
+
 #[tauri::command]
 pub async fn get_track(
     track_id: i64,
     app_state: State<'_, AppState>,
 ) -> Result<PersistentTrack, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let track = library::get_track(track_id, conn).map_err(|err| err.to_string())?;
 
     Ok(track)
 }
 
+#[tauri::command]
+pub async fn get_track_by_file_path(
+    file_path: String,
+    app_state: State<'_, AppState>,
+) -> Result<PersistentTrack, String> {
+    let conn = &app_state.db_connection()?;
+    let track = library::get_track_by_file_path(&file_path, conn).map_err(|err| err.to_string())?;
+
+    Ok(track)
+}
+
+#[tauri::command]
+pub async fn get_track_by_isrc(
+    isrc: String,
+    app_state: State<'_, AppState>,
+) -> Result<Option<PersistentTrack>, String> {
+    let conn = &app_state.db_connection()?;
+    let track = library::get_track_by_isrc(&isrc, conn).map_err(|err| err.to_string())?;
+
+    Ok(track)
+}
+
+#[tauri::command]
+pub async fn get_tracks_with_stale_lyrics(
+    older_than_days: u32,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let track_ids = library::get_stale_lyrics_track_ids(older_than_days, conn).map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
+
+#[tauri::command]
+pub async fn get_recently_added_tracks(
+    limit: Option<usize>,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let limit = limit.unwrap_or(DEFAULT_RECENTLY_ADDED_LIMIT);
+    let track_ids = library::get_recently_added_track_ids(limit, conn).map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
+
+#[tauri::command]
+pub async fn get_most_played_track_ids(
+    limit: usize,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let track_ids = library::get_most_played_track_ids(limit, conn).map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
+
+#[tauri::command]
+pub async fn get_most_played_tracks(
+    limit: usize,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<PersistentTrack>, String> {
+    let conn = &app_state.db_connection()?;
+    let tracks = library::get_most_played_tracks(limit, conn).map_err(|err| err.to_string())?;
+
+    Ok(tracks)
+}
+
+#[tauri::command]
+pub async fn get_genres(app_state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let conn = &app_state.db_connection()?;
+    let genres = library::get_genres(conn).map_err(|err| err.to_string())?;
+
+    Ok(genres)
+}
+
+#[tauri::command]
+pub async fn get_tracks_by_genre(
+    genre: String,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let track_ids = library::get_tracks_by_genre(&genre, conn).map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
+
+#[tauri::command]
+pub async fn get_composers(app_state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let conn = &app_state.db_connection()?;
+    let composers = library::get_composers(conn).map_err(|err| err.to_string())?;
+
+    Ok(composers)
+}
+
+#[tauri::command]
+pub async fn get_tracks_by_composer(
+    composer: String,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let track_ids = library::get_tracks_by_composer(&composer, conn).map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
+
 #[tauri::command]
 pub async fn get_albums(app_state: State<'_, AppState>) -> Result<Vec<PersistentAlbum>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let albums = library::get_albums(conn).map_err(|err| err.to_string())?;
 
     Ok(albums)
 }
 
 #[tauri::command]
-pub async fn get_album_ids(search_query: Option<String>, app_state: State<'_, AppState>) -> Result<Vec<i64>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+pub async fn get_album_ids(
+    search_query: Option<String>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
     let search_query = search_query.filter(|s| !s.is_empty());
-    let album_ids = library::get_album_ids(search_query.as_deref(), conn).map_err(|err| err.to_string())?;
+    let sort_by = sort_by.unwrap_or_else(|| "name".to_owned());
+    let sort_order = sort_order.unwrap_or_else(|| "asc".to_owned());
+    let album_ids = library::get_album_ids(search_query.as_deref(), &sort_by, &sort_order, conn)
+        .map_err(|err| err.to_string())?;
 
     Ok(album_ids)
 }
@@ -208,8 +422,7 @@ pub async fn get_album(
     album_id: i64,
     app_state: State<'_, AppState>,
 ) -> Result<PersistentAlbum, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let album = library::get_album(album_id, conn).map_err(|err| err.to_string())?;
 
     Ok(album)
@@ -217,8 +430,7 @@ pub async fn get_album(
 
 #[tauri::command]
 pub async fn get_artists(app_state: State<'_, AppState>) -> Result<Vec<PersistentArtist>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let artists = library::get_artists(conn).map_err(|err| err.to_string())?;
 
     Ok(artists)
@@ -226,8 +438,7 @@ pub async fn get_artists(app_state: State<'_, AppState>) -> Result<Vec<Persisten
 
 #[tauri::command]
 pub async fn get_artist_ids(search_query: Option<String>, app_state: State<'_, AppState>) -> Result<Vec<i64>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let search_query = search_query.filter(|s| !s.is_empty());
     let artist_ids = library::get_artist_ids(search_query.as_deref(), conn).map_err(|err| err.to_string())?;
 
@@ -239,20 +450,40 @@ pub async fn get_artist(
     artist_id: i64,
     app_state: State<'_, AppState>,
 ) -> Result<PersistentArtist, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let artist = library::get_artist(artist_id, conn).map_err(|err| err.to_string())?;
 
     Ok(artist)
 }
 
+#[tauri::command]
+pub async fn get_artist_albums(
+    artist_id: i64,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<PersistentAlbum>, String> {
+    let conn = &app_state.db_connection()?;
+    let albums = library::get_artist_albums(artist_id, conn).map_err(|err| err.to_string())?;
+
+    Ok(albums)
+}
+
+#[tauri::command]
+pub async fn get_artist_album_ids(
+    artist_id: i64,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let album_ids = library::get_artist_album_ids(artist_id, conn).map_err(|err| err.to_string())?;
+
+    Ok(album_ids)
+}
+
 #[tauri::command]
 pub async fn get_album_tracks(
     album_id: i64,
     app_state: State<'_, AppState>,
 ) -> Result<Vec<PersistentTrack>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let tracks = library::get_album_tracks(album_id, conn).map_err(|err| err.to_string())?;
 
     Ok(tracks)
@@ -263,8 +494,7 @@ pub async fn get_artist_tracks(
     artist_id: i64,
     app_state: State<'_, AppState>,
 ) -> Result<Vec<PersistentTrack>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let tracks = library::get_artist_tracks(artist_id, conn).map_err(|err| err.to_string())?;
 
     Ok(tracks)
@@ -279,10 +509,10 @@ pub async fn get_album_track_ids(
     sort_order: Option<String>,
     app_state: State<'_, AppState>,
 ) -> Result<Vec<i64>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
-    let sort_by = sort_by.unwrap_or_else(|| "track_number".to_owned());
+    let conn = &app_state.db_connection()?;
+    let sort_by = sort_by.unwrap_or_else(|| "disc_track".to_owned());
     let sort_order = sort_order.unwrap_or_else(|| "asc".to_owned());
+    validate_track_sort(&sort_by, &sort_order)?;
     let track_ids = library::get_album_track_ids(album_id, without_plain_lyrics.unwrap_or(false), without_synced_lyrics.unwrap_or(false), &sort_by, &sort_order, conn).map_err(|err| err.to_string())?;
 
     Ok(track_ids)
@@ -297,10 +527,10 @@ pub async fn get_artist_track_ids(
     sort_order: Option<String>,
     app_state: State<'_, AppState>,
 ) -> Result<Vec<i64>, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let sort_by = sort_by.unwrap_or_else(|| "title".to_owned());
     let sort_order = sort_order.unwrap_or_else(|| "asc".to_owned());
+    validate_track_sort(&sort_by, &sort_order)?;
     let track_ids =
         library::get_artist_track_ids(artist_id, without_plain_lyrics.unwrap_or(false), without_synced_lyrics.unwrap_or(false), &sort_by, &sort_order, conn).map_err(|err| err.to_string())?;
 
@@ -309,9 +539,195 @@ pub async fn get_artist_track_ids(
 
 #[tauri::command]
 pub async fn get_library_stats(app_state: State<'_, AppState>) -> Result<LibraryStats, String> {
-    let conn_guard = app_state.db.lock().map_err(|e| format!("Database lock error: {}", e))?;
-    let conn = conn_guard.as_ref().ok_or("Database not initialized")?;
+    let conn = &app_state.db_connection()?;
     let stats = db::get_library_stats(conn).map_err(|err| err.to_string())?;
 
     Ok(stats)
 }
+
+#[tauri::command]
+pub async fn get_directory_stats(app_state: State<'_, AppState>) -> Result<Vec<DirectoryStats>, String> {
+    let conn = &app_state.db_connection()?;
+    let directories = db::get_directories(conn).map_err(|err| err.to_string())?;
+
+    directories
+        .into_iter()
+        .map(|directory| {
+            let stats = db::get_directory_stats(&directory, conn).map_err(|err| err.to_string())?;
+            Ok(DirectoryStats { directory, stats })
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub async fn export_lyrics_archive(
+    dest_path: String,
+    format: String,
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<u64, String> {
+    let tracks = {
+        let conn = &app_state.db_connection()?;
+        library::get_tracks(conn).map_err(|err| err.to_string())?
+    };
+
+    tokio::task::spawn_blocking(move || {
+        library::export_lyrics_archive(&tracks, std::path::Path::new(&dest_path), &format, &app_handle)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn export_library_to_json(
+    dest_path: String,
+    export_lyrics: bool,
+    app_state: State<'_, AppState>,
+) -> Result<u64, String> {
+    let conn = app_state.db_connection()?;
+
+    tokio::task::spawn_blocking(move || {
+        library::export_library_to_json(&conn, std::path::Path::new(&dest_path), export_lyrics)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn validate_lyrics_files(
+    app_state: State<'_, AppState>,
+) -> Result<Vec<LyricsValidationIssue>, String> {
+    let conn = &app_state.db_connection()?;
+    let issues = library::validate_lyrics_files(conn).map_err(|err| err.to_string())?;
+
+    Ok(issues)
+}
+
+#[tauri::command]
+pub async fn fix_lyrics_discrepancies(
+    issues: Vec<LyricsValidationIssue>,
+    app_handle: AppHandle,
+) -> Result<usize, String> {
+    let fixed_track_ids = app_handle
+        .db_write_tracks(|db| library::fix_lyrics_discrepancies(&issues, db))
+        .map_err(|err| err.to_string())?;
+
+    for track_id in fixed_track_ids.iter() {
+        let _ = app_handle.emit("reload-track-id", *track_id);
+    }
+
+    Ok(fixed_track_ids.len())
+}
+
+#[tauri::command]
+pub async fn get_duplicate_tracks(
+    app_state: State<'_, AppState>,
+) -> Result<Vec<Vec<PersistentTrack>>, String> {
+    let conn = &app_state.db_connection()?;
+    let groups = library::get_duplicate_tracks(conn).map_err(|err| err.to_string())?;
+
+    Ok(groups)
+}
+
+#[tauri::command]
+pub async fn delete_track(
+    track_id: i64,
+    with_files: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    app_handle
+        .db_write_track(track_id, |db| library::delete_track(track_id, with_files.unwrap_or(false), db))
+        .map_err(|err| err.to_string())?;
+
+    app_handle.emit("library-changed", ()).map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn rescan_single_track(
+    track_id: i64,
+    app_handle: AppHandle,
+) -> Result<PersistentTrack, String> {
+    let track = app_handle
+        .db_write_track(track_id, |db| library::rescan_single_track(track_id, db))
+        .map_err(|err| err.to_string())?;
+
+    let _ = app_handle.emit("reload-track-id", track_id);
+
+    Ok(track)
+}
+
+#[tauri::command]
+pub async fn delete_duplicate_keeping_first(
+    group: Vec<i64>,
+    app_handle: AppHandle,
+) -> Result<usize, String> {
+    let deleted_track_ids = app_handle
+        .db_write_tracks(|db| library::delete_duplicate_keeping_first(&group, db))
+        .map_err(|err| err.to_string())?;
+
+    Ok(deleted_track_ids.len())
+}
+
+#[tauri::command]
+pub async fn set_log_level(level: String, app_state: State<'_, AppState>) -> Result<(), String> {
+    logging::set_level(&app_state.log_filter, &level).map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn vacuum_database(app_state: State<'_, AppState>) -> Result<i64, String> {
+    let conn = app_state.db_connection()?;
+
+    tokio::task::spawn_blocking(move || library::vacuum_database(&conn))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn analyze_database(app_state: State<'_, AppState>) -> Result<i64, String> {
+    let conn = app_state.db_connection()?;
+
+    tokio::task::spawn_blocking(move || library::analyze_database(&conn))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn extract_album_art(
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<u32, String> {
+    let conn = app_state.db_connection()?;
+
+    tokio::task::spawn_blocking(move || library::extract_album_art(&conn, &app_handle))
+        .await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn get_album_art(album_id: i64, app_state: State<'_, AppState>) -> Result<Option<String>, String> {
+    let conn = &app_state.db_connection()?;
+    library::get_album_art(album_id, conn).map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub async fn backup_database(
+    dest_path: String,
+    app_state: State<'_, AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let conn = app_state.db_connection()?;
+
+    tokio::task::spawn_blocking(move || {
+        library::backup_database(&conn, std::path::Path::new(&dest_path), &app_handle)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())
+}
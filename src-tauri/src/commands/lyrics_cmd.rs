@@ -1,46 +1,184 @@
-use crate::db;
+use crate::db::{self, DbError};
 use crate::lrclib;
 use crate::lyrics;
-use crate::state::ServiceAccess;
-use crate::utils::{strip_timestamp, RE_INSTRUMENTAL};
+use crate::lyrics::{ApplyLyricsError, SaveMode};
+use crate::persistent_entities::PersistentConfig;
+use crate::state::{AppState, Notify, NotifyType, ServiceAccess};
+use crate::utils::{lrc_shift_timestamps, parse_lrc_metadata, strip_timestamp, RE_INSTRUMENTAL};
 use rusqlite::Connection;
 use serde::Serialize;
-use tauri::{AppHandle, Emitter};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The challenge steps shared by publishing and flagging. The final step is reported
+/// separately, so listeners merge each payload into what they already have.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChallengeProgress<'a> {
+    /// Identifies this challenge to `cancel_challenge`
+    challenge_id: &'a str,
+    request_challenge: &'static str,
+    solve_challenge: &'static str,
+}
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct PublishLyricsProgress {
-    request_challenge: String,
-    solve_challenge: String,
-    publish_lyrics: String,
+    publish_lyrics: &'static str,
 }
 
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct FlagLyricsProgress {
-    request_challenge: String,
-    solve_challenge: String,
-    flag_lyrics: String,
+    flag_lyrics: &'static str,
 }
 
-#[tauri::command]
-pub async fn download_lyrics(track_id: i64, app_handle: AppHandle) -> Result<String, String> {
-    let track = app_handle
-        .db(|db| db::get_track_by_id(track_id, db))
-        .map_err(|err| err.to_string())?;
+/// Turns a missing track into a readable message instead of the raw database error.
+fn track_error_message(err: anyhow::Error) -> String {
+    match err.downcast_ref::<DbError>() {
+        Some(DbError::NotFound { id, .. }) => format!("Track {} no longer exists in the library", id),
+        _ => err.to_string(),
+    }
+}
 
-    // Skip if track already has synced lyrics (already best quality)
-    let has_synced = track.lrc_lyrics.as_ref().is_some_and(|l| l != "[au: instrumental]");
-    if has_synced {
-        return Ok("Skipped: already has synced lyrics".to_owned());
+/// Embedding is best effort: the sidecar file is already saved by then, so a failed embed
+/// is reported as a warning instead of failing the whole command.
+fn handle_apply_error(err: ApplyLyricsError, app_handle: &AppHandle) -> Result<(), String> {
+    match err {
+        ApplyLyricsError::EmbedFailed(_) => {
+            let _ = app_handle.emit("app-notification", Notify::new(err.to_string(), NotifyType::Warning));
+            Ok(())
+        }
+        ApplyLyricsError::Other(err) => Err(err.to_string()),
     }
-    let has_plain = track.txt_lyrics.is_some();
+}
 
+/// What downloading a track's lyrics did, along with the message shown for it.
+enum DownloadOutcome {
+    Saved(String),
+    Instrumental(String),
+    Skipped(String),
+}
+
+impl DownloadOutcome {
+    fn into_message(self) -> String {
+        match self {
+            DownloadOutcome::Saved(message)
+            | DownloadOutcome::Instrumental(message)
+            | DownloadOutcome::Skipped(message) => message,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FailedDownload {
+    track_id: i64,
+    error: String,
+}
+
+/// Track IDs of a `batch_download_lyrics` run, grouped by what happened to them.
+#[derive(Default, Serialize)]
+pub struct BatchDownloadResult {
+    succeeded: Vec<i64>,
+    failed: Vec<FailedDownload>,
+    /// Tracks that already had the best lyrics available for them.
+    skipped: Vec<i64>,
+    instrumental: Vec<i64>,
+    duration_secs: f64,
+}
+
+#[tauri::command]
+pub async fn download_lyrics(
+    track_id: i64,
+    force: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<String, String> {
     let config = app_handle
-        .db(|db| db::get_config(db))
+        .db_read(db::get_config)
         .map_err(|err| err.to_string())?;
+
+    download_track_lyrics(track_id, force.unwrap_or(false), &config, &app_handle)
+        .await
+        .map(DownloadOutcome::into_message)
+}
+
+/// Downloads lyrics for `track_ids`, running up to `max_concurrent_downloads` downloads at once.
+#[tauri::command]
+pub async fn batch_download_lyrics(
+    track_ids: Vec<i64>,
+    force: Option<bool>,
+    app_handle: AppHandle,
+) -> Result<BatchDownloadResult, String> {
+    let started = tokio::time::Instant::now();
+    let force = force.unwrap_or(false);
+    let config = Arc::new(
+        app_handle
+            .db_read(db::get_config)
+            .map_err(|err| err.to_string())?,
+    );
+    let concurrency = config.max_concurrent_downloads.clamp(
+        PersistentConfig::MIN_CONCURRENT_DOWNLOADS,
+        PersistentConfig::MAX_CONCURRENT_DOWNLOADS,
+    );
+    let semaphore = Arc::new(Semaphore::new(concurrency as usize));
+
+    let mut downloads = JoinSet::new();
+    for track_id in track_ids {
+        let semaphore = semaphore.clone();
+        let config = config.clone();
+        let app_handle = app_handle.clone();
+        downloads.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("Download semaphore is never closed");
+            (track_id, download_track_lyrics(track_id, force, &config, &app_handle).await)
+        });
+    }
+
+    let mut result = BatchDownloadResult::default();
+    while let Some(download) = downloads.join_next().await {
+        let (track_id, outcome) = download.map_err(|err| err.to_string())?;
+        match outcome {
+            Ok(DownloadOutcome::Saved(_)) => result.succeeded.push(track_id),
+            Ok(DownloadOutcome::Instrumental(_)) => result.instrumental.push(track_id),
+            Ok(DownloadOutcome::Skipped(_)) => result.skipped.push(track_id),
+            Err(error) => result.failed.push(FailedDownload { track_id, error }),
+        }
+    }
+    result.duration_secs = started.elapsed().as_secs_f64();
+
+    Ok(result)
+}
+
+/// Downloads and saves the lyrics of one track, the way `download_lyrics` does.
+async fn download_track_lyrics(
+    track_id: i64,
+    force: bool,
+    config: &PersistentConfig,
+    app_handle: &AppHandle,
+) -> Result<DownloadOutcome, String> {
+    let track = app_handle
+        .cached_track(track_id)
+        .map_err(track_error_message)?;
+
+    // Skip if track already has synced lyrics (already best quality), unless forced
+    if !force && lyrics::has_synced_lyrics(&track) {
+        return Ok(DownloadOutcome::Skipped("Skipped: already has synced lyrics".to_owned()));
+    }
+    let has_plain = !force && track.txt_lyrics.is_some();
+
+    let client = app_handle.http_client();
+    let options = lyrics::DownloadOptions {
+        force,
+        ..lyrics::DownloadOptions::from_config(config)
+    };
     let (lyrics, match_source) =
-        lyrics::download_lyrics_for_track(track, config.try_embed_lyrics, &config.lrclib_instance, config.duration_tolerance, config.fuzzy_search_enabled)
+        lyrics::download_lyrics_for_track(track, &options, &client)
             .await
             .map_err(|err| err.to_string())?;
 
@@ -58,43 +196,71 @@ pub async fn download_lyrics(track_id: i64, app_handle: AppHandle) -> Result<Str
                 // User wants plain only: strip timestamps and save as plain
                 let stripped = strip_timestamp(&synced_lyrics);
                 if has_plain {
-                    return Ok("Skipped: already has plain lyrics".to_owned());
+                    return Ok(DownloadOutcome::Skipped("Skipped: already has plain lyrics".to_owned()));
                 }
                 app_handle
-                    .db(|db: &Connection| db::update_track_plain_lyrics(track_id, &stripped, db))
+                    .db_write_track(track_id, |db: &mut Connection| db::update_track_plain_lyrics(track_id, &stripped, match_source.as_db_value(), db))
                     .map_err(|err| err.to_string())?;
                 let _ = app_handle.emit("reload-track-id", track_id);
-                Ok(format!("Plain lyrics saved (stripped from synced){}", via))
+                Ok(DownloadOutcome::Saved(format!("Plain lyrics saved (stripped from synced){}", via)))
             } else {
                 app_handle
-                    .db(|db: &Connection| {
-                        db::update_track_synced_lyrics(track_id, &synced_lyrics, &plain_lyrics, db)
+                    .db_write_track(track_id, |db: &mut Connection| {
+                        db::update_track_synced_lyrics(track_id, &synced_lyrics, &plain_lyrics, match_source.as_db_value(), db)
                     })
                     .map_err(|err| err.to_string())?;
                 let _ = app_handle.emit("reload-track-id", track_id);
-                Ok(format!("Synced lyrics downloaded{}", via))
+                Ok(DownloadOutcome::Saved(format!("Synced lyrics downloaded{}", via)))
             }
         }
         lrclib::get::Response::UnsyncedLyrics(plain_lyrics) => {
             if lyrics_pref == "synced_only" {
-                return Ok("Skipped: only plain lyrics available, synced preferred".to_owned());
+                return Ok(DownloadOutcome::Skipped("Skipped: only plain lyrics available, synced preferred".to_owned()));
             }
             if has_plain {
-                return Ok("Skipped: already has plain lyrics, no synced available".to_owned());
+                return Ok(DownloadOutcome::Skipped("Skipped: already has plain lyrics, no synced available".to_owned()));
             }
             app_handle
-                .db(|db: &Connection| db::update_track_plain_lyrics(track_id, &plain_lyrics, db))
+                .db_write_track(track_id, |db: &mut Connection| db::update_track_plain_lyrics(track_id, &plain_lyrics, match_source.as_db_value(), db))
                 .map_err(|err| err.to_string())?;
             let _ = app_handle.emit("reload-track-id", track_id);
-            Ok(format!("Plain lyrics downloaded{}", via))
+            Ok(DownloadOutcome::Saved(format!("Plain lyrics downloaded{}", via)))
         }
         lrclib::get::Response::IsInstrumental => {
             app_handle
-                .db(|db: &Connection| db::update_track_instrumental(track_id, db))
+                .db_write_track(track_id, |db: &mut Connection| db::update_track_instrumental(track_id, db))
                 .map_err(|err| err.to_string())?;
-            Ok(format!("Marked track as instrumental{}", via))
+            Ok(DownloadOutcome::Instrumental(format!("Marked track as instrumental{}", via)))
         }
-        lrclib::get::Response::None => Err(lyrics::GetLyricsError::NotFound.to_string()),
+        lrclib::get::Response::None(reason) => Err(lyrics::GetLyricsError::NotFound(reason).to_string()),
+    }
+}
+
+/// Looks up the lyrics `download_lyrics` would pick, without saving anything. The result can be
+/// passed to `apply_lyrics` once the user confirms it.
+#[tauri::command]
+pub async fn preview_lyrics(
+    track_id: i64,
+    app_handle: AppHandle,
+) -> Result<lrclib::get::RawResponse, String> {
+    let track = app_handle
+        .cached_track(track_id)
+        .map_err(track_error_message)?;
+    let config = app_handle
+        .db_read(db::get_config)
+        .map_err(|err| err.to_string())?;
+    let options = lyrics::DownloadOptions {
+        dry_run: true,
+        force: true,
+        ..lyrics::DownloadOptions::from_config(&config)
+    };
+    let (lyrics, _) = lyrics::download_lyrics_for_track(track, &options, &app_handle.http_client())
+        .await
+        .map_err(|err| err.to_string())?;
+
+    match lyrics {
+        lrclib::get::Response::None(reason) => Err(lyrics::GetLyricsError::NotFound(reason).to_string()),
+        lyrics => Ok(lrclib::get::RawResponse::from_response(lyrics)),
     }
 }
 
@@ -103,45 +269,46 @@ pub async fn apply_lyrics(
     track_id: i64,
     lrclib_response: lrclib::get::RawResponse,
     app_handle: AppHandle,
-) -> Result<String, String> {
+) -> Result<lrclib::get::Response, String> {
     let track = app_handle
-        .db(|db| db::get_track_by_id(track_id, db))
-        .map_err(|err| err.to_string())?;
+        .cached_track(track_id)
+        .map_err(track_error_message)?;
     let is_try_embed_lyrics = app_handle
-        .db(|db| db::get_config(db))
+        .db_read(db::get_config)
         .map_err(|err| err.to_string())?
         .try_embed_lyrics;
 
     let lyrics = lrclib::get::Response::from_raw_response(lrclib_response);
-    let lyrics = lyrics::apply_lyrics_for_track(track, lyrics, is_try_embed_lyrics)
-        .await
-        .map_err(|err| err.to_string())?;
+    if let Err(err) =
+        lyrics::apply_lyrics_for_track(track, lyrics.clone(), is_try_embed_lyrics, SaveMode::Overwrite).await
+    {
+        handle_apply_error(err, &app_handle)?;
+    }
 
-    match lyrics {
+    match &lyrics {
         lrclib::get::Response::SyncedLyrics(synced_lyrics, plain_lyrics) => {
             app_handle
-                .db(|db: &Connection| {
-                    db::update_track_synced_lyrics(track_id, &synced_lyrics, &plain_lyrics, db)
+                .db_write_track(track_id, |db: &mut Connection| {
+                    db::update_track_synced_lyrics(track_id, synced_lyrics, plain_lyrics, None, db)
                 })
                 .map_err(|err| err.to_string())?;
             let _ = app_handle.emit("reload-track-id", track_id);
-            Ok("Synced lyrics downloaded".to_owned())
         }
         lrclib::get::Response::UnsyncedLyrics(plain_lyrics) => {
             app_handle
-                .db(|db: &Connection| db::update_track_plain_lyrics(track_id, &plain_lyrics, db))
+                .db_write_track(track_id, |db: &mut Connection| db::update_track_plain_lyrics(track_id, plain_lyrics, None, db))
                 .map_err(|err| err.to_string())?;
             let _ = app_handle.emit("reload-track-id", track_id);
-            Ok("Plain lyrics downloaded".to_owned())
         }
         lrclib::get::Response::IsInstrumental => {
             app_handle
-                .db(|db: &Connection| db::update_track_instrumental(track_id, db))
+                .db_write_track(track_id, |db: &mut Connection| db::update_track_instrumental(track_id, db))
                 .map_err(|err| err.to_string())?;
-            Ok("Marked track as instrumental".to_owned())
         }
-        lrclib::get::Response::None => Err(lyrics::GetLyricsError::NotFound.to_string()),
+        lrclib::get::Response::None(reason) => return Err(lyrics::GetLyricsError::NotFound(reason.clone()).to_string()),
     }
+
+    Ok(lyrics)
 }
 
 #[tauri::command]
@@ -150,23 +317,46 @@ pub async fn retrieve_lyrics(
     album_name: String,
     artist_name: String,
     duration: f64,
+    include_fallback: Option<bool>,
     app_handle: AppHandle,
 ) -> Result<lrclib::get::RawResponse, String> {
     let config = app_handle
-        .db(|db: &Connection| db::get_config(db))
+        .db_read(db::get_config)
         .map_err(|err| err.to_string())?;
 
-    let response = lrclib::get::request_raw(
+    // Without fallbacks this only asks the exact-match endpoint, as before
+    if !include_fallback.unwrap_or(false) {
+        let lrclib_instances = config.lrclib_instances();
+        let lrclib_instances: Vec<&str> = lrclib_instances.iter().map(String::as_str).collect();
+
+        return lrclib::get::request_raw(
+            &title,
+            &album_name,
+            &artist_name,
+            duration,
+            &lrclib_instances,
+            &app_handle.http_client(),
+        )
+        .await
+        .map_err(|err| err.to_string());
+    }
+
+    let options = lyrics::DownloadOptions::from_config(&config);
+    let (lyrics, _) = lyrics::find_lyrics(
         &title,
         &album_name,
         &artist_name,
         duration,
-        &config.lrclib_instance,
+        &options,
+        &app_handle.http_client(),
     )
     .await
     .map_err(|err| err.to_string())?;
 
-    Ok(response)
+    match lyrics {
+        lrclib::get::Response::None(reason) => Err(lyrics::GetLyricsError::NotFound(reason).to_string()),
+        lyrics => Ok(lrclib::get::RawResponse::from_response(lyrics)),
+    }
 }
 
 #[tauri::command]
@@ -175,10 +365,10 @@ pub async fn retrieve_lyrics_by_id(
     app_handle: AppHandle,
 ) -> Result<lrclib::get_by_id::RawResponse, String> {
     let config = app_handle
-        .db(|db: &Connection| db::get_config(db))
+        .db_read(|db: &Connection| db::get_config(db))
         .map_err(|err| err.to_string())?;
 
-    let response = lrclib::get_by_id::request_raw(id, &config.lrclib_instance)
+    let response = lrclib::get_by_id::request_raw(id, &config.lrclib_instance, &app_handle.http_client())
         .await
         .map_err(|err| err.to_string())?;
 
@@ -191,17 +381,22 @@ pub async fn search_lyrics(
     album_name: String,
     artist_name: String,
     q: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
     app_handle: AppHandle,
 ) -> Result<lrclib::search::Response, String> {
-    let config = app_handle
-        .db(|db: &Connection| db::get_config(db))
+    let lrclib_instances = app_handle
+        .db_read(db::get_all_lrclib_instances)
         .map_err(|err| err.to_string())?;
+    let lrclib_instances: Vec<&str> = lrclib_instances.iter().map(String::as_str).collect();
     let response = lrclib::search::request(
         &title,
         &album_name,
         &artist_name,
         &q,
-        &config.lrclib_instance,
+        lrclib::search::Paging { limit, offset },
+        &lrclib_instances,
+        &app_handle.http_client(),
     )
     .await
     .map_err(|err| err.to_string())?;
@@ -217,49 +412,198 @@ pub async fn save_lyrics(
     app_handle: AppHandle,
 ) -> Result<String, String> {
     let track = app_handle
-        .db(|db| db::get_track_by_id(track_id, db))
-        .map_err(|err| err.to_string())?;
+        .cached_track(track_id)
+        .map_err(track_error_message)?;
     let is_try_embed_lyrics = app_handle
-        .db(|db| db::get_config(db))
+        .db_read(db::get_config)
         .map_err(|err| err.to_string())?
         .try_embed_lyrics;
 
     let is_instrumental = RE_INSTRUMENTAL.is_match(&synced_lyrics);
 
-    lyrics::apply_string_lyrics_for_track(
+    if !is_instrumental && !synced_lyrics.is_empty() {
+        lyrics::validate_lrc(&synced_lyrics).map_err(|err| err.to_string())?;
+    }
+
+    if let Err(err) = lyrics::apply_string_lyrics_for_track(
         &track,
         &plain_lyrics,
         &synced_lyrics,
         is_try_embed_lyrics,
     )
     .await
-    .map_err(|err| err.to_string())?;
-
-    if is_instrumental {
-        app_handle
-            .db(|db: &Connection| db::update_track_instrumental(track.id, db))
-            .map_err(|err| err.to_string())?;
-    } else if !synced_lyrics.is_empty() {
-        app_handle
-            .db(|db: &Connection| {
-                db::update_track_synced_lyrics(track.id, &synced_lyrics, &plain_lyrics, db)
-            })
-            .map_err(|err| err.to_string())?;
-    } else if !plain_lyrics.is_empty() {
-        app_handle
-            .db(|db: &Connection| db::update_track_plain_lyrics(track.id, &plain_lyrics, db))
-            .map_err(|err| err.to_string())?;
-    } else {
-        app_handle
-            .db(|db: &Connection| db::update_track_null_lyrics(track.id, db))
-            .map_err(|err| err.to_string())?;
+    {
+        handle_apply_error(err, &app_handle)?;
     }
 
+    app_handle
+        .db_write_track(track.id, |db: &mut Connection| {
+            if is_instrumental {
+                db::update_track_instrumental(track.id, db)
+            } else if !synced_lyrics.is_empty() {
+                db::update_track_synced_lyrics(track.id, &synced_lyrics, &plain_lyrics, None, db)
+            } else if !plain_lyrics.is_empty() {
+                db::update_track_plain_lyrics(track.id, &plain_lyrics, None, db)
+            } else {
+                db::update_track_null_lyrics(track.id, db)
+            }
+        })
+        .map_err(|err| err.to_string())?;
+
     let _ = app_handle.emit("reload-track-id", track_id);
 
     Ok("Lyrics saved successfully".to_owned())
 }
 
+#[tauri::command]
+pub async fn adjust_lrc_offset(
+    track_id: i64,
+    offset_ms: i64,
+    app_handle: AppHandle,
+) -> Result<String, String> {
+    let track = app_handle
+        .cached_track(track_id)
+        .map_err(track_error_message)?;
+    let synced_lyrics = track
+        .lrc_lyrics
+        .as_deref()
+        .filter(|lyrics| !RE_INSTRUMENTAL.is_match(lyrics))
+        .ok_or("Track has no synced lyrics to adjust")?;
+    let is_try_embed_lyrics = app_handle
+        .db_read(db::get_config)
+        .map_err(|err| err.to_string())?
+        .try_embed_lyrics;
+
+    let shifted_lyrics = lrc_shift_timestamps(synced_lyrics, offset_ms).map_err(|err| err.to_string())?;
+    let plain_lyrics = track
+        .txt_lyrics
+        .clone()
+        .unwrap_or_else(|| strip_timestamp(&shifted_lyrics));
+
+    if let Err(err) =
+        lyrics::apply_string_lyrics_for_track(&track, &plain_lyrics, &shifted_lyrics, is_try_embed_lyrics).await
+    {
+        handle_apply_error(err, &app_handle)?;
+    }
+
+    app_handle
+        .db_write_track(track.id, |db: &mut Connection| {
+            db::update_track_synced_lyrics(track.id, &shifted_lyrics, &plain_lyrics, None, db)
+        })
+        .map_err(|err| err.to_string())?;
+
+    let _ = app_handle.emit("reload-track-id", track_id);
+
+    Ok(shifted_lyrics)
+}
+
+/// A challenge's cancellation flag, registered under a fresh ID for as long as this lives.
+struct ChallengeRegistration<'a> {
+    id: String,
+    cancel: Arc<AtomicBool>,
+    app_handle: &'a AppHandle,
+}
+
+impl<'a> ChallengeRegistration<'a> {
+    fn new(app_handle: &'a AppHandle) -> Result<Self, String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let cancel = Arc::new(AtomicBool::new(false));
+        app_handle
+            .state::<AppState>()
+            .challenge_cancels
+            .lock()
+            .map_err(|err| err.to_string())?
+            .insert(id.clone(), cancel.clone());
+
+        Ok(ChallengeRegistration { id, cancel, app_handle })
+    }
+}
+
+impl Drop for ChallengeRegistration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut challenge_cancels) = self.app_handle.state::<AppState>().challenge_cancels.lock() {
+            if challenge_cancels.get(&self.id).is_some_and(|cancel| Arc::ptr_eq(cancel, &self.cancel)) {
+                challenge_cancels.remove(&self.id);
+            }
+        }
+    }
+}
+
+/// Solves the challenge on a blocking thread, forwarding the number of nonces tried as
+/// `solve-challenge-progress` events. Setting `cancel` stops it early.
+async fn solve_challenge(
+    challenge: &lrclib::request_challenge::Response,
+    cancel: Arc<AtomicBool>,
+    app_handle: &AppHandle,
+) -> Result<String, String> {
+    let (progress_tx, progress_rx) = mpsc::channel::<u64>();
+    let progress_handle = app_handle.clone();
+    // Ends once the solver drops its sender
+    let forward_progress = tokio::task::spawn_blocking(move || {
+        for nonces_tried in progress_rx {
+            progress_handle.emit("solve-challenge-progress", nonces_tried).ok();
+        }
+    });
+
+    let prefix = challenge.prefix.clone();
+    let target = challenge.target.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        lrclib::challenge_solver::solve_challenge(&prefix, &target, cancel, Some(progress_tx), CHALLENGE_TIMEOUT)
+    })
+    .await;
+    forward_progress.await.ok();
+
+    result
+        .map_err(|err| err.to_string())?
+        .map_err(|err| err.to_string())
+}
+
+/// Stops the challenge with the given ID, as sent in the progress events. Without an ID,
+/// every challenge being solved is stopped.
+#[tauri::command]
+pub async fn cancel_challenge(challenge_id: Option<String>, app_state: State<'_, AppState>) -> Result<(), String> {
+    let challenge_cancels = app_state.challenge_cancels.lock().map_err(|err| err.to_string())?;
+    match challenge_id {
+        Some(challenge_id) => {
+            if let Some(cancel) = challenge_cancels.get(&challenge_id) {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+        None => {
+            for cancel in challenge_cancels.values() {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Requests a challenge, solves it and returns the resulting token, reporting each step
+/// under `event_name`.
+async fn solve_and_get_token(
+    lrclib_instance: &str,
+    app_handle: &AppHandle,
+    event_name: &str,
+) -> Result<String, String> {
+    let registration = ChallengeRegistration::new(app_handle)?;
+    let progress = |request_challenge, solve_challenge| ChallengeProgress {
+        challenge_id: &registration.id,
+        request_challenge,
+        solve_challenge,
+    };
+
+    app_handle.emit(event_name, progress("In Progress", "Pending")).ok();
+    let challenge_response = lrclib::request_challenge::request(lrclib_instance, &app_handle.http_client())
+        .await
+        .map_err(|err| err.to_string())?;
+
+    app_handle.emit(event_name, progress("Done", "In Progress")).ok();
+    let nonce = solve_challenge(&challenge_response, registration.cancel.clone(), app_handle).await?;
+
+    app_handle.emit(event_name, progress("Done", "Done")).ok();
+    Ok(lrclib::challenge_solver::format_token(&challenge_response.prefix, &nonce))
+}
+
 #[tauri::command]
 pub async fn publish_lyrics(
     title: String,
@@ -269,42 +613,15 @@ pub async fn publish_lyrics(
     plain_lyrics: String,
     synced_lyrics: String,
     app_handle: AppHandle,
-) -> Result<(), String> {
-    let config = app_handle
-        .db(|db: &Connection| db::get_config(db))
-        .map_err(|err| err.to_string())?;
+) -> Result<i64, String> {
+    lrclib::publish::validate_publish_params(&title, &artist_name, duration).map_err(|err| err.to_string())?;
+    let config = app_handle.db_read(db::get_config).map_err(|err| err.to_string())?;
 
-    let mut progress = PublishLyricsProgress {
-        request_challenge: "Pending".to_owned(),
-        solve_challenge: "Pending".to_owned(),
-        publish_lyrics: "Pending".to_owned(),
-    };
-    progress.request_challenge = "In Progress".to_owned();
+    let publish_token = solve_and_get_token(&config.lrclib_instance, &app_handle, "publish-lyrics-progress").await?;
     app_handle
-        .emit("publish-lyrics-progress", &progress)
+        .emit("publish-lyrics-progress", PublishLyricsProgress { publish_lyrics: "In Progress" })
         .ok();
-    let challenge_response = lrclib::request_challenge::request(&config.lrclib_instance)
-        .await
-        .map_err(|err| err.to_string())?;
-    progress.request_challenge = "Done".to_owned();
-    progress.solve_challenge = "In Progress".to_owned();
-    app_handle
-        .emit("publish-lyrics-progress", &progress)
-        .ok();
-    let prefix = challenge_response.prefix.clone();
-    let target = challenge_response.target.clone();
-    let nonce = tokio::task::spawn_blocking(move || {
-        lrclib::challenge_solver::solve_challenge(&prefix, &target)
-    })
-    .await
-    .map_err(|err| err.to_string())?;
-    progress.solve_challenge = "Done".to_owned();
-    progress.publish_lyrics = "In Progress".to_owned();
-    app_handle
-        .emit("publish-lyrics-progress", &progress)
-        .ok();
-    let publish_token = format!("{}:{}", challenge_response.prefix, nonce);
-    lrclib::publish::request(
+    let lrclib_id = lrclib::publish::request(
         &title,
         &album_name,
         &artist_name,
@@ -313,14 +630,14 @@ pub async fn publish_lyrics(
         &synced_lyrics,
         &publish_token,
         &config.lrclib_instance,
+        &app_handle.http_client(),
     )
     .await
     .map_err(|err| err.to_string())?;
-    progress.publish_lyrics = "Done".to_owned();
     app_handle
-        .emit("publish-lyrics-progress", &progress)
+        .emit("publish-lyrics-progress", PublishLyricsProgress { publish_lyrics: "Done" })
         .ok();
-    Ok(())
+    Ok(lrclib_id)
 }
 
 #[tauri::command]
@@ -329,51 +646,43 @@ pub async fn flag_lyrics(
     flag_reason: String,
     app_handle: AppHandle,
 ) -> Result<(), String> {
-    let config = app_handle
-        .db(|db: &Connection| db::get_config(db))
-        .map_err(|err| err.to_string())?;
+    lrclib::flag::validate_flag_reason(&flag_reason).map_err(|err| err.to_string())?;
+    let config = app_handle.db_read(db::get_config).map_err(|err| err.to_string())?;
 
-    let mut progress = FlagLyricsProgress {
-        request_challenge: "Pending".to_owned(),
-        solve_challenge: "Pending".to_owned(),
-        flag_lyrics: "Pending".to_owned(),
-    };
-    progress.request_challenge = "In Progress".to_owned();
+    let flag_token = solve_and_get_token(&config.lrclib_instance, &app_handle, "flag-lyrics-progress").await?;
     app_handle
-        .emit("flag-lyrics-progress", &progress)
+        .emit("flag-lyrics-progress", FlagLyricsProgress { flag_lyrics: "In Progress" })
         .ok();
-    let challenge_response = lrclib::request_challenge::request(&config.lrclib_instance)
+    lrclib::flag::request(track_id, &flag_reason, &flag_token, &config.lrclib_instance, &app_handle.http_client())
         .await
         .map_err(|err| err.to_string())?;
-    progress.request_challenge = "Done".to_owned();
-    progress.solve_challenge = "In Progress".to_owned();
     app_handle
-        .emit("flag-lyrics-progress", &progress)
-        .ok();
-    let prefix = challenge_response.prefix.clone();
-    let target = challenge_response.target.clone();
-    let nonce = tokio::task::spawn_blocking(move || {
-        lrclib::challenge_solver::solve_challenge(&prefix, &target)
-    })
-    .await
-    .map_err(|err| err.to_string())?;
-    progress.solve_challenge = "Done".to_owned();
-    progress.flag_lyrics = "In Progress".to_owned();
-    app_handle
-        .emit("flag-lyrics-progress", &progress)
-        .ok();
-    let publish_token = format!("{}:{}", challenge_response.prefix, nonce);
-    lrclib::flag::request(
-        track_id,
-        &flag_reason,
-        &publish_token,
-        &config.lrclib_instance,
-    )
-    .await
-    .map_err(|err| err.to_string())?;
-    progress.flag_lyrics = "Done".to_owned();
-    app_handle
-        .emit("flag-lyrics-progress", &progress)
+        .emit("flag-lyrics-progress", FlagLyricsProgress { flag_lyrics: "Done" })
         .ok();
     Ok(())
 }
+
+#[tauri::command]
+pub async fn validate_lrclib_instance(
+    url: String,
+    app_handle: AppHandle,
+) -> Result<lrclib::InstanceInfo, String> {
+    lrclib::validate_instance_url(&url, &app_handle.http_client())
+        .await
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn get_lrc_metadata(synced_lyrics: String) -> Result<HashMap<String, String>, String> {
+    parse_lrc_metadata(&synced_lyrics).map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn convert_lrc_to_srt(synced_lyrics: String) -> Result<String, String> {
+    lyrics::lrc_to_srt(&synced_lyrics).map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn convert_lrc_to_vtt(synced_lyrics: String) -> Result<String, String> {
+    lyrics::lrc_to_vtt(&synced_lyrics).map_err(|err| err.to_string())
+}
@@ -1,3 +1,4 @@
 pub mod library_cmd;
 pub mod lyrics_cmd;
 pub mod player_cmd;
+pub mod playlist_cmd;
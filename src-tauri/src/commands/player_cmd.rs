@@ -1,6 +1,7 @@
 use crate::db;
+use crate::player::{AudioDevice, PlaybackMode, Player};
 use crate::state::{AppState, ServiceAccess};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter};
 
 #[tauri::command]
 pub fn play_track(
@@ -9,13 +10,64 @@ pub fn play_track(
     app_handle: AppHandle,
 ) -> Result<(), String> {
     let track = app_handle
-        .db(|db| db::get_track_by_id(track_id, db))
+        .cached_track(track_id)
         .map_err(|err| err.to_string())?;
-
     let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
 
     if let Some(ref mut player) = *player_guard {
+        let previous_track_id = player.current_track_id;
         player.play(track).map_err(|err| err.to_string())?;
+
+        app_handle
+            .db_write_track(track_id, |db| db::increment_play_count(track_id, db))
+            .map_err(|err| err.to_string())?;
+
+        // Lets the previous track's lyrics view drop its highlighted line
+        if let Some(previous_track_id) = previous_track_id.filter(|&id| id != track_id) {
+            let _ = app_handle.emit("reload-track-id", previous_track_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Plays `track_id` on an already locked player, the same way `play_track` does.
+fn play_track_id(player: &mut Player, track_id: i64, app_handle: &AppHandle) -> Result<(), String> {
+    let track = app_handle
+        .cached_track(track_id)
+        .map_err(|err| err.to_string())?;
+    player.play(track).map_err(|err| err.to_string())?;
+
+    app_handle
+        .db_write_track(track_id, |db| db::increment_play_count(track_id, db))
+        .map_err(|err| err.to_string())?;
+    let _ = app_handle.emit("reload-track-id", track_id);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn next_track(app_state: tauri::State<AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        if let Some(track_id) = player.skip_next() {
+            play_track_id(player, track_id, &app_handle)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn prev_track(app_state: tauri::State<AppState>, app_handle: AppHandle) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        match player.skip_prev() {
+            Some(track_id) => play_track_id(player, track_id, &app_handle)?,
+            None => player.seek(0.0),
+        }
     }
 
     Ok(())
@@ -66,12 +118,187 @@ pub fn stop_track(app_state: tauri::State<AppState>) -> Result<(), String> {
 }
 
 #[tauri::command]
-pub fn set_volume(volume: f64, app_state: tauri::State<AppState>) -> Result<(), String> {
+pub fn set_volume(
+    volume: f64,
+    app_state: tauri::State<AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let volume = Player::clamp_volume(volume)
+        .ok_or_else(|| "Volume must be a finite number".to_owned())?;
+
+    {
+        let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+        if let Some(ref mut player) = *player_guard {
+            player.set_volume(volume);
+        }
+    }
+
+    app_handle
+        .db_write(|db| db::set_last_volume(volume, db))
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_playback_speed(
+    speed: f32,
+    app_state: tauri::State<AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let speed = Player::clamp_speed(speed)
+        .ok_or_else(|| "Playback speed must be a finite number".to_owned())?;
+
+    {
+        let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+        if let Some(ref mut player) = *player_guard {
+            player.set_speed(speed);
+        }
+    }
+
+    app_handle
+        .db_write(|db| db::set_playback_speed(speed, db))
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_sleep_timer(seconds: u64, app_state: tauri::State<AppState>) -> Result<(), String> {
     let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
 
     if let Some(ref mut player) = *player_guard {
-        player.set_volume(volume);
+        player.set_sleep_timer(seconds);
     }
 
     Ok(())
 }
+
+#[tauri::command]
+pub fn cancel_sleep_timer(app_state: tauri::State<AppState>) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        player.cancel_sleep_timer();
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_sleep_timer_remaining(app_state: tauri::State<AppState>) -> Result<Option<u64>, String> {
+    let player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    Ok(player_guard
+        .as_ref()
+        .and_then(|player| player.sleep_timer_remaining()))
+}
+
+#[tauri::command]
+pub fn set_playback_mode(mode: String, app_state: tauri::State<AppState>) -> Result<(), String> {
+    let mode: PlaybackMode = mode.parse().map_err(|err: anyhow::Error| err.to_string())?;
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        player.set_mode(mode);
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn enqueue_tracks(track_ids: Vec<i64>, app_state: tauri::State<AppState>) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        player.enqueue(track_ids);
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn enqueue_track(track_id: i64, app_state: tauri::State<AppState>) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        player.enqueue(vec![track_id]);
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn dequeue_track(index: usize, app_state: tauri::State<AppState>) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        player
+            .dequeue(index)
+            .ok_or_else(|| format!("No queued track at position {}", index))?;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn clear_queue(app_state: tauri::State<AppState>) -> Result<(), String> {
+    let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    if let Some(ref mut player) = *player_guard {
+        player.clear_queue();
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_queue(app_state: tauri::State<AppState>) -> Result<Vec<i64>, String> {
+    let player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+    Ok(player_guard
+        .as_ref()
+        .map(|player| player.queue.iter().copied().collect())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_output_devices() -> Result<Vec<AudioDevice>, String> {
+    Player::output_devices().map_err(|err| err.to_string())
+}
+
+/// Switches to the named output device and remembers it for the next start.
+/// An empty name selects the system default device.
+#[tauri::command]
+pub fn set_output_device(
+    device_name: String,
+    app_state: tauri::State<AppState>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let device_name = Some(device_name).filter(|name| !name.is_empty());
+
+    if let Some(ref name) = device_name {
+        let devices = Player::output_devices().map_err(|err| err.to_string())?;
+        if !devices.iter().any(|device| &device.name == name) {
+            return Err(format!("Output device {} is not available", name));
+        }
+    }
+
+    {
+        let mut player_guard = app_state.player.lock().map_err(|e| e.to_string())?;
+
+        if let Some(ref mut player) = *player_guard {
+            player
+                .set_output_device(device_name.as_deref())
+                .map_err(|err| err.to_string())?;
+        }
+    }
+
+    app_handle
+        .db_write(|db| db::set_audio_output_device(device_name.as_deref(), db))
+        .map_err(|err| err.to_string())?;
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use crate::db;
+use crate::persistent_entities::PersistentPlaylist;
+use crate::state::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_playlists(app_state: State<'_, AppState>) -> Result<Vec<PersistentPlaylist>, String> {
+    let conn = &app_state.db_connection()?;
+    let playlists = db::get_playlists(conn).map_err(|err| err.to_string())?;
+
+    Ok(playlists)
+}
+
+#[tauri::command]
+pub async fn create_playlist(name: String, app_state: State<'_, AppState>) -> Result<i64, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Playlist name cannot be empty".to_owned());
+    }
+
+    let conn = &app_state.db_connection()?;
+    let playlist_id = db::create_playlist(name, conn).map_err(|err| err.to_string())?;
+
+    Ok(playlist_id)
+}
+
+#[tauri::command]
+pub async fn rename_playlist(
+    playlist_id: i64,
+    name: String,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Playlist name cannot be empty".to_owned());
+    }
+
+    let conn = &app_state.db_connection()?;
+    db::rename_playlist(playlist_id, name, conn).map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_playlist(playlist_id: i64, app_state: State<'_, AppState>) -> Result<(), String> {
+    let conn = &app_state.db_connection()?;
+    db::delete_playlist(playlist_id, conn).map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn add_track_to_playlist(
+    playlist_id: i64,
+    track_id: i64,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    let conn = &app_state.db_connection()?;
+    db::add_track_to_playlist(playlist_id, track_id, conn).map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn remove_track_from_playlist(
+    playlist_id: i64,
+    track_id: i64,
+    app_state: State<'_, AppState>,
+) -> Result<(), String> {
+    let conn = &app_state.db_connection()?;
+    db::remove_track_from_playlist(playlist_id, track_id, conn).map_err(|err| err.to_string())?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn get_playlist_track_ids(
+    playlist_id: i64,
+    app_state: State<'_, AppState>,
+) -> Result<Vec<i64>, String> {
+    let conn = &app_state.db_connection()?;
+    let track_ids = db::get_playlist_track_ids(playlist_id, conn).map_err(|err| err.to_string())?;
+
+    Ok(track_ids)
+}
@@ -1,19 +1,65 @@
 use crate::fs_track;
+use crate::lyrics::a2_lyrics_json;
 use crate::persistent_entities::{
-    LibraryStats, PersistentAlbum, PersistentArtist, PersistentConfig, PersistentTrack,
+    LibraryStats, PersistentAlbum, PersistentArtist, PersistentConfig, PersistentPlaylist,
+    PersistentTrack, PersistentTrackSummary,
 };
-use crate::utils::{prepare_input, RE_INSTRUMENTAL};
+use crate::utils::{escape_like, fts5_quote, prepare_input, RE_INSTRUMENTAL};
 use anyhow::Result;
-use indoc::indoc;
-use rusqlite::{named_params, params, Connection};
+use indoc::{formatdoc, indoc};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{named_params, params, Connection, OptionalExtension};
 use std::fs;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::info;
 use tauri::{AppHandle, Manager};
 
-const CURRENT_DB_VERSION: u32 = 13;
+const CURRENT_DB_VERSION: u32 = 41;
+const DB_POOL_SIZE: u32 = 4;
+const DB_BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub type DbPool = Pool<SqliteConnectionManager>;
+pub type DbConnection = PooledConnection<SqliteConnectionManager>;
+
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error("No {entity} was found with `{id}`")]
+    NotFound { entity: String, id: String },
+    #[error("Constraint violation: {0}")]
+    ConstraintViolation(rusqlite::Error),
+    #[error("Database migration failed: {0}")]
+    Migration(rusqlite::Error),
+    #[error(transparent)]
+    Other(rusqlite::Error),
+}
+
+impl DbError {
+    /// Maps an empty single-row query result to `NotFound` for the given entity.
+    fn from_query(err: rusqlite::Error, entity: &str, id: impl ToString) -> DbError {
+        match err {
+            rusqlite::Error::QueryReturnedNoRows => DbError::NotFound {
+                entity: entity.to_owned(),
+                id: id.to_string(),
+            },
+            err => DbError::from(err),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(err: rusqlite::Error) -> DbError {
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::ConstraintViolation) => DbError::ConstraintViolation(err),
+            _ => DbError::Other(err),
+        }
+    }
+}
 
-/// Initializes the database connection, creating the .sqlite file if needed, and upgrading the database
+/// Initializes the database connection pool, creating the .sqlite file if needed, and upgrading the database
 /// if it's out of date.
-pub fn initialize_database(app_handle: &AppHandle) -> Result<Connection, rusqlite::Error> {
+pub fn initialize_database(app_handle: &AppHandle) -> Result<DbPool> {
     let app_dir = app_handle
         .path()
         .app_data_dir()
@@ -21,17 +67,30 @@ pub fn initialize_database(app_handle: &AppHandle) -> Result<Connection, rusqlit
     fs::create_dir_all(&app_dir).expect("The app data directory should be created.");
     let sqlite_path = app_dir.join("db.sqlite3");
 
-    println!("Database file path: {}", sqlite_path.display());
+    info!("Database file path: {}", sqlite_path.display());
 
-    let mut db = Connection::open(sqlite_path)?;
+    let mut db = Connection::open(&sqlite_path)?;
 
     let mut user_pragma = db.prepare("PRAGMA user_version")?;
     let existing_user_version: u32 = user_pragma.query_row([], |row| Ok(row.get(0)?))?;
     drop(user_pragma);
 
-    upgrade_database_if_needed(&mut db, existing_user_version)?;
-
-    Ok(db)
+    upgrade_database_if_needed(&mut db, existing_user_version).map_err(DbError::Migration)?;
+    drop(db);
+
+    // WAL mode lets pooled connections read while another one writes. Writers are still
+    // serialized by SQLite, so each connection waits on the busy timeout instead of failing.
+    //
+    // Foreign keys are enforced on pooled connections only, which is what removes a playlist's
+    // entries along with it. Playlist entries refer to file paths rather than track rows, so
+    // re-initializing the library does not empty the playlists.
+    let manager = SqliteConnectionManager::file(sqlite_path).with_init(|conn| {
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+        conn.busy_timeout(DB_BUSY_TIMEOUT)
+    });
+    let pool = Pool::builder().max_size(DB_POOL_SIZE).build(manager)?;
+
+    Ok(pool)
 }
 
 /// Upgrades the database to the current version.
@@ -39,11 +98,11 @@ pub fn upgrade_database_if_needed(
     db: &mut Connection,
     existing_version: u32,
 ) -> Result<(), rusqlite::Error> {
-    println!("Existing database version: {}", existing_version);
+    info!("Existing database version: {}", existing_version);
 
     if existing_version < CURRENT_DB_VERSION {
         if existing_version <= 0 {
-            println!("Migrate database version 1...");
+            info!("Migrate database version 1...");
             db.pragma_update(None, "journal_mode", "WAL")?;
 
             let tx = db.transaction()?;
@@ -101,7 +160,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 1 {
-            println!("Migrate database version 2...");
+            info!("Migrate database version 2...");
             db.pragma_update(None, "journal_mode", "WAL")?;
 
             let tx = db.transaction()?;
@@ -118,7 +177,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 2 {
-            println!("Migrate database version 3...");
+            info!("Migrate database version 3...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 3)?;
@@ -130,7 +189,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 3 {
-            println!("Migrate database version 4...");
+            info!("Migrate database version 4...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 4)?;
@@ -148,7 +207,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 4 {
-            println!("Migrate database version 5...");
+            info!("Migrate database version 5...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 5)?;
@@ -172,7 +231,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 5 {
-            println!("Migrate database version 6...");
+            info!("Migrate database version 6...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 6)?;
@@ -188,7 +247,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 6 {
-            println!("Migrate database version 7...");
+            info!("Migrate database version 7...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 7)?;
@@ -201,7 +260,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 7 {
-            println!("Migrate database version 8...");
+            info!("Migrate database version 8...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 8)?;
@@ -214,7 +273,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 8 {
-            println!("Migrate database version 9...");
+            info!("Migrate database version 9...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 9)?;
@@ -227,7 +286,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 9 {
-            println!("Migrate database version 10...");
+            info!("Migrate database version 10...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 10)?;
@@ -240,7 +299,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 10 {
-            println!("Migrate database version 11...");
+            info!("Migrate database version 11...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 11)?;
@@ -253,7 +312,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 11 {
-            println!("Migrate database version 12...");
+            info!("Migrate database version 12...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 12)?;
@@ -268,7 +327,7 @@ pub fn upgrade_database_if_needed(
         }
 
         if existing_version <= 12 {
-            println!("Migrate database version 13...");
+            info!("Migrate database version 13...");
             let tx = db.transaction()?;
 
             tx.pragma_update(None, "user_version", 13)?;
@@ -286,319 +345,1155 @@ pub fn upgrade_database_if_needed(
 
             tx.commit()?;
         }
-    }
 
-    Ok(())
-}
+        if existing_version <= 13 {
+            info!("Migrate database version 14...");
+            let tx = db.transaction()?;
 
-pub fn get_directories(db: &Connection) -> Result<Vec<String>> {
-    let mut statement = db.prepare("SELECT * FROM directories")?;
-    let mut rows = statement.query([])?;
-    let mut directories: Vec<String> = Vec::new();
-    while let Some(row) = rows.next()? {
-        let path: String = row.get("path")?;
+            tx.pragma_update(None, "user_version", 14)?;
 
-        directories.push(path);
-    }
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD http_proxy TEXT DEFAULT NULL;
+            "})?;
 
-    Ok(directories)
-}
+            tx.commit()?;
+        }
 
-pub fn set_directories(directories: Vec<String>, db: &Connection) -> Result<()> {
-    db.execute("DELETE FROM directories WHERE 1", ())?;
-    let mut statement = db.prepare("INSERT INTO directories (path) VALUES (@path)")?;
-    for directory in directories.iter() {
-        statement.execute(named_params! { "@path": directory })?;
-    }
+        if existing_version <= 14 {
+            info!("Migrate database version 15...");
+            let tx = db.transaction()?;
 
-    Ok(())
-}
+            tx.pragma_update(None, "user_version", 15)?;
 
-pub fn get_init(db: &Connection) -> Result<bool> {
-    let mut statement = db.prepare("SELECT init FROM library_data LIMIT 1")?;
-    let init: bool = statement.query_row([], |r| r.get(0))?;
-    Ok(init)
-}
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD genre TEXT;
+            CREATE INDEX idx_tracks_genre ON tracks(genre);
+            "})?;
 
-pub fn set_init(init: bool, db: &Connection) -> Result<()> {
-    let mut statement = db.prepare("UPDATE library_data SET init = ? WHERE 1")?;
-    statement.execute([init])?;
-    Ok(())
-}
+            tx.commit()?;
+        }
 
-pub fn get_config(db: &Connection) -> Result<PersistentConfig> {
-    let mut statement = db.prepare(indoc! {"
-      SELECT
-        skip_tracks_with_synced_lyrics,
-        skip_tracks_with_plain_lyrics,
-        show_line_count,
-        try_embed_lyrics,
-        theme_mode,
-        lrclib_instance,
-        lyrics_type_preference,
-        duration_tolerance,
-        fuzzy_search_enabled
-      FROM config_data
-      LIMIT 1
-    "})?;
-    let row = statement.query_row([], |r| {
-        Ok(PersistentConfig {
-            skip_tracks_with_synced_lyrics: r.get("skip_tracks_with_synced_lyrics")?,
-            skip_tracks_with_plain_lyrics: r.get("skip_tracks_with_plain_lyrics")?,
-            show_line_count: r.get("show_line_count")?,
-            try_embed_lyrics: r.get("try_embed_lyrics")?,
-            theme_mode: r.get("theme_mode")?,
-            lrclib_instance: r.get("lrclib_instance")?,
-            lyrics_type_preference: r.get("lyrics_type_preference")?,
-            duration_tolerance: r.get("duration_tolerance")?,
-            fuzzy_search_enabled: r.get("fuzzy_search_enabled")?,
-        })
-    })?;
-    Ok(row)
-}
+        if existing_version <= 15 {
+            info!("Migrate database version 16...");
+            let tx = db.transaction()?;
 
-pub fn set_config(
-    skip_tracks_with_synced_lyrics: bool,
-    skip_tracks_with_plain_lyrics: bool,
-    show_line_count: bool,
-    try_embed_lyrics: bool,
-    theme_mode: &str,
-    lrclib_instance: &str,
-    lyrics_type_preference: &str,
-    duration_tolerance: f64,
-    fuzzy_search_enabled: bool,
-    db: &Connection,
-) -> Result<()> {
-    let mut statement = db.prepare(indoc! {"
-      UPDATE config_data
-      SET
-        skip_tracks_with_synced_lyrics = ?,
-        skip_tracks_with_plain_lyrics = ?,
-        show_line_count = ?,
-        try_embed_lyrics = ?,
-        theme_mode = ?,
-        lrclib_instance = ?,
-        lyrics_type_preference = ?,
-        duration_tolerance = ?,
-        fuzzy_search_enabled = ?
-      WHERE 1
-    "})?;
-    statement.execute((
-        skip_tracks_with_synced_lyrics,
-        skip_tracks_with_plain_lyrics,
-        show_line_count,
-        try_embed_lyrics,
-        theme_mode,
-        lrclib_instance,
-        lyrics_type_preference,
-        duration_tolerance,
-        fuzzy_search_enabled,
-    ))?;
-    Ok(())
-}
+            tx.pragma_update(None, "user_version", 16)?;
 
-fn get_order_clause(sort_by: &str, sort_order: &str) -> String {
-    let column = match sort_by {
-        "title" => "title_lower",
-        "duration" => "duration",
-        "track_number" => "track_number",
-        "lyrics_status" => "CASE WHEN lrc_lyrics IS NOT NULL AND lrc_lyrics != '[au: instrumental]' THEN 0 WHEN txt_lyrics IS NOT NULL THEN 1 WHEN instrumental = 1 THEN 2 ELSE 3 END",
-        _ => "title_lower",
-    };
-    let direction = if sort_order == "desc" { "DESC" } else { "ASC" };
-    format!("ORDER BY {} {}", column, direction)
-}
+            tx.execute_batch(indoc! {"
+            ALTER TABLE albums ADD year INTEGER;
+            UPDATE albums SET year = NULL;
+            "})?;
 
-pub fn get_library_stats(db: &Connection) -> Result<LibraryStats> {
-    let mut statement = db.prepare(indoc! {"
-      SELECT
-        COUNT(*) as total,
-        SUM(CASE WHEN lyrics_status = 'instrumental' THEN 1 ELSE 0 END) as instrumental,
-        SUM(CASE WHEN lyrics_status = 'synced' THEN 1 ELSE 0 END) as synced,
-        SUM(CASE WHEN lyrics_status = 'plain' THEN 1 ELSE 0 END) as plain_only,
-        SUM(CASE WHEN lyrics_status = 'missing' THEN 1 ELSE 0 END) as missing
-      FROM tracks
-    "})?;
-    let row = statement.query_row([], |r| {
-        Ok(LibraryStats {
-            total: r.get("total")?,
-            instrumental: r.get::<_, Option<i64>>("instrumental")?.unwrap_or(0),
-            synced: r.get::<_, Option<i64>>("synced")?.unwrap_or(0),
-            plain_only: r.get::<_, Option<i64>>("plain_only")?.unwrap_or(0),
-            missing: r.get::<_, Option<i64>>("missing")?.unwrap_or(0),
-        })
-    })?;
-    Ok(row)
-}
+            tx.commit()?;
+        }
 
-pub fn find_artist(name: &str, db: &Connection) -> Result<i64> {
-    let mut statement = db.prepare("SELECT id FROM artists WHERE name = ?")?;
-    let id: i64 = statement.query_row([name], |r| r.get(0))?;
-    Ok(id)
-}
+        if existing_version <= 16 {
+            info!("Migrate database version 17...");
+            let tx = db.transaction()?;
 
-pub fn add_artist(name: &str, db: &Connection) -> Result<i64> {
-    let mut statement = db.prepare("INSERT INTO artists (name, name_lower) VALUES (?, ?)")?;
-    let row_id = statement.insert((name, prepare_input(name)))?;
-    Ok(row_id)
-}
+            tx.pragma_update(None, "user_version", 17)?;
 
-pub fn find_album(name: &str, album_artist_name: &str, db: &Connection) -> Result<i64> {
-    let mut statement =
-        db.prepare("SELECT id FROM albums WHERE name = ? AND album_artist_name = ?")?;
-    let id: i64 = statement.query_row((name, album_artist_name), |r| r.get(0))?;
-    Ok(id)
-}
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD disc_number INTEGER;
+            "})?;
 
-pub fn add_album(name: &str, album_artist_name: &str, db: &Connection) -> Result<i64> {
-    let mut statement = db.prepare("INSERT INTO albums (name, name_lower, album_artist_name, album_artist_name_lower) VALUES (?, ?, ?, ?)")?;
-    let row_id = statement.insert((
-        name,
-        prepare_input(name),
-        album_artist_name,
-        prepare_input(album_artist_name),
-    ))?;
-    Ok(row_id)
-}
+            tx.commit()?;
+        }
 
-pub fn get_track_by_id(id: i64, db: &Connection) -> Result<PersistentTrack> {
-    let query = indoc! {"
-    SELECT
-      tracks.id,
-      file_path,
-      file_name,
-      title,
-      artists.name AS artist_name,
-      tracks.artist_id,
-      albums.name AS album_name,
-      albums.album_artist_name,
-      album_id,
-      duration,
-      track_number,
-      albums.image_path,
-      txt_lyrics,
-      lrc_lyrics,
-      instrumental,
-      bitrate
-    FROM tracks
-    JOIN albums ON tracks.album_id = albums.id
-    JOIN artists ON tracks.artist_id = artists.id
-    WHERE tracks.id = ?
-    LIMIT 1
-  "};
+        if existing_version <= 17 {
+            info!("Migrate database version 18...");
+            let tx = db.transaction()?;
 
-    let mut statement = db.prepare(query)?;
-    let row = statement.query_row([id], |row| {
-        let is_instrumental: Option<bool> = row.get("instrumental")?;
+            tx.pragma_update(None, "user_version", 18)?;
 
-        Ok(PersistentTrack {
-            id: row.get("id")?,
-            file_path: row.get("file_path")?,
-            file_name: row.get("file_name")?,
-            title: row.get("title")?,
-            artist_name: row.get("artist_name")?,
-            artist_id: row.get("artist_id")?,
-            album_name: row.get("album_name")?,
-            album_artist_name: row.get("album_artist_name")?,
-            album_id: row.get("album_id")?,
-            duration: row.get("duration")?,
-            track_number: row.get("track_number")?,
-            txt_lyrics: row.get("txt_lyrics")?,
-            lrc_lyrics: row.get("lrc_lyrics")?,
-            image_path: row.get("image_path")?,
-            instrumental: is_instrumental.unwrap_or(false),
-            bitrate: row.get("bitrate")?,
-        })
-    })?;
-    Ok(row)
-}
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD play_count INTEGER DEFAULT 0;
+            ALTER TABLE tracks ADD last_played_at INTEGER;
+            UPDATE tracks SET play_count = 0;
+            "})?;
 
-pub fn update_track_synced_lyrics(
-    id: i64,
-    synced_lyrics: &str,
-    plain_lyrics: &str,
-    db: &Connection,
-) -> Result<PersistentTrack> {
-    let mut statement = db.prepare(
-        "UPDATE tracks SET lrc_lyrics = ?, txt_lyrics = ?, instrumental = false, lyrics_status = 'synced' WHERE id = ?",
-    )?;
-    statement.execute((synced_lyrics, plain_lyrics, id))?;
+            tx.commit()?;
+        }
 
-    Ok(get_track_by_id(id, db)?)
-}
+        if existing_version <= 18 {
+            info!("Migrate database version 19...");
+            let tx = db.transaction()?;
 
-pub fn update_track_plain_lyrics(
-    id: i64,
-    plain_lyrics: &str,
-    db: &Connection,
-) -> Result<PersistentTrack> {
-    let mut statement = db.prepare(
-        "UPDATE tracks SET txt_lyrics = ?, lrc_lyrics = null, instrumental = false, lyrics_status = 'plain' WHERE id = ?",
-    )?;
-    statement.execute((plain_lyrics, id))?;
+            tx.pragma_update(None, "user_version", 19)?;
 
-    Ok(get_track_by_id(id, db)?)
-}
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD is_favorite BOOLEAN DEFAULT 0;
+            CREATE INDEX idx_tracks_is_favorite ON tracks(is_favorite);
+            "})?;
 
-pub fn update_track_null_lyrics(id: i64, db: &Connection) -> Result<PersistentTrack> {
-    let mut statement = db.prepare(
-        "UPDATE tracks SET txt_lyrics = null, lrc_lyrics = null, instrumental = false, lyrics_status = 'missing' WHERE id = ?",
-    )?;
-    statement.execute([id])?;
+            tx.commit()?;
+        }
 
-    Ok(get_track_by_id(id, db)?)
-}
+        if existing_version <= 19 {
+            info!("Migrate database version 20...");
+            let tx = db.transaction()?;
 
-pub fn update_track_instrumental(id: i64, db: &Connection) -> Result<PersistentTrack> {
-    let mut statement = db.prepare(
-        "UPDATE tracks SET txt_lyrics = null, lrc_lyrics = ?, instrumental = true, lyrics_status = 'instrumental' WHERE id = ?",
-    )?;
-    statement.execute(params!["[au: instrumental]", id])?;
+            tx.pragma_update(None, "user_version", 20)?;
 
-    Ok(get_track_by_id(id, db)?)
-}
+            tx.execute_batch(indoc! {"
+            CREATE TABLE playlists (
+                id INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
 
-pub fn add_tracks(
-    tracks: &Vec<fs_track::FsTrack>,
-    db: &mut Connection,
-    artist_cache: &mut std::collections::HashMap<String, i64>,
-    album_cache: &mut std::collections::HashMap<(String, String), i64>,
-) -> Result<()> {
-    let tx = db.transaction()?;
+            CREATE TABLE playlist_tracks (
+                playlist_id INTEGER NOT NULL,
+                track_id INTEGER NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY(playlist_id, track_id),
+                FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+                FOREIGN KEY(track_id) REFERENCES tracks(id) ON DELETE CASCADE
+            );
 
-    // Prepare statement once, reuse for all tracks in the batch
-    let mut insert_stmt = tx.prepare(indoc! {"
-        INSERT INTO tracks (
-            file_path, file_name, title, title_lower, album_id, artist_id,
-            duration, track_number, txt_lyrics, lrc_lyrics, instrumental, bitrate, lyrics_status
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-    "})?;
+            CREATE INDEX idx_playlist_tracks_position ON playlist_tracks(playlist_id, position);
+            "})?;
 
-    for track in tracks.iter() {
-        let artist_key = track.artist().to_owned();
-        let artist_id = if let Some(&id) = artist_cache.get(&artist_key) {
-            id
-        } else {
-            let id = match find_artist(track.artist(), &tx) {
-                Ok(id) => id,
-                Err(_) => add_artist(track.artist(), &tx)?,
-            };
-            artist_cache.insert(artist_key, id);
-            id
-        };
+            tx.commit()?;
+        }
 
-        let album_key = (track.album().to_owned(), track.album_artist().to_owned());
-        let album_id = if let Some(&id) = album_cache.get(&album_key) {
-            id
-        } else {
-            let id = match find_album(track.album(), track.album_artist(), &tx) {
-                Ok(id) => id,
-                Err(_) => add_album(track.album(), track.album_artist(), &tx)?,
-            };
-            album_cache.insert(album_key, id);
+        if existing_version <= 20 {
+            info!("Migrate database version 21...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 21)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD audio_output_device TEXT DEFAULT NULL;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 21 {
+            info!("Migrate database version 22...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 22)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD replaygain_track_gain REAL;
+            ALTER TABLE config_data ADD enable_replaygain BOOLEAN DEFAULT 0;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 22 {
+            info!("Migrate database version 23...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 23)?;
+
+            tx.execute_batch(indoc! {"
+            CREATE INDEX IF NOT EXISTS idx_tracks_file_path ON tracks(file_path);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 23 {
+            info!("Migrate database version 24...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 24)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD content_hash TEXT;
+            CREATE INDEX IF NOT EXISTS idx_tracks_content_hash ON tracks(content_hash);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 24 {
+            info!("Migrate database version 25...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 25)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD composer TEXT;
+            CREATE INDEX idx_tracks_composer ON tracks(composer);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 25 {
+            info!("Migrate database version 26...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 26)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD playback_speed REAL DEFAULT 1.0;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 26 {
+            info!("Migrate database version 27...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 27)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD lyrics_match_source TEXT;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 27 {
+            info!("Migrate database version 28...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 28)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD lrclib_fallback_instances TEXT DEFAULT '[]';
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 28 {
+            info!("Migrate database version 29...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 29)?;
+
+            // ALTER TABLE can't add a column with an expression default, so existing tracks get
+            // the epoch and new ones are stamped on insert
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD added_at INTEGER NOT NULL DEFAULT 0;
+            CREATE INDEX idx_tracks_added_at ON tracks(added_at);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 29 {
+            info!("Migrate database version 30...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 30)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD last_volume REAL DEFAULT 1.0;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 30 {
+            info!("Migrate database version 31...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 31)?;
+
+            // Album and artist names never change in place, so only track writes need to
+            // reach the index
+            tx.execute_batch(indoc! {"
+            CREATE VIRTUAL TABLE tracks_fts USING fts5(
+                title,
+                album_name,
+                artist_name,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+
+            INSERT INTO tracks_fts (rowid, title, album_name, artist_name)
+            SELECT tracks.id, tracks.title_lower, albums.name_lower, artists.name_lower
+            FROM tracks
+            JOIN albums ON tracks.album_id = albums.id
+            JOIN artists ON tracks.artist_id = artists.id;
+
+            CREATE TRIGGER tracks_fts_insert AFTER INSERT ON tracks BEGIN
+                INSERT INTO tracks_fts (rowid, title, album_name, artist_name)
+                VALUES (
+                    new.id,
+                    new.title_lower,
+                    (SELECT name_lower FROM albums WHERE id = new.album_id),
+                    (SELECT name_lower FROM artists WHERE id = new.artist_id)
+                );
+            END;
+
+            CREATE TRIGGER tracks_fts_delete AFTER DELETE ON tracks BEGIN
+                DELETE FROM tracks_fts WHERE rowid = old.id;
+            END;
+
+            CREATE TRIGGER tracks_fts_update AFTER UPDATE OF title_lower, album_id, artist_id ON tracks BEGIN
+                DELETE FROM tracks_fts WHERE rowid = old.id;
+                INSERT INTO tracks_fts (rowid, title, album_name, artist_name)
+                VALUES (
+                    new.id,
+                    new.title_lower,
+                    (SELECT name_lower FROM albums WHERE id = new.album_id),
+                    (SELECT name_lower FROM artists WHERE id = new.artist_id)
+                );
+            END;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 31 {
+            info!("Migrate database version 32...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 32)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD isrc TEXT;
+            CREATE INDEX idx_tracks_isrc ON tracks(isrc);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 32 {
+            info!("Migrate database version 33...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 33)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD check_lyrics_subdirectory BOOLEAN DEFAULT 0;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 33 {
+            info!("Migrate database version 34...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 34)?;
+
+            // Keep the first row of any path scanned twice so the unique index can be built.
+            // Foreign keys are off on this connection, so the duplicates' playlist entries and
+            // favorites are moved onto the kept row by hand instead of relying on the cascade.
+            tx.execute_batch(indoc! {"
+            UPDATE tracks SET is_favorite = 1
+            WHERE id IN (SELECT MIN(id) FROM tracks GROUP BY file_path HAVING MAX(is_favorite) = 1);
+            UPDATE OR IGNORE playlist_tracks
+            SET track_id = (
+              SELECT MIN(kept.id) FROM tracks kept
+              JOIN tracks duplicate ON duplicate.file_path = kept.file_path
+              WHERE duplicate.id = playlist_tracks.track_id
+            )
+            WHERE track_id NOT IN (SELECT MIN(id) FROM tracks GROUP BY file_path);
+            DELETE FROM playlist_tracks WHERE track_id NOT IN (SELECT MIN(id) FROM tracks GROUP BY file_path);
+            DELETE FROM tracks WHERE id NOT IN (SELECT MIN(id) FROM tracks GROUP BY file_path);
+            DROP INDEX IF EXISTS idx_tracks_file_path;
+            CREATE UNIQUE INDEX idx_tracks_file_path ON tracks(file_path);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 34 {
+            info!("Migrate database version 35...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 35)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD lyrics_last_updated INTEGER;
+            CREATE INDEX idx_tracks_lyrics_last_updated ON tracks(lyrics_last_updated);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 35 {
+            info!("Migrate database version 36...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 36)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD max_concurrent_downloads INTEGER DEFAULT 3;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 36 {
+            info!("Migrate database version 37...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 37)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD a2_lyrics TEXT;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 37 {
+            info!("Migrate database version 38...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 38)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE config_data ADD preferred_lyrics_language TEXT DEFAULT NULL;
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 38 {
+            info!("Migrate database version 39...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 39)?;
+
+            tx.execute_batch(indoc! {"
+            ALTER TABLE tracks ADD year INTEGER;
+            CREATE INDEX idx_tracks_year ON tracks(year);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 39 {
+            info!("Migrate database version 40...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 40)?;
+
+            // Databases that went through version 34 before it kept playlist entries may still
+            // point at the duplicate rows it deleted
+            tx.execute_batch(indoc! {"
+            DELETE FROM playlist_tracks WHERE track_id NOT IN (SELECT id FROM tracks);
+            "})?;
+
+            tx.commit()?;
+        }
+
+        if existing_version <= 40 {
+            info!("Migrate database version 41...");
+            let tx = db.transaction()?;
+
+            tx.pragma_update(None, "user_version", 41)?;
+
+            // Playlist entries point at file paths rather than track rows, so they survive
+            // re-initializing the library, which deletes and re-creates every track
+            tx.execute_batch(indoc! {"
+            CREATE TABLE playlist_tracks_new (
+                playlist_id INTEGER NOT NULL,
+                file_path TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY(playlist_id, file_path),
+                FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+            );
+            INSERT OR IGNORE INTO playlist_tracks_new (playlist_id, file_path, position)
+            SELECT playlist_tracks.playlist_id, tracks.file_path, playlist_tracks.position
+            FROM playlist_tracks
+            JOIN tracks ON tracks.id = playlist_tracks.track_id;
+            DROP TABLE playlist_tracks;
+            ALTER TABLE playlist_tracks_new RENAME TO playlist_tracks;
+            CREATE INDEX idx_playlist_tracks_position ON playlist_tracks(playlist_id, position);
+            "})?;
+
+            tx.commit()?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn get_directories(db: &Connection) -> Result<Vec<String>> {
+    let mut statement = db.prepare("SELECT * FROM directories")?;
+    let mut rows = statement.query([])?;
+    let mut directories: Vec<String> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let path: String = row.get("path")?;
+
+        directories.push(path);
+    }
+
+    Ok(directories)
+}
+
+pub fn set_directories(directories: Vec<String>, db: &Connection) -> Result<()> {
+    db.execute("DELETE FROM directories WHERE 1", ())?;
+    let mut statement = db.prepare("INSERT INTO directories (path) VALUES (@path)")?;
+    for directory in directories.iter() {
+        statement.execute(named_params! { "@path": directory })?;
+    }
+
+    Ok(())
+}
+
+pub fn get_init(db: &Connection) -> Result<bool> {
+    let mut statement = db.prepare("SELECT init FROM library_data LIMIT 1")?;
+    let init: bool = statement.query_row([], |r| r.get(0))?;
+    Ok(init)
+}
+
+pub fn set_init(init: bool, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE library_data SET init = ? WHERE 1")?;
+    statement.execute([init])?;
+    Ok(())
+}
+
+pub fn get_config(db: &Connection) -> Result<PersistentConfig> {
+    let mut statement = db.prepare(indoc! {"
+      SELECT
+        skip_tracks_with_synced_lyrics,
+        skip_tracks_with_plain_lyrics,
+        show_line_count,
+        try_embed_lyrics,
+        theme_mode,
+        lrclib_instance,
+        lrclib_fallback_instances,
+        lyrics_type_preference,
+        duration_tolerance,
+        fuzzy_search_enabled,
+        http_proxy,
+        audio_output_device,
+        enable_replaygain,
+        playback_speed,
+        check_lyrics_subdirectory,
+        max_concurrent_downloads,
+        preferred_lyrics_language,
+        last_volume
+      FROM config_data
+      LIMIT 1
+    "})?;
+    let row = statement.query_row([], |r| {
+        let fallback_instances: Option<String> = r.get("lrclib_fallback_instances")?;
+        let lrclib_fallback_instances = fallback_instances
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(err)))?
+            .unwrap_or_default();
+
+        Ok(PersistentConfig {
+            skip_tracks_with_synced_lyrics: r.get("skip_tracks_with_synced_lyrics")?,
+            skip_tracks_with_plain_lyrics: r.get("skip_tracks_with_plain_lyrics")?,
+            show_line_count: r.get("show_line_count")?,
+            try_embed_lyrics: r.get("try_embed_lyrics")?,
+            theme_mode: r.get("theme_mode")?,
+            lrclib_instance: r.get("lrclib_instance")?,
+            lrclib_fallback_instances,
+            lyrics_type_preference: r.get("lyrics_type_preference")?,
+            duration_tolerance: r.get("duration_tolerance")?,
+            fuzzy_search_enabled: r.get("fuzzy_search_enabled")?,
+            http_proxy: r.get("http_proxy")?,
+            audio_output_device: r.get("audio_output_device")?,
+            enable_replaygain: r.get("enable_replaygain")?,
+            playback_speed: r.get("playback_speed")?,
+            check_lyrics_subdirectory: r.get("check_lyrics_subdirectory")?,
+            max_concurrent_downloads: r.get("max_concurrent_downloads")?,
+            preferred_lyrics_language: r.get("preferred_lyrics_language")?,
+            last_volume: r.get("last_volume")?,
+        })
+    })?;
+    Ok(row)
+}
+
+pub fn set_config(config: &PersistentConfig, db: &Connection) -> Result<()> {
+    let PersistentConfig {
+        skip_tracks_with_synced_lyrics,
+        skip_tracks_with_plain_lyrics,
+        show_line_count,
+        try_embed_lyrics,
+        theme_mode,
+        lrclib_instance,
+        lrclib_fallback_instances,
+        lyrics_type_preference,
+        duration_tolerance,
+        fuzzy_search_enabled,
+        http_proxy,
+        audio_output_device: _,
+        enable_replaygain,
+        playback_speed,
+        check_lyrics_subdirectory,
+        max_concurrent_downloads,
+        preferred_lyrics_language,
+        last_volume: _,
+    } = config;
+    let lrclib_fallback_instances = serde_json::to_string(lrclib_fallback_instances)?;
+
+    let mut statement = db.prepare(indoc! {"
+      UPDATE config_data
+      SET
+        skip_tracks_with_synced_lyrics = ?,
+        skip_tracks_with_plain_lyrics = ?,
+        show_line_count = ?,
+        try_embed_lyrics = ?,
+        theme_mode = ?,
+        lrclib_instance = ?,
+        lrclib_fallback_instances = ?,
+        lyrics_type_preference = ?,
+        duration_tolerance = ?,
+        fuzzy_search_enabled = ?,
+        http_proxy = ?,
+        enable_replaygain = ?,
+        playback_speed = ?,
+        check_lyrics_subdirectory = ?,
+        max_concurrent_downloads = ?,
+        preferred_lyrics_language = ?
+      WHERE 1
+    "})?;
+    statement.execute((
+        skip_tracks_with_synced_lyrics,
+        skip_tracks_with_plain_lyrics,
+        show_line_count,
+        try_embed_lyrics,
+        theme_mode,
+        lrclib_instance,
+        lrclib_fallback_instances,
+        lyrics_type_preference,
+        duration_tolerance,
+        fuzzy_search_enabled,
+        http_proxy,
+        enable_replaygain,
+        playback_speed,
+        check_lyrics_subdirectory,
+        max_concurrent_downloads,
+        preferred_lyrics_language,
+    ))?;
+    Ok(())
+}
+
+/// The configured LRCLIB instances in the order they should be tried.
+pub fn get_all_lrclib_instances(db: &Connection) -> Result<Vec<String>> {
+    Ok(get_config(db)?.lrclib_instances())
+}
+
+pub fn set_audio_output_device(device_name: Option<&str>, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE config_data SET audio_output_device = ? WHERE 1")?;
+    statement.execute([device_name])?;
+    Ok(())
+}
+
+pub fn set_playback_speed(playback_speed: f32, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE config_data SET playback_speed = ? WHERE 1")?;
+    statement.execute([playback_speed])?;
+    Ok(())
+}
+
+pub fn set_last_volume(volume: f64, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE config_data SET last_volume = ? WHERE 1")?;
+    statement.execute([volume])?;
+    Ok(())
+}
+
+fn get_order_clause(sort_by: &str, sort_order: &str) -> String {
+    let column = match sort_by {
+        "title" => "title_lower",
+        "duration" => "duration",
+        "track_number" => "track_number",
+        // Tracks without a disc number go last, like in `get_album_tracks`
+        "disc_track" => "COALESCE(disc_number, 999) * 10000 + COALESCE(track_number, 0)",
+        // Compilation tracks carry their own year, the album's is the earliest of them
+        "year" => "COALESCE(tracks.year, albums.year)",
+        "added_at" => "tracks.added_at",
+        // Qualified so list queries that select `NULL AS lrc_lyrics` still sort on the stored text
+        "lyrics_status" => "CASE WHEN tracks.lrc_lyrics IS NOT NULL AND tracks.lrc_lyrics != '[au: instrumental]' THEN 0 WHEN tracks.txt_lyrics IS NOT NULL THEN 1 WHEN instrumental = 1 THEN 2 ELSE 3 END",
+        _ => "title_lower",
+    };
+    let direction = if sort_order == "desc" { "DESC" } else { "ASC" };
+    format!("ORDER BY {} {}", column, direction)
+}
+
+/// Size of the database file in bytes, or zero for an in-memory database.
+pub fn get_file_size(db: &Connection) -> Result<u64> {
+    match db.path().filter(|path| !path.is_empty()) {
+        Some(path) => Ok(fs::metadata(path)?.len()),
+        None => Ok(0),
+    }
+}
+
+/// Rebuilds the database file to release free pages. The WAL is checkpointed afterwards
+/// so the reclaimed space shows up in the main file right away.
+pub fn vacuum(db: &Connection) -> Result<()> {
+    db.execute_batch("VACUUM")?;
+    db.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok(())
+}
+
+pub fn analyze(db: &Connection) -> Result<()> {
+    db.execute_batch("ANALYZE")?;
+    Ok(())
+}
+
+pub fn get_library_stats(db: &Connection) -> Result<LibraryStats> {
+    query_library_stats("", [], db)
+}
+
+/// Stats for the tracks under `directory`. The trailing separator keeps `/music` from also
+/// counting `/music2`, and both separators are tried since paths keep the platform's style.
+pub fn get_directory_stats(directory: &str, db: &Connection) -> Result<LibraryStats> {
+    let prefix = escape_like(directory.trim_end_matches(['/', '\\']), '!');
+    query_library_stats(
+        "WHERE file_path LIKE ? ESCAPE '!' OR file_path LIKE ? ESCAPE '!'",
+        [format!("{}/%", prefix), format!("{}\\%", prefix)],
+        db,
+    )
+}
+
+fn query_library_stats<P: rusqlite::Params>(where_clause: &str, params: P, db: &Connection) -> Result<LibraryStats> {
+    let base_query = indoc! {"
+      SELECT
+        COUNT(*) as total,
+        SUM(CASE WHEN lyrics_status = 'instrumental' THEN 1 ELSE 0 END) as instrumental,
+        SUM(CASE WHEN lyrics_status = 'synced' THEN 1 ELSE 0 END) as synced,
+        SUM(CASE WHEN lyrics_status = 'plain' THEN 1 ELSE 0 END) as plain_only,
+        SUM(CASE WHEN lyrics_status = 'missing' THEN 1 ELSE 0 END) as missing,
+        COALESCE(SUM(duration), 0.0) as total_duration
+      FROM tracks
+    "};
+    let query = format!("{}{}", base_query, where_clause);
+    let mut statement = db.prepare(&query)?;
+    let row = statement.query_row(params, |r| {
+        Ok(LibraryStats {
+            total: r.get("total")?,
+            instrumental: r.get::<_, Option<i64>>("instrumental")?.unwrap_or(0),
+            synced: r.get::<_, Option<i64>>("synced")?.unwrap_or(0),
+            plain_only: r.get::<_, Option<i64>>("plain_only")?.unwrap_or(0),
+            missing: r.get::<_, Option<i64>>("missing")?.unwrap_or(0),
+            total_duration_seconds: r.get("total_duration")?,
+        })
+    })?;
+    Ok(row)
+}
+
+pub fn find_artist(name: &str, db: &Connection) -> Result<i64, DbError> {
+    let mut statement = db.prepare("SELECT id FROM artists WHERE name = ?")?;
+    let id: i64 = statement
+        .query_row([name], |r| r.get(0))
+        .map_err(|err| DbError::from_query(err, "artist", name))?;
+    Ok(id)
+}
+
+pub fn add_artist(name: &str, db: &Connection) -> Result<i64> {
+    let mut statement = db.prepare("INSERT INTO artists (name, name_lower) VALUES (?, ?)")?;
+    let row_id = statement.insert((name, prepare_input(name)))?;
+    Ok(row_id)
+}
+
+pub fn find_album(name: &str, album_artist_name: &str, db: &Connection) -> Result<i64, DbError> {
+    let mut statement =
+        db.prepare("SELECT id FROM albums WHERE name = ? AND album_artist_name = ?")?;
+    let id: i64 = statement
+        .query_row((name, album_artist_name), |r| r.get(0))
+        .map_err(|err| DbError::from_query(err, "album", format!("{} - {}", album_artist_name, name)))?;
+    Ok(id)
+}
+
+pub fn add_album(name: &str, album_artist_name: &str, year: Option<u32>, db: &Connection) -> Result<i64> {
+    let mut statement = db.prepare("INSERT INTO albums (name, name_lower, album_artist_name, album_artist_name_lower, year) VALUES (?, ?, ?, ?, ?)")?;
+    let row_id = statement.insert((
+        name,
+        prepare_input(name),
+        album_artist_name,
+        prepare_input(album_artist_name),
+        year,
+    ))?;
+    Ok(row_id)
+}
+
+fn find_or_add_artist(name: &str, db: &Connection) -> Result<i64> {
+    match find_artist(name, db) {
+        Ok(id) => Ok(id),
+        Err(_) => add_artist(name, db),
+    }
+}
+
+fn find_or_add_album(name: &str, album_artist_name: &str, year: Option<u32>, db: &Connection) -> Result<i64> {
+    match find_album(name, album_artist_name, db) {
+        Ok(id) => Ok(id),
+        Err(_) => add_album(name, album_artist_name, year, db),
+    }
+}
+
+/// Lowers the album year to `year` if it is unset or later, so the earliest track year wins.
+pub fn update_album_year(id: i64, year: u32, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE albums SET year = ?1 WHERE id = ?2 AND (year IS NULL OR year > ?1)")?;
+    statement.execute((year, id))?;
+    Ok(())
+}
+
+/// Columns of `tracks` joined with `albums` and `artists` that every track query selects,
+/// apart from the lyrics text.
+macro_rules! track_summary_columns {
+    () => {
+        "tracks.id, file_path, file_name, title, artists.name AS artist_name, tracks.artist_id, \
+        albums.name AS album_name, albums.album_artist_name, album_id, duration, track_number, \
+        albums.image_path, instrumental, bitrate, genre, disc_number, play_count, last_played_at, \
+        is_favorite, replaygain_track_gain, composer, lyrics_match_source, added_at, isrc, \
+        lyrics_last_updated, tracks.year"
+    };
+}
+
+/// The columns `track_summary_from_row` reads.
+const TRACK_SUMMARY_COLUMNS: &str = track_summary_columns!();
+
+/// The columns `track_from_row` reads.
+const TRACK_COLUMNS: &str = concat!(track_summary_columns!(), ", txt_lyrics, lrc_lyrics, a2_lyrics");
+
+/// `TRACK_COLUMNS` with `NULL` in place of the lyrics text, for list views that should not load
+/// every track's lyrics.
+const TRACK_COLUMNS_WITHOUT_LYRICS: &str =
+    concat!(track_summary_columns!(), ", NULL AS txt_lyrics, NULL AS lrc_lyrics, NULL AS a2_lyrics");
+
+fn track_from_row(row: &rusqlite::Row) -> rusqlite::Result<PersistentTrack> {
+    let is_instrumental: Option<bool> = row.get("instrumental")?;
+
+    Ok(PersistentTrack {
+        id: row.get("id")?,
+        file_path: row.get("file_path")?,
+        file_name: row.get("file_name")?,
+        title: row.get("title")?,
+        artist_name: row.get("artist_name")?,
+        artist_id: row.get("artist_id")?,
+        album_name: row.get("album_name")?,
+        album_artist_name: row.get("album_artist_name")?,
+        album_id: row.get("album_id")?,
+        duration: row.get("duration")?,
+        track_number: row.get("track_number")?,
+        txt_lyrics: row.get("txt_lyrics")?,
+        lrc_lyrics: row.get("lrc_lyrics")?,
+        image_path: row.get("image_path")?,
+        instrumental: is_instrumental.unwrap_or(false),
+        bitrate: row.get("bitrate")?,
+        genre: row.get("genre")?,
+        disc_number: row.get("disc_number")?,
+        play_count: row.get::<_, Option<i64>>("play_count")?.unwrap_or(0),
+        last_played_at: row.get("last_played_at")?,
+        is_favorite: row.get::<_, Option<bool>>("is_favorite")?.unwrap_or(false),
+        replaygain_track_gain: row.get("replaygain_track_gain")?,
+        composer: row.get("composer")?,
+        lyrics_match_source: row.get("lyrics_match_source")?,
+        added_at: row.get("added_at")?,
+        isrc: row.get("isrc")?,
+        lyrics_last_updated: row.get("lyrics_last_updated")?,
+        a2_lyrics: row.get("a2_lyrics")?,
+        year: row.get("year")?,
+    })
+}
+
+fn track_summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<PersistentTrackSummary> {
+    let is_instrumental: Option<bool> = row.get("instrumental")?;
+
+    Ok(PersistentTrackSummary {
+        id: row.get("id")?,
+        file_path: row.get("file_path")?,
+        file_name: row.get("file_name")?,
+        title: row.get("title")?,
+        artist_name: row.get("artist_name")?,
+        artist_id: row.get("artist_id")?,
+        album_name: row.get("album_name")?,
+        album_artist_name: row.get("album_artist_name")?,
+        album_id: row.get("album_id")?,
+        duration: row.get("duration")?,
+        track_number: row.get("track_number")?,
+        image_path: row.get("image_path")?,
+        instrumental: is_instrumental.unwrap_or(false),
+        bitrate: row.get("bitrate")?,
+        genre: row.get("genre")?,
+        disc_number: row.get("disc_number")?,
+        play_count: row.get::<_, Option<i64>>("play_count")?.unwrap_or(0),
+        last_played_at: row.get("last_played_at")?,
+        is_favorite: row.get::<_, Option<bool>>("is_favorite")?.unwrap_or(false),
+        replaygain_track_gain: row.get("replaygain_track_gain")?,
+        composer: row.get("composer")?,
+        lyrics_match_source: row.get("lyrics_match_source")?,
+        added_at: row.get("added_at")?,
+        isrc: row.get("isrc")?,
+        lyrics_last_updated: row.get("lyrics_last_updated")?,
+        year: row.get("year")?,
+    })
+}
+
+pub fn get_track_by_id(id: i64, db: &Connection) -> Result<PersistentTrack, DbError> {
+    let query = formatdoc! {"
+    SELECT {}
+    FROM tracks
+    JOIN albums ON tracks.album_id = albums.id
+    JOIN artists ON tracks.artist_id = artists.id
+    WHERE tracks.id = ?
+    LIMIT 1
+  ", TRACK_COLUMNS};
+
+    let mut statement = db.prepare(&query)?;
+    let row = statement
+        .query_row([id], track_from_row)
+        .map_err(|err| DbError::from_query(err, "track", id))?;
+    Ok(row)
+}
+
+pub fn get_track_by_file_path(file_path: &str, db: &Connection) -> Result<PersistentTrack, DbError> {
+    let query = formatdoc! {"
+    SELECT {}
+    FROM tracks
+    JOIN albums ON tracks.album_id = albums.id
+    JOIN artists ON tracks.artist_id = artists.id
+    WHERE file_path = ?
+    LIMIT 1
+  ", TRACK_COLUMNS};
+
+    let mut statement = db.prepare(&query)?;
+    let row = statement
+        .query_row([file_path], track_from_row)
+        .map_err(|err| DbError::from_query(err, "track", file_path))?;
+    Ok(row)
+}
+
+/// Looks up a track by its compact ISRC. When several files share one, the first added wins.
+pub fn get_track_by_isrc(isrc: &str, db: &Connection) -> Result<Option<PersistentTrack>> {
+    let query = formatdoc! {"
+    SELECT {}
+    FROM tracks
+    JOIN albums ON tracks.album_id = albums.id
+    JOIN artists ON tracks.artist_id = artists.id
+    WHERE isrc = ?
+    ORDER BY tracks.id ASC
+    LIMIT 1
+  ", TRACK_COLUMNS};
+
+    let mut statement = db.prepare(&query)?;
+    let track = statement.query_row([isrc], track_from_row).optional()?;
+    Ok(track)
+}
+
+pub fn update_track_synced_lyrics(
+    id: i64,
+    synced_lyrics: &str,
+    plain_lyrics: &str,
+    match_source: Option<&str>,
+    db: &Connection,
+) -> Result<PersistentTrack> {
+    let mut statement = db.prepare(
+        "UPDATE tracks SET lrc_lyrics = ?, a2_lyrics = ?, txt_lyrics = ?, instrumental = false, lyrics_status = 'synced', lyrics_match_source = ?, lyrics_last_updated = strftime('%s', 'now') WHERE id = ?",
+    )?;
+    statement.execute((synced_lyrics, a2_lyrics_json(synced_lyrics), plain_lyrics, match_source, id))?;
+
+    Ok(get_track_by_id(id, db)?)
+}
+
+pub fn update_track_plain_lyrics(
+    id: i64,
+    plain_lyrics: &str,
+    match_source: Option<&str>,
+    db: &Connection,
+) -> Result<PersistentTrack> {
+    let mut statement = db.prepare(
+        "UPDATE tracks SET txt_lyrics = ?, lrc_lyrics = null, a2_lyrics = null, instrumental = false, lyrics_status = 'plain', lyrics_match_source = ?, lyrics_last_updated = strftime('%s', 'now') WHERE id = ?",
+    )?;
+    statement.execute((plain_lyrics, match_source, id))?;
+
+    Ok(get_track_by_id(id, db)?)
+}
+
+pub fn update_track_null_lyrics(id: i64, db: &Connection) -> Result<PersistentTrack> {
+    let mut statement = db.prepare(
+        "UPDATE tracks SET txt_lyrics = null, lrc_lyrics = null, a2_lyrics = null, instrumental = false, lyrics_status = 'missing', lyrics_match_source = null, lyrics_last_updated = strftime('%s', 'now') WHERE id = ?",
+    )?;
+    statement.execute([id])?;
+
+    Ok(get_track_by_id(id, db)?)
+}
+
+pub fn update_track_instrumental(id: i64, db: &Connection) -> Result<PersistentTrack> {
+    let mut statement = db.prepare(
+        "UPDATE tracks SET txt_lyrics = null, lrc_lyrics = ?, a2_lyrics = null, instrumental = true, lyrics_status = 'instrumental', lyrics_match_source = null, lyrics_last_updated = strftime('%s', 'now') WHERE id = ?",
+    )?;
+    statement.execute(params!["[au: instrumental]", id])?;
+
+    Ok(get_track_by_id(id, db)?)
+}
+
+/// Tracks whose lyrics were last written by the app more than `older_than_days` ago, oldest
+/// first. Lyrics only ever read from files during a scan have no timestamp and are left out.
+pub fn get_stale_lyrics_track_ids(older_than_days: u32, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare(indoc! {"
+        SELECT id FROM tracks
+        WHERE lyrics_last_updated < strftime('%s', 'now') - (? * 86400)
+        ORDER BY lyrics_last_updated ASC, id ASC
+    "})?;
+    let mut rows = statement.query([older_than_days])?;
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+pub fn set_track_favorite(id: i64, favorite: bool, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE tracks SET is_favorite = ? WHERE id = ?")?;
+    statement.execute((favorite, id))?;
+
+    Ok(())
+}
+
+pub fn get_favorite_track_ids(sort_by: &str, sort_order: &str, db: &Connection) -> Result<Vec<i64>> {
+    let order = get_order_clause(sort_by, sort_order);
+    let query = format!(
+        "SELECT tracks.id FROM tracks JOIN albums ON tracks.album_id = albums.id WHERE is_favorite = 1 {}",
+        order
+    );
+
+    let mut statement = db.prepare(&query)?;
+    let mut rows = statement.query([])?;
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+/// Counts a play and records when it happened in one statement, so the two never disagree.
+pub fn increment_play_count(id: i64, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare(
+        "UPDATE tracks SET play_count = COALESCE(play_count, 0) + 1, last_played_at = strftime('%s', 'now') WHERE id = ?",
+    )?;
+    statement.execute([id])?;
+
+    Ok(())
+}
+
+pub fn get_recently_added_track_ids(limit: usize, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare("SELECT id FROM tracks ORDER BY added_at DESC, id DESC LIMIT ?")?;
+    let mut rows = statement.query([limit as i64])?;
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+pub fn get_most_played_track_ids(limit: usize, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare(
+        "SELECT id FROM tracks WHERE play_count > 0 ORDER BY play_count DESC, last_played_at DESC LIMIT ?",
+    )?;
+    let mut rows = statement.query([limit as i64])?;
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+pub fn get_most_played_tracks(limit: usize, db: &Connection) -> Result<Vec<PersistentTrack>> {
+    let mut statement = db.prepare(&formatdoc! {"
+      SELECT {}
+      FROM tracks
+      JOIN albums ON tracks.album_id = albums.id
+      JOIN artists ON tracks.artist_id = artists.id
+      WHERE play_count > 0
+      ORDER BY play_count DESC, last_played_at DESC
+      LIMIT ?
+  ", TRACK_COLUMNS})?;
+    let mut rows = statement.query([limit as i64])?;
+    let mut tracks: Vec<PersistentTrack> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        tracks.push(track_from_row(row)?);
+    }
+
+    Ok(tracks)
+}
+
+/// Groups tracks sharing the same content hash, keeping only groups with more than one track.
+/// Tracks in a group are ordered by ID, so the first one is the earliest added.
+pub fn find_duplicate_tracks(db: &Connection) -> Result<Vec<Vec<PersistentTrack>>> {
+    let mut statement = db.prepare(&formatdoc! {"
+      SELECT {}, content_hash
+      FROM tracks
+      JOIN albums ON tracks.album_id = albums.id
+      JOIN artists ON tracks.artist_id = artists.id
+      WHERE content_hash IN (
+        SELECT content_hash FROM tracks
+        WHERE content_hash IS NOT NULL
+        GROUP BY content_hash
+        HAVING COUNT(*) > 1
+      )
+      ORDER BY content_hash, tracks.id
+  ", TRACK_COLUMNS})?;
+    let mut rows = statement.query([])?;
+    let mut groups: Vec<Vec<PersistentTrack>> = Vec::new();
+    let mut current_hash: Option<String> = None;
+
+    while let Some(row) = rows.next()? {
+        let content_hash: String = row.get("content_hash")?;
+        let track = track_from_row(row)?;
+
+        match groups.last_mut() {
+            Some(group) if current_hash.as_deref() == Some(content_hash.as_str()) => group.push(track),
+            _ => {
+                groups.push(vec![track]);
+                current_hash = Some(content_hash);
+            }
+        }
+    }
+
+    Ok(groups)
+}
+
+pub fn add_tracks(
+    tracks: &Vec<fs_track::FsTrack>,
+    db: &mut Connection,
+    artist_cache: &mut std::collections::HashMap<String, i64>,
+    album_cache: &mut std::collections::HashMap<(String, String), i64>,
+) -> Result<()> {
+    let tx = db.transaction()?;
+
+    // Prepare statement once, reuse for all tracks in the batch. A path seen twice, e.g.
+    // through a symlink loop, keeps its first row.
+    let mut insert_stmt = tx.prepare(indoc! {"
+        INSERT OR IGNORE INTO tracks (
+            file_path, file_name, title, title_lower, album_id, artist_id,
+            duration, track_number, txt_lyrics, lrc_lyrics, a2_lyrics, instrumental, bitrate, lyrics_status, genre,
+            disc_number, replaygain_track_gain, content_hash, composer, isrc, year, added_at
+        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, strftime('%s', 'now'))
+    "})?;
+
+    for track in tracks.iter() {
+        let artist_key = track.artist().to_owned();
+        let artist_id = if let Some(&id) = artist_cache.get(&artist_key) {
+            id
+        } else {
+            let id = find_or_add_artist(track.artist(), &tx)?;
+            artist_cache.insert(artist_key, id);
+            id
+        };
+
+        let album_key = (track.album().to_owned(), track.album_artist().to_owned());
+        let album_id = if let Some(&id) = album_cache.get(&album_key) {
+            id
+        } else {
+            let id = find_or_add_album(track.album(), track.album_artist(), track.year(), &tx)?;
+            album_cache.insert(album_key, id);
             id
         };
 
+        if let Some(year) = track.year() {
+            update_album_year(album_id, year, &tx)?;
+        }
+
         let is_instrumental = track
             .lrc_lyrics()
             .map_or(false, |lyrics| RE_INSTRUMENTAL.is_match(lyrics));
@@ -613,7 +1508,7 @@ pub fn add_tracks(
             "missing"
         };
 
-        insert_stmt.execute((
+        insert_stmt.execute(params![
             track.file_path(),
             track.file_name(),
             track.title(),
@@ -624,10 +1519,18 @@ pub fn add_tracks(
             track.track_number(),
             track.txt_lyrics(),
             track.lrc_lyrics(),
+            track.a2_lyrics(),
             is_instrumental,
             track.bitrate(),
             lyrics_status,
-        ))?;
+            track.genre(),
+            track.disc_number(),
+            track.replaygain_track_gain(),
+            track.content_hash(),
+            track.composer(),
+            track.isrc(),
+            track.year(),
+        ])?;
     }
 
     drop(insert_stmt);
@@ -636,78 +1539,323 @@ pub fn add_tracks(
     Ok(())
 }
 
+/// Rewrites the tag-derived columns of an existing track from a fresh read of its file, moving
+/// it to another album or artist if those tags changed. Lyrics, play stats and favorites stay.
+pub fn update_track_metadata(id: i64, track: &fs_track::FsTrack, db: &mut Connection) -> Result<()> {
+    let tx = db.transaction()?;
+
+    let artist_id = find_or_add_artist(track.artist(), &tx)?;
+    let album_id = find_or_add_album(track.album(), track.album_artist(), track.year(), &tx)?;
+    if let Some(year) = track.year() {
+        update_album_year(album_id, year, &tx)?;
+    }
+
+    tx.execute(
+        indoc! {"
+            UPDATE tracks SET
+                title = ?, title_lower = ?, album_id = ?, artist_id = ?, duration = ?,
+                track_number = ?, bitrate = ?, genre = ?, disc_number = ?,
+                replaygain_track_gain = ?, content_hash = ?, composer = ?, isrc = ?, year = ?
+            WHERE id = ?
+        "},
+        params![
+            track.title(),
+            prepare_input(track.title()),
+            album_id,
+            artist_id,
+            track.duration(),
+            track.track_number(),
+            track.bitrate(),
+            track.genre(),
+            track.disc_number(),
+            track.replaygain_track_gain(),
+            track.content_hash(),
+            track.composer(),
+            track.isrc(),
+            track.year(),
+            id,
+        ],
+    )?;
+
+    delete_orphan_albums(&tx)?;
+    delete_orphan_artists(&tx)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Loads every track with its full lyrics, which only exports need. List views should use
+/// `get_tracks_without_lyrics` or `get_tracks_paginated` instead.
 pub fn get_tracks(db: &Connection) -> Result<Vec<PersistentTrack>> {
-    let query = indoc! {"
-      SELECT
-          tracks.id, file_path, file_name, title,
-          artists.name AS artist_name, tracks.artist_id,
-          albums.name AS album_name, albums.album_artist_name, album_id, duration, track_number,
-          albums.image_path, txt_lyrics, lrc_lyrics, instrumental, bitrate
+    let query = formatdoc! {"
+      SELECT {}
       FROM tracks
       JOIN albums ON tracks.album_id = albums.id
       JOIN artists ON tracks.artist_id = artists.id
       ORDER BY title_lower ASC
-  "};
-    let mut statement = db.prepare(query)?;
+  ", TRACK_COLUMNS};
+    let mut statement = db.prepare(&query)?;
     let mut rows = statement.query([])?;
     let mut tracks: Vec<PersistentTrack> = Vec::new();
 
     while let Some(row) = rows.next()? {
-        let is_instrumental: Option<bool> = row.get("instrumental")?;
+        tracks.push(track_from_row(row)?);
+    }
 
-        let track = PersistentTrack {
-            id: row.get("id")?,
-            file_path: row.get("file_path")?,
-            file_name: row.get("file_name")?,
-            title: row.get("title")?,
-            artist_name: row.get("artist_name")?,
-            artist_id: row.get("artist_id")?,
-            album_name: row.get("album_name")?,
-            album_artist_name: row.get("album_artist_name")?,
-            album_id: row.get("album_id")?,
-            duration: row.get("duration")?,
-            track_number: row.get("track_number")?,
-            txt_lyrics: row.get("txt_lyrics")?,
-            lrc_lyrics: row.get("lrc_lyrics")?,
-            image_path: row.get("image_path")?,
-            instrumental: is_instrumental.unwrap_or(false),
-            bitrate: row.get("bitrate")?,
-        };
+    Ok(tracks)
+}
+
+/// Loads every track with `NULL` in place of the lyrics columns, so listing a large library
+/// does not pull every lyrics text into memory. Use `get_track_by_id` to read a track's lyrics.
+pub fn get_tracks_without_lyrics(db: &Connection) -> Result<Vec<PersistentTrack>> {
+    let query = formatdoc! {"
+      SELECT {}
+      FROM tracks
+      JOIN albums ON tracks.album_id = albums.id
+      JOIN artists ON tracks.artist_id = artists.id
+      ORDER BY title_lower ASC
+  ", TRACK_COLUMNS_WITHOUT_LYRICS};
+    let mut statement = db.prepare(&query)?;
+    let mut rows = statement.query([])?;
+    let mut tracks: Vec<PersistentTrack> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        tracks.push(track_from_row(row)?);
+    }
+
+    Ok(tracks)
+}
+
+pub fn get_track_summaries(db: &Connection) -> Result<Vec<PersistentTrackSummary>> {
+    let query = formatdoc! {"
+      SELECT {}
+      FROM tracks
+      JOIN albums ON tracks.album_id = albums.id
+      JOIN artists ON tracks.artist_id = artists.id
+      ORDER BY title_lower ASC
+  ", TRACK_SUMMARY_COLUMNS};
+    let mut statement = db.prepare(&query)?;
+    let mut rows = statement.query([])?;
+    let mut tracks: Vec<PersistentTrackSummary> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        tracks.push(track_summary_from_row(row)?);
+    }
+
+    Ok(tracks)
+}
+
+/// The `(lrc_lyrics, txt_lyrics)` of a track.
+pub type TrackLyrics = (Option<String>, Option<String>);
+
+/// The lyrics of every track, keyed by track ID.
+pub fn get_all_track_lyrics(db: &Connection) -> Result<std::collections::HashMap<i64, TrackLyrics>> {
+    let mut statement = db.prepare("SELECT id, lrc_lyrics, txt_lyrics FROM tracks")?;
+    let mut rows = statement.query([])?;
+    let mut lyrics = std::collections::HashMap::new();
+
+    while let Some(row) = rows.next()? {
+        lyrics.insert(row.get("id")?, (row.get("lrc_lyrics")?, row.get("txt_lyrics")?));
+    }
+
+    Ok(lyrics)
+}
+
+pub fn get_tracks_paginated(
+    offset: usize,
+    limit: usize,
+    sort_by: &str,
+    sort_order: &str,
+    db: &Connection,
+) -> Result<Vec<PersistentTrack>> {
+    let base_query = formatdoc! {"
+      SELECT {}
+      FROM tracks
+      JOIN albums ON tracks.album_id = albums.id
+      JOIN artists ON tracks.artist_id = artists.id
+    ", TRACK_COLUMNS_WITHOUT_LYRICS};
+    let order = get_order_clause(sort_by, sort_order);
+    // Break ties on id so pages don't overlap when many rows share a sort key
+    let full_query = format!("{}{}, tracks.id ASC LIMIT ? OFFSET ?", base_query, order);
+
+    let mut statement = db.prepare(&full_query)?;
+    let mut rows = statement.query(params![limit, offset])?;
+    let mut tracks: Vec<PersistentTrack> = Vec::new();
 
-        tracks.push(track);
+    while let Some(row) = rows.next()? {
+        tracks.push(track_from_row(row)?);
     }
 
     Ok(tracks)
 }
 
+pub fn count_tracks_total(db: &Connection) -> Result<usize> {
+    let count: i64 = db.query_row("SELECT COUNT(*) FROM tracks", [], |r| r.get(0))?;
+    Ok(count as usize)
+}
+
+/// Which lyrics states a track list includes, matched against `tracks.lyrics_status`.
+#[derive(Clone, Copy, Debug)]
+pub struct LyricsFilter {
+    pub synced: bool,
+    pub plain: bool,
+    pub instrumental: bool,
+    pub missing: bool,
+}
+
+impl LyricsFilter {
+    /// Includes every track, whatever its lyrics.
+    pub const ALL: LyricsFilter = LyricsFilter { synced: true, plain: true, instrumental: true, missing: true };
+
+    /// The condition dropping the excluded states, or `None` when every state is included.
+    fn condition(&self) -> Option<String> {
+        let mut excluded = Vec::new();
+        if !self.synced { excluded.push("'synced'"); }
+        if !self.plain { excluded.push("'plain'"); }
+        if !self.instrumental { excluded.push("'instrumental'"); }
+        if !self.missing { excluded.push("'missing'"); }
+
+        (!excluded.is_empty()).then(|| format!("tracks.lyrics_status NOT IN ({})", excluded.join(", ")))
+    }
+}
+
 pub fn get_track_ids(
-    synced_lyrics: bool,
-    plain_lyrics: bool,
-    instrumental: bool,
-    no_lyrics: bool,
+    lyrics_filter: LyricsFilter,
+    favorites_only: bool,
+    sort_by: &str,
+    sort_order: &str,
+    db: &Connection
+) -> Result<Vec<i64>> {
+    let base_query = "SELECT tracks.id FROM tracks JOIN albums ON tracks.album_id = albums.id";
+
+    let mut conditions = Vec::new();
+    conditions.extend(lyrics_filter.condition());
+    if favorites_only {
+        conditions.push("is_favorite = 1".to_owned());
+    }
+
+    let where_clause = if !conditions.is_empty() {
+        format!(" WHERE {}", conditions.join(" AND "))
+    } else {
+        String::new()
+    };
+
+    let order = get_order_clause(sort_by, sort_order);
+    let full_query = format!("{}{} {}", base_query, where_clause, order);
+
+    let mut statement = db.prepare(&full_query)?;
+    let mut rows = statement.query([])?;
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+pub fn get_search_track_ids(
+    query_str: &String,
+    lyrics_filter: LyricsFilter,
+    favorites_only: bool,
+    fuzzy_search_enabled: bool,
     sort_by: &str,
     sort_order: &str,
     db: &Connection
 ) -> Result<Vec<i64>> {
-    let base_query = "SELECT id FROM tracks";
+    let prepared_query_str = prepare_input(query_str);
+    // An empty MATCH is a syntax error, and LIKE '%%' already matches everything
+    let use_fts = fuzzy_search_enabled && !prepared_query_str.is_empty();
+
+    let base_query = if use_fts {
+        indoc! {"
+          SELECT tracks.id
+          FROM tracks
+          JOIN tracks_fts ON tracks_fts.rowid = tracks.id
+          JOIN artists ON tracks.artist_id = artists.id
+          JOIN albums ON tracks.album_id = albums.id
+          WHERE tracks_fts MATCH ?
+        "}
+    } else {
+        indoc! {"
+          SELECT tracks.id
+          FROM tracks
+          JOIN artists ON tracks.artist_id = artists.id
+          JOIN albums ON tracks.album_id = albums.id
+          WHERE (artists.name_lower LIKE ? ESCAPE '\\'
+          OR albums.name_lower LIKE ? ESCAPE '\\'
+          OR tracks.title_lower LIKE ? ESCAPE '\\')
+        "}
+    };
+
+    let mut where_clause = lyrics_filter
+        .condition()
+        .map(|condition| format!(" AND {}", condition))
+        .unwrap_or_default();
+    if favorites_only {
+        where_clause.push_str(" AND tracks.is_favorite = 1");
+    }
+
+    let order = get_order_clause(sort_by, sort_order);
+    let full_query = format!("{}{} {}", base_query, where_clause, order);
+
+    let mut statement = db.prepare(&full_query)?;
+    let mut rows = if use_fts {
+        // Every word has to match the start of a token in the title, album or artist
+        let match_query = prepared_query_str
+            .split_whitespace()
+            .map(|word| format!("{}*", fts5_quote(word)))
+            .collect::<Vec<String>>()
+            .join(" ");
+        statement.query(params![match_query])?
+    } else {
+        let formatted_query_str = format!("%{}%", escape_like(&prepared_query_str, '\\'));
+        statement.query(params![
+            formatted_query_str,
+            formatted_query_str,
+            formatted_query_str
+        ])?
+    };
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+pub fn get_genres(db: &Connection) -> Result<Vec<String>> {
+    let mut statement = db.prepare(
+        "SELECT DISTINCT genre FROM tracks WHERE genre IS NOT NULL AND genre != '' ORDER BY genre COLLATE NOCASE ASC"
+    )?;
+    let mut rows = statement.query([])?;
+    let mut genres: Vec<String> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        genres.push(row.get("genre")?);
+    }
 
-    let mut excluded = Vec::new();
-    if !synced_lyrics { excluded.push("'synced'"); }
-    if !plain_lyrics { excluded.push("'plain'"); }
-    if !instrumental { excluded.push("'instrumental'"); }
-    if !no_lyrics { excluded.push("'missing'"); }
+    Ok(genres)
+}
 
-    let where_clause = if !excluded.is_empty() {
-        format!(" WHERE lyrics_status NOT IN ({})", excluded.join(", "))
-    } else {
-        String::new()
-    };
+pub fn get_composers(db: &Connection) -> Result<Vec<String>> {
+    let mut statement = db.prepare(
+        "SELECT DISTINCT composer FROM tracks WHERE composer IS NOT NULL AND composer != '' ORDER BY composer COLLATE NOCASE ASC"
+    )?;
+    let mut rows = statement.query([])?;
+    let mut composers: Vec<String> = Vec::new();
 
-    let order = get_order_clause(sort_by, sort_order);
-    let full_query = format!("{}{} {}", base_query, where_clause, order);
+    while let Some(row) = rows.next()? {
+        composers.push(row.get("composer")?);
+    }
 
-    let mut statement = db.prepare(&full_query)?;
-    let mut rows = statement.query([])?;
+    Ok(composers)
+}
+
+pub fn get_tracks_by_composer(composer: &str, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare("SELECT id FROM tracks WHERE composer = ? ORDER BY title_lower ASC")?;
+    let mut rows = statement.query([composer])?;
     let mut track_ids: Vec<i64> = Vec::new();
 
     while let Some(row) = rows.next()? {
@@ -717,48 +1865,9 @@ pub fn get_track_ids(
     Ok(track_ids)
 }
 
-pub fn get_search_track_ids(
-    query_str: &String,
-    synced_lyrics: bool,
-    plain_lyrics: bool,
-    instrumental: bool,
-    no_lyrics: bool,
-    sort_by: &str,
-    sort_order: &str,
-    db: &Connection
-) -> Result<Vec<i64>> {
-    let base_query = indoc! {"
-      SELECT tracks.id
-      FROM tracks
-      JOIN artists ON tracks.artist_id = artists.id
-      JOIN albums ON tracks.album_id = albums.id
-      WHERE (artists.name_lower LIKE ?
-      OR albums.name_lower LIKE ?
-      OR tracks.title_lower LIKE ?)
-    "};
-
-    let mut excluded = Vec::new();
-    if !synced_lyrics { excluded.push("'synced'"); }
-    if !plain_lyrics { excluded.push("'plain'"); }
-    if !instrumental { excluded.push("'instrumental'"); }
-    if !no_lyrics { excluded.push("'missing'"); }
-
-    let where_clause = if !excluded.is_empty() {
-        format!(" AND tracks.lyrics_status NOT IN ({})", excluded.join(", "))
-    } else {
-        String::new()
-    };
-
-    let order = get_order_clause(sort_by, sort_order);
-    let full_query = format!("{}{} {}", base_query, where_clause, order);
-
-    let mut statement = db.prepare(&full_query)?;
-    let formatted_query_str = format!("%{}%", prepare_input(query_str));
-    let mut rows = statement.query(params![
-        formatted_query_str,
-        formatted_query_str,
-        formatted_query_str
-    ])?;
+pub fn get_tracks_by_genre(genre: &str, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare("SELECT id FROM tracks WHERE genre = ? ORDER BY title_lower ASC")?;
+    let mut rows = statement.query([genre])?;
     let mut track_ids: Vec<i64> = Vec::new();
 
     while let Some(row) = rows.next()? {
@@ -771,7 +1880,7 @@ pub fn get_search_track_ids(
 pub fn get_albums(db: &Connection) -> Result<Vec<PersistentAlbum>> {
     let mut statement = db.prepare(indoc! {"
       SELECT albums.id, albums.name, albums.album_artist_name AS album_artist_name, albums.album_artist_name,
-          albums.image_path, COUNT(tracks.id) AS tracks_count
+          albums.image_path, albums.year, COUNT(tracks.id) AS tracks_count
       FROM albums
       JOIN tracks ON tracks.album_id = albums.id
       GROUP BY albums.id, albums.name, albums.album_artist_name
@@ -788,6 +1897,7 @@ pub fn get_albums(db: &Connection) -> Result<Vec<PersistentAlbum>> {
             artist_name: row.get("album_artist_name")?,
             album_artist_name: row.get("album_artist_name")?,
             tracks_count: row.get("tracks_count")?,
+            year: row.get("year")?,
         };
 
         albums.push(album);
@@ -796,12 +1906,13 @@ pub fn get_albums(db: &Connection) -> Result<Vec<PersistentAlbum>> {
     Ok(albums)
 }
 
-pub fn get_album_by_id(id: i64, db: &Connection) -> Result<PersistentAlbum> {
+pub fn get_album_by_id(id: i64, db: &Connection) -> Result<PersistentAlbum, DbError> {
     let mut statement = db.prepare(indoc! {"
     SELECT
       albums.id,
       albums.name,
       albums.album_artist_name,
+      albums.year,
       COUNT(tracks.id) AS tracks_count
     FROM albums
     JOIN tracks ON tracks.album_id = albums.id
@@ -820,18 +1931,54 @@ pub fn get_album_by_id(id: i64, db: &Connection) -> Result<PersistentAlbum> {
             artist_name: row.get("album_artist_name")?,
             album_artist_name: row.get("album_artist_name")?,
             tracks_count: row.get("tracks_count")?,
+            year: row.get("year")?,
         })
-    })?;
+    })
+    .map_err(|err| DbError::from_query(err, "album", id))?;
     Ok(row)
 }
 
-pub fn get_album_ids(search_query: Option<&str>, db: &Connection) -> Result<Vec<i64>> {
+/// Each album paired with the file path of its first track, ordered by path, to read cover art from.
+pub fn get_album_cover_sources(db: &Connection) -> Result<Vec<(i64, String)>> {
+    let mut statement = db.prepare("SELECT album_id, MIN(file_path) AS file_path FROM tracks GROUP BY album_id")?;
+    let mut rows = statement.query([])?;
+    let mut sources: Vec<(i64, String)> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        sources.push((row.get("album_id")?, row.get("file_path")?));
+    }
+
+    Ok(sources)
+}
+
+pub fn update_album_image_path(album_id: i64, image_path: Option<&str>, db: &Connection) -> Result<()> {
+    db.execute("UPDATE albums SET image_path = ? WHERE id = ?", params![image_path, album_id])?;
+    Ok(())
+}
+
+pub fn get_album_image_path(album_id: i64, db: &Connection) -> Result<Option<String>> {
+    let image_path = db
+        .query_row("SELECT image_path FROM albums WHERE id = ?", [album_id], |row| row.get(0))
+        .optional()?
+        .flatten();
+    Ok(image_path)
+}
+
+pub fn get_album_ids(search_query: Option<&str>, sort_by: &str, sort_order: &str, db: &Connection) -> Result<Vec<i64>> {
+    let order = if sort_by == "year" {
+        let direction = if sort_order == "desc" { "DESC" } else { "ASC" };
+        format!("ORDER BY albums.year {}, albums.name_lower ASC", direction)
+    } else {
+        "ORDER BY albums.name_lower ASC".to_owned()
+    };
+
     let album_ids = match search_query {
         Some(query) => {
-            let like_query = format!("%{}%", prepare_input(query));
-            let mut statement = db.prepare(
-                "SELECT id FROM albums WHERE name_lower LIKE ?1 OR album_artist_name_lower LIKE ?1 ORDER BY name_lower ASC"
-            )?;
+            let like_query = format!("%{}%", escape_like(&prepare_input(query), '\\'));
+            let mut statement = db.prepare(&format!(
+                "SELECT id FROM albums WHERE name_lower LIKE ?1 ESCAPE '\\' OR album_artist_name_lower LIKE ?1 ESCAPE '\\' {}",
+                order
+            ))?;
             let mut rows = statement.query([&like_query])?;
             let mut ids: Vec<i64> = Vec::new();
             while let Some(row) = rows.next()? {
@@ -840,7 +1987,7 @@ pub fn get_album_ids(search_query: Option<&str>, db: &Connection) -> Result<Vec<
             ids
         }
         None => {
-            let mut statement = db.prepare("SELECT id FROM albums ORDER BY name_lower ASC")?;
+            let mut statement = db.prepare(&format!("SELECT id FROM albums {}", order))?;
             let mut rows = statement.query([])?;
             let mut ids: Vec<i64> = Vec::new();
             while let Some(row) = rows.next()? {
@@ -852,6 +1999,56 @@ pub fn get_album_ids(search_query: Option<&str>, db: &Connection) -> Result<Vec<
     Ok(album_ids)
 }
 
+/// Albums credited to the artist as album artist, plus albums holding any of the artist's tracks.
+pub fn get_artist_albums(artist_id: i64, db: &Connection) -> Result<Vec<PersistentAlbum>> {
+    let mut statement = db.prepare(indoc! {"
+      SELECT albums.id, albums.name, albums.album_artist_name,
+          albums.image_path, albums.year, COUNT(tracks.id) AS tracks_count
+      FROM albums
+      JOIN tracks ON tracks.album_id = albums.id
+      WHERE albums.album_artist_name_lower = (SELECT name_lower FROM artists WHERE id = ?1)
+        OR albums.id IN (SELECT album_id FROM tracks WHERE artist_id = ?1)
+      GROUP BY albums.id, albums.name, albums.album_artist_name
+      ORDER BY albums.year ASC, albums.name_lower ASC
+  "})?;
+    let mut rows = statement.query([artist_id])?;
+    let mut albums: Vec<PersistentAlbum> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let album = PersistentAlbum {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            image_path: row.get("image_path")?,
+            artist_name: row.get("album_artist_name")?,
+            album_artist_name: row.get("album_artist_name")?,
+            tracks_count: row.get("tracks_count")?,
+            year: row.get("year")?,
+        };
+
+        albums.push(album);
+    }
+
+    Ok(albums)
+}
+
+pub fn get_artist_album_ids(artist_id: i64, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare(indoc! {"
+      SELECT albums.id
+      FROM albums
+      WHERE albums.album_artist_name_lower = (SELECT name_lower FROM artists WHERE id = ?1)
+        OR albums.id IN (SELECT album_id FROM tracks WHERE artist_id = ?1)
+      ORDER BY albums.year ASC, albums.name_lower ASC
+  "})?;
+    let mut rows = statement.query([artist_id])?;
+    let mut album_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        album_ids.push(row.get("id")?);
+    }
+
+    Ok(album_ids)
+}
+
 pub fn get_artists(db: &Connection) -> Result<Vec<PersistentArtist>> {
     let mut statement = db.prepare(indoc! {"
     SELECT artists.id, artists.name AS name, COUNT(tracks.id) AS tracks_count
@@ -877,7 +2074,7 @@ pub fn get_artists(db: &Connection) -> Result<Vec<PersistentArtist>> {
     Ok(artists)
 }
 
-pub fn get_artist_by_id(id: i64, db: &Connection) -> Result<PersistentArtist> {
+pub fn get_artist_by_id(id: i64, db: &Connection) -> Result<PersistentArtist, DbError> {
     let mut statement = db.prepare(indoc! {"
     SELECT artists.id,
       artists.name AS name,
@@ -895,16 +2092,17 @@ pub fn get_artist_by_id(id: i64, db: &Connection) -> Result<PersistentArtist> {
             // albums_count: row.get("albums_count")?,
             tracks_count: row.get("tracks_count")?,
         })
-    })?;
+    })
+    .map_err(|err| DbError::from_query(err, "artist", id))?;
     Ok(row)
 }
 
 pub fn get_artist_ids(search_query: Option<&str>, db: &Connection) -> Result<Vec<i64>> {
     let artist_ids = match search_query {
         Some(query) => {
-            let like_query = format!("%{}%", prepare_input(query));
+            let like_query = format!("%{}%", escape_like(&prepare_input(query), '\\'));
             let mut statement = db.prepare(
-                "SELECT id FROM artists WHERE name_lower LIKE ?1 ORDER BY name_lower ASC"
+                "SELECT id FROM artists WHERE name_lower LIKE ?1 ESCAPE '\\' ORDER BY name_lower ASC"
             )?;
             let mut rows = statement.query([&like_query])?;
             let mut ids: Vec<i64> = Vec::new();
@@ -927,61 +2125,45 @@ pub fn get_artist_ids(search_query: Option<&str>, db: &Connection) -> Result<Vec
 }
 
 pub fn get_album_tracks(album_id: i64, db: &Connection) -> Result<Vec<PersistentTrack>> {
-    let mut statement = db.prepare(indoc! {"
-    SELECT
-      tracks.id,
-      file_path,
-      file_name,
-      title,
-      artists.name AS artist_name,
-      tracks.artist_id,
-      albums.name AS album_name,
-      albums.album_artist_name,
-      album_id,
-      duration,
-      track_number,
-      albums.image_path,
-      txt_lyrics,
-      lrc_lyrics,
-      instrumental,
-      bitrate
+    let mut statement = db.prepare(&formatdoc! {"
+    SELECT {}
     FROM tracks
     JOIN albums ON tracks.album_id = albums.id
     JOIN artists ON tracks.artist_id = artists.id
     WHERE tracks.album_id = ?
-    ORDER BY track_number ASC
-  "})?;
+    ORDER BY COALESCE(disc_number, 999) ASC, track_number ASC
+  ", TRACK_COLUMNS})?;
     let mut rows = statement.query([album_id])?;
     let mut tracks: Vec<PersistentTrack> = Vec::new();
 
     while let Some(row) = rows.next()? {
-        let is_instrumental: Option<bool> = row.get("instrumental")?;
-
-        let track = PersistentTrack {
-            id: row.get("id")?,
-            file_path: row.get("file_path")?,
-            file_name: row.get("file_name")?,
-            title: row.get("title")?,
-            artist_name: row.get("artist_name")?,
-            album_artist_name: row.get("album_artist_name")?,
-            album_name: row.get("album_name")?,
-            album_id: row.get("album_id")?,
-            artist_id: row.get("artist_id")?,
-            duration: row.get("duration")?,
-            track_number: row.get("track_number")?,
-            txt_lyrics: row.get("txt_lyrics")?,
-            lrc_lyrics: row.get("lrc_lyrics")?,
-            image_path: row.get("image_path")?,
-            instrumental: is_instrumental.unwrap_or(false),
-            bitrate: row.get("bitrate")?,
-        };
-
-        tracks.push(track);
+        tracks.push(track_from_row(row)?);
     }
 
     Ok(tracks)
 }
 
+/// Builds the `lyrics_status` condition for the album and artist track lists, which the
+/// "skip tracks with lyrics" downloads use to pick what to fetch:
+///
+/// | without_plain | without_synced | kept statuses                    |
+/// |---------------|----------------|----------------------------------|
+/// | false         | false          | all                              |
+/// | false         | true           | `missing`, `plain`               |
+/// | true          | false          | `missing`, `instrumental`        |
+/// | true          | true           | `missing`                        |
+///
+/// Synced tracks always carry plain text as well, so `without_plain_lyrics` drops them too.
+/// Instrumental tracks have no plain text, but count as done once synced lyrics are skipped.
+fn lyrics_filter_clause(without_plain_lyrics: bool, without_synced_lyrics: bool) -> &'static str {
+    match (without_plain_lyrics, without_synced_lyrics) {
+        (false, false) => "",
+        (false, true) => " AND tracks.lyrics_status IN ('missing', 'plain')",
+        (true, false) => " AND tracks.lyrics_status IN ('missing', 'instrumental')",
+        (true, true) => " AND tracks.lyrics_status = 'missing'",
+    }
+}
+
 pub fn get_album_track_ids(album_id: i64, without_plain_lyrics: bool, without_synced_lyrics: bool, sort_by: &str, sort_order: &str, db: &Connection) -> Result<Vec<i64>> {
     let base_query = indoc! {"
       SELECT tracks.id
@@ -989,14 +2171,7 @@ pub fn get_album_track_ids(album_id: i64, without_plain_lyrics: bool, without_sy
       JOIN albums ON tracks.album_id = albums.id
       WHERE tracks.album_id = ?"};
 
-    // without_plain = only tracks without txt_lyrics (= 'missing', since synced always has txt)
-    // without_synced = only tracks without lrc_lyrics (= 'missing' + 'plain')
-    let lyrics_conditions = match (without_plain_lyrics, without_synced_lyrics) {
-        (true, true) => " AND tracks.lyrics_status = 'missing'",
-        (true, false) => " AND tracks.lyrics_status = 'missing'",
-        (false, true) => " AND tracks.lyrics_status IN ('missing', 'plain')",
-        (false, false) => "",
-    };
+    let lyrics_conditions = lyrics_filter_clause(without_plain_lyrics, without_synced_lyrics);
 
     let order = get_order_clause(sort_by, sort_order);
     let full_query = format!("{}{} {}",
@@ -1014,42 +2189,19 @@ pub fn get_album_track_ids(album_id: i64, without_plain_lyrics: bool, without_sy
 }
 
 pub fn get_artist_tracks(artist_id: i64, db: &Connection) -> Result<Vec<PersistentTrack>> {
-    let mut statement = db.prepare(indoc! {"
-      SELECT tracks.id, file_path, file_name, title, artists.name AS artist_name,
-        tracks.artist_id, albums.name AS album_name, albums.album_artist_name, album_id, duration, track_number,
-        albums.image_path, txt_lyrics, lrc_lyrics, instrumental, bitrate
+    let mut statement = db.prepare(&formatdoc! {"
+      SELECT {}
       FROM tracks
       JOIN albums ON tracks.album_id = albums.id
       JOIN artists ON tracks.artist_id = artists.id
       WHERE tracks.artist_id = ?
       ORDER BY album_name_lower ASC, track_number ASC
-  "})?;
+  ", TRACK_COLUMNS})?;
     let mut rows = statement.query([artist_id])?;
     let mut tracks: Vec<PersistentTrack> = Vec::new();
 
     while let Some(row) = rows.next()? {
-        let is_instrumental: Option<bool> = row.get("instrumental")?;
-
-        let track = PersistentTrack {
-            id: row.get("id")?,
-            file_path: row.get("file_path")?,
-            file_name: row.get("file_name")?,
-            title: row.get("title")?,
-            artist_name: row.get("artist_name")?,
-            artist_id: row.get("artist_id")?,
-            album_name: row.get("album_name")?,
-            album_artist_name: row.get("album_artist_name")?,
-            album_id: row.get("album_id")?,
-            duration: row.get("duration")?,
-            track_number: row.get("track_number")?,
-            txt_lyrics: row.get("txt_lyrics")?,
-            lrc_lyrics: row.get("lrc_lyrics")?,
-            image_path: row.get("image_path")?,
-            instrumental: is_instrumental.unwrap_or(false),
-            bitrate: row.get("bitrate")?,
-        };
-
-        tracks.push(track);
+        tracks.push(track_from_row(row)?);
     }
 
     Ok(tracks)
@@ -1063,12 +2215,7 @@ pub fn get_artist_track_ids(artist_id: i64, without_plain_lyrics: bool, without_
       JOIN artists ON tracks.artist_id = artists.id
       WHERE tracks.artist_id = ?"};
 
-    let lyrics_conditions = match (without_plain_lyrics, without_synced_lyrics) {
-        (true, true) => " AND tracks.lyrics_status = 'missing'",
-        (true, false) => " AND tracks.lyrics_status = 'missing'",
-        (false, true) => " AND tracks.lyrics_status IN ('missing', 'plain')",
-        (false, false) => "",
-    };
+    let lyrics_conditions = lyrics_filter_clause(without_plain_lyrics, without_synced_lyrics);
 
     let order = get_order_clause(sort_by, sort_order);
     let full_query = format!("{}{} {}",
@@ -1085,10 +2232,15 @@ pub fn get_artist_track_ids(artist_id: i64, without_plain_lyrics: bool, without_
     Ok(tracks)
 }
 
+/// Empties the library and marks it as uninitialized, all in one transaction. Playlists keep
+/// their entries and pick the tracks up again once the same files are scanned back in.
 pub fn clean_library(db: &Connection) -> Result<()> {
-    db.execute("DELETE FROM tracks WHERE 1", ())?;
-    db.execute("DELETE FROM albums WHERE 1", ())?;
-    db.execute("DELETE FROM artists WHERE 1", ())?;
+    let tx = db.unchecked_transaction()?;
+    tx.execute("DELETE FROM tracks WHERE 1", ())?;
+    tx.execute("DELETE FROM albums WHERE 1", ())?;
+    tx.execute("DELETE FROM artists WHERE 1", ())?;
+    tx.execute("UPDATE library_data SET init = 0 WHERE 1", ())?;
+    tx.commit()?;
     Ok(())
 }
 
@@ -1102,9 +2254,14 @@ pub fn get_existing_file_paths(db: &Connection) -> Result<std::collections::Hash
     Ok(paths)
 }
 
-pub fn delete_tracks_not_in(file_paths: &std::collections::HashSet<String>, db: &Connection) -> Result<usize> {
-    let all_db_paths = get_existing_file_paths(db)?;
-    let to_delete: Vec<&String> = all_db_paths.iter().filter(|p| !file_paths.contains(*p)).collect();
+/// Deletes the tracks among `existing_paths`, as returned by `get_existing_file_paths`, that are
+/// missing from `file_paths`.
+pub fn delete_tracks_not_in(
+    file_paths: &std::collections::HashSet<String>,
+    existing_paths: &std::collections::HashSet<String>,
+    db: &Connection,
+) -> Result<usize> {
+    let to_delete: Vec<&String> = existing_paths.iter().filter(|p| !file_paths.contains(*p)).collect();
     let count = to_delete.len();
 
     if count > 0 {
@@ -1120,6 +2277,21 @@ pub fn delete_tracks_not_in(file_paths: &std::collections::HashSet<String>, db:
     Ok(count)
 }
 
+pub fn delete_track(id: i64, db: &Connection) -> Result<()> {
+    db.execute("DELETE FROM tracks WHERE id = ?", [id])?;
+    Ok(())
+}
+
+/// Removes the track record, along with its album and artist when nothing else references them.
+pub fn delete_track_by_id(id: i64, db: &Connection) -> Result<()> {
+    let deleted = db.execute("DELETE FROM tracks WHERE id = ?", [id])?;
+    if deleted > 0 {
+        delete_orphan_albums(db)?;
+        delete_orphan_artists(db)?;
+    }
+    Ok(())
+}
+
 pub fn delete_orphan_albums(db: &Connection) -> Result<usize> {
     let count = db.execute(
         "DELETE FROM albums WHERE id NOT IN (SELECT DISTINCT album_id FROM tracks)",
@@ -1135,3 +2307,624 @@ pub fn delete_orphan_artists(db: &Connection) -> Result<usize> {
     )?;
     Ok(count)
 }
+
+pub fn get_playlists(db: &Connection) -> Result<Vec<PersistentPlaylist>> {
+    let mut statement = db.prepare(indoc! {"
+      SELECT playlists.id, playlists.name, playlists.created_at, COUNT(tracks.id) AS tracks_count
+      FROM playlists
+      LEFT JOIN playlist_tracks ON playlist_tracks.playlist_id = playlists.id
+      LEFT JOIN tracks ON tracks.file_path = playlist_tracks.file_path
+      GROUP BY playlists.id, playlists.name, playlists.created_at
+      ORDER BY playlists.name COLLATE NOCASE ASC
+  "})?;
+    let mut rows = statement.query([])?;
+    let mut playlists: Vec<PersistentPlaylist> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let playlist = PersistentPlaylist {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            created_at: row.get("created_at")?,
+            tracks_count: row.get("tracks_count")?,
+        };
+
+        playlists.push(playlist);
+    }
+
+    Ok(playlists)
+}
+
+pub fn create_playlist(name: &str, db: &Connection) -> Result<i64> {
+    let mut statement =
+        db.prepare("INSERT INTO playlists (name, created_at) VALUES (?, strftime('%s', 'now'))")?;
+    let row_id = statement.insert([name])?;
+    Ok(row_id)
+}
+
+pub fn rename_playlist(id: i64, name: &str, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("UPDATE playlists SET name = ? WHERE id = ?")?;
+    statement.execute((name, id))?;
+    Ok(())
+}
+
+pub fn delete_playlist(id: i64, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare("DELETE FROM playlists WHERE id = ?")?;
+    statement.execute([id])?;
+    Ok(())
+}
+
+/// Appends the track to the end of the playlist. Adding a track that is already in the playlist is a no-op.
+/// Entries are stored by file path, so they outlive the track row when the library is re-initialized.
+pub fn add_track_to_playlist(playlist_id: i64, track_id: i64, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare(indoc! {"
+      INSERT OR IGNORE INTO playlist_tracks (playlist_id, file_path, position)
+      SELECT ?1, tracks.file_path, (SELECT COALESCE(MAX(position) + 1, 0) FROM playlist_tracks WHERE playlist_id = ?1)
+      FROM tracks WHERE tracks.id = ?2
+  "})?;
+    statement.execute((playlist_id, track_id))?;
+    Ok(())
+}
+
+pub fn remove_track_from_playlist(playlist_id: i64, track_id: i64, db: &Connection) -> Result<()> {
+    let mut statement = db.prepare(
+        "DELETE FROM playlist_tracks WHERE playlist_id = ? AND file_path = (SELECT file_path FROM tracks WHERE id = ?)",
+    )?;
+    statement.execute((playlist_id, track_id))?;
+    Ok(())
+}
+
+/// The playlist's tracks in order. Entries whose file is not currently in the library are skipped.
+pub fn get_playlist_track_ids(playlist_id: i64, db: &Connection) -> Result<Vec<i64>> {
+    let mut statement = db.prepare(indoc! {"
+      SELECT tracks.id
+      FROM playlist_tracks
+      JOIN tracks ON tracks.file_path = playlist_tracks.file_path
+      WHERE playlist_tracks.playlist_id = ?
+      ORDER BY playlist_tracks.position ASC
+  "})?;
+    let mut rows = statement.query([playlist_id])?;
+    let mut track_ids: Vec<i64> = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        track_ids.push(row.get("id")?);
+    }
+
+    Ok(track_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let mut db = Connection::open_in_memory().unwrap();
+        upgrade_database_if_needed(&mut db, 0).unwrap();
+        db.pragma_update(None, "foreign_keys", "ON").unwrap();
+        db
+    }
+
+    fn insert_track(db: &Connection, title: &str, album_id: i64, artist_id: i64, disc_number: Option<i64>, track_number: Option<i64>) {
+        db.execute(
+            "INSERT INTO tracks (file_path, file_name, title, title_lower, album_id, artist_id, duration, track_number, disc_number) VALUES (?, ?, ?, ?, ?, ?, 0, ?, ?)",
+            params![title, title, title, prepare_input(title), album_id, artist_id, track_number, disc_number],
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_multi_disc_album_ordering() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Box Set", "Artist", None, &db).unwrap();
+
+        insert_track(&db, "2-02", album_id, artist_id, Some(2), Some(2));
+        insert_track(&db, "1-02", album_id, artist_id, Some(1), Some(2));
+        insert_track(&db, "2-01", album_id, artist_id, Some(2), Some(1));
+        insert_track(&db, "1-01", album_id, artist_id, Some(1), Some(1));
+        insert_track(&db, "Bonus", album_id, artist_id, None, Some(1));
+
+        let titles: Vec<String> = get_album_tracks(album_id, &db).unwrap()
+            .into_iter()
+            .map(|track| track.title)
+            .collect();
+        assert_eq!(titles, vec!["1-01", "1-02", "2-01", "2-02", "Bonus"]);
+
+        let ids = get_album_track_ids(album_id, false, false, "disc_track", "asc", &db).unwrap();
+        let titles: Vec<String> = ids.iter()
+            .map(|id| get_track_by_id(*id, &db).unwrap().title)
+            .collect();
+        assert_eq!(titles, vec!["1-01", "1-02", "2-01", "2-02", "Bonus"]);
+    }
+
+    #[test]
+    fn test_track_ids_lyrics_filter() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Missing", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Synced", album_id, artist_id, None, Some(2));
+        insert_track(&db, "Favorite", album_id, artist_id, None, Some(3));
+        update_track_synced_lyrics(2, "[00:01.00]Line", "Line", None, &db).unwrap();
+        set_track_favorite(3, true, &db).unwrap();
+
+        let only_missing = LyricsFilter { synced: false, ..LyricsFilter::ALL };
+        assert_eq!(get_track_ids(LyricsFilter::ALL, false, "track_number", "asc", &db).unwrap(), vec![1, 2, 3]);
+        assert_eq!(get_track_ids(only_missing, false, "track_number", "asc", &db).unwrap(), vec![1, 3]);
+        assert_eq!(get_track_ids(only_missing, true, "track_number", "asc", &db).unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn test_album_and_artist_track_ids_lyrics_filters() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Missing", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Plain", album_id, artist_id, None, Some(2));
+        insert_track(&db, "Synced", album_id, artist_id, None, Some(3));
+        insert_track(&db, "Instrumental", album_id, artist_id, None, Some(4));
+        update_track_plain_lyrics(2, "Line", None, &db).unwrap();
+        update_track_synced_lyrics(3, "[00:01.00]Line", "Line", None, &db).unwrap();
+        update_track_instrumental(4, &db).unwrap();
+
+        let cases = [
+            (false, false, vec!["Missing", "Plain", "Synced", "Instrumental"]),
+            (false, true, vec!["Missing", "Plain"]),
+            (true, false, vec!["Missing", "Instrumental"]),
+            (true, true, vec!["Missing"]),
+        ];
+        for (without_plain, without_synced, expected) in cases {
+            for ids in [
+                get_album_track_ids(album_id, without_plain, without_synced, "track_number", "asc", &db).unwrap(),
+                get_artist_track_ids(artist_id, without_plain, without_synced, "track_number", "asc", &db).unwrap(),
+            ] {
+                let titles: Vec<String> = ids.iter()
+                    .map(|id| get_track_by_id(*id, &db).unwrap().title)
+                    .collect();
+                assert_eq!(titles, expected, "without_plain={}, without_synced={}", without_plain, without_synced);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_track_ids_fts() {
+        let db = test_db();
+        let bjork_id = add_artist("Björk", &db).unwrap();
+        let homogenic_id = add_album("Homogenic", "Björk", None, &db).unwrap();
+        insert_track(&db, "Jóga", homogenic_id, bjork_id, None, Some(1));
+        insert_track(&db, "Hunter", homogenic_id, bjork_id, None, Some(2));
+
+        let search = |query: &str, fuzzy_search_enabled: bool| -> Vec<String> {
+            get_search_track_ids(&query.to_owned(), LyricsFilter::ALL, false, fuzzy_search_enabled, "title", "asc", &db)
+                .unwrap()
+                .iter()
+                .map(|id| get_track_by_id(*id, &db).unwrap().title)
+                .collect()
+        };
+
+        assert_eq!(search("bjork joga", true), vec!["Jóga"]);
+        assert_eq!(search("Jóga Björk", true), vec!["Jóga"]);
+        assert_eq!(search("hom", true), vec!["Hunter", "Jóga"]);
+        assert_eq!(search("", true), vec!["Hunter", "Jóga"]);
+        // Query syntax in the input is matched as text instead of failing
+        assert!(search("joga\" OR NEAR(", true).is_empty());
+        // Word order only matters for the LIKE search
+        assert!(search("joga bjork", false).is_empty());
+        assert_eq!(search("hunt", false), vec!["Hunter"]);
+
+        delete_track_by_id(1, &db).unwrap();
+        assert_eq!(search("hom", true), vec!["Hunter"]);
+    }
+
+    #[test]
+    fn test_get_track_by_isrc() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Tagged", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Untagged", album_id, artist_id, None, Some(2));
+        db.execute("UPDATE tracks SET isrc = 'USRC17607839' WHERE title = 'Tagged'", ()).unwrap();
+
+        let track = get_track_by_isrc("USRC17607839", &db).unwrap().unwrap();
+        assert_eq!(track.title, "Tagged");
+        assert_eq!(track.isrc.as_deref(), Some("USRC17607839"));
+        assert!(get_track_by_isrc("GBAYE0601498", &db).unwrap().is_none());
+
+        let summaries = get_track_summaries(&db).unwrap();
+        assert_eq!(summaries.iter().filter(|track| track.isrc.is_some()).count(), 1);
+    }
+
+    #[test]
+    fn test_delete_tracks_not_in() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Kept", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Gone", album_id, artist_id, None, Some(2));
+
+        let existing_paths = get_existing_file_paths(&db).unwrap();
+        let disk_paths = std::collections::HashSet::from(["Kept".to_owned()]);
+        assert_eq!(delete_tracks_not_in(&disk_paths, &existing_paths, &db).unwrap(), 1);
+        assert_eq!(get_existing_file_paths(&db).unwrap(), disk_paths);
+    }
+
+    #[test]
+    fn test_unique_file_path() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Once", album_id, artist_id, None, Some(1));
+
+        let insert = |verb: &str| db.execute(
+            &format!("{} INTO tracks (file_path, file_name, title, title_lower, album_id, artist_id, duration) VALUES ('Once', 'Once', 'Again', 'again', ?, ?, 0)", verb),
+            params![album_id, artist_id],
+        );
+        assert!(insert("INSERT").is_err());
+        assert_eq!(insert("INSERT OR IGNORE").unwrap(), 0);
+        assert_eq!(count_tracks_total(&db).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_stale_lyrics_track_ids() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Old", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Fresh", album_id, artist_id, None, Some(2));
+        insert_track(&db, "Never", album_id, artist_id, None, Some(3));
+
+        update_track_plain_lyrics(1, "Line", None, &db).unwrap();
+        let fresh = update_track_instrumental(2, &db).unwrap();
+        assert!(fresh.lyrics_last_updated.is_some());
+        db.execute("UPDATE tracks SET lyrics_last_updated = lyrics_last_updated - 40 * 86400 WHERE id = 1", ()).unwrap();
+
+        assert_eq!(get_stale_lyrics_track_ids(30, &db).unwrap(), vec![1]);
+        assert_eq!(get_stale_lyrics_track_ids(60, &db).unwrap(), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn test_a2_lyrics_follow_synced_lyrics() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Song", album_id, artist_id, None, Some(1));
+
+        let track = update_track_synced_lyrics(1, "[00:01.00]<00:01.00>Hello <00:01.50>world", "Hello world", None, &db).unwrap();
+        assert_eq!(
+            track.a2_lyrics.as_deref(),
+            Some(r#"[{"timestamp_ms":1000,"words":[{"timestamp_ms":1000,"word":"Hello"},{"timestamp_ms":1500,"word":"world"}]}]"#)
+        );
+
+        let track = update_track_synced_lyrics(1, "[00:01.00]Hello world", "Hello world", None, &db).unwrap();
+        assert_eq!(track.a2_lyrics, None);
+    }
+
+    #[test]
+    fn test_album_and_artist_ids_search() {
+        let db = test_db();
+        let beatles_id = add_artist("The Beatles", &db).unwrap();
+        let doors_id = add_artist("The Doors", &db).unwrap();
+        add_artist("Beach House", &db).unwrap();
+        let abbey_road_id = add_album("Abbey Road", "The Beatles", None, &db).unwrap();
+        add_album("L.A. Woman", "The Doors", None, &db).unwrap();
+
+        assert_eq!(get_artist_ids(Some("beat"), &db).unwrap(), vec![beatles_id]);
+        assert_eq!(get_artist_ids(Some("DOORS"), &db).unwrap(), vec![doors_id]);
+        assert_eq!(get_artist_ids(Some("the"), &db).unwrap(), vec![beatles_id, doors_id]);
+        assert_eq!(get_artist_ids(None, &db).unwrap().len(), 3);
+
+        assert_eq!(get_album_ids(Some("abbey"), "name", "asc", &db).unwrap(), vec![abbey_road_id]);
+        assert_eq!(get_album_ids(Some("beatles"), "name", "asc", &db).unwrap(), vec![abbey_road_id]);
+    }
+
+    #[test]
+    fn test_get_tracks_paginated() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        for title in ["Delta", "Alpha", "Echo", "Charlie", "Bravo"] {
+            insert_track(&db, title, album_id, artist_id, None, None);
+        }
+
+        let page_titles = |offset, limit| -> Vec<String> {
+            get_tracks_paginated(offset, limit, "title", "asc", &db).unwrap()
+                .into_iter()
+                .map(|track| track.title)
+                .collect()
+        };
+        assert_eq!(page_titles(0, 2), vec!["Alpha", "Bravo"]);
+        assert_eq!(page_titles(2, 2), vec!["Charlie", "Delta"]);
+        assert_eq!(page_titles(4, 2), vec!["Echo"]);
+        assert!(page_titles(6, 2).is_empty());
+        assert_eq!(count_tracks_total(&db).unwrap(), 5);
+
+        let summary_titles: Vec<String> = get_track_summaries(&db)
+            .unwrap()
+            .into_iter()
+            .map(|track| track.title)
+            .collect();
+        assert_eq!(summary_titles, vec!["Alpha", "Bravo", "Charlie", "Delta", "Echo"]);
+    }
+
+    #[test]
+    fn test_recently_added_track_ids() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        for (title, added_at) in [("Old", 0), ("Newest", 300), ("Newer", 200)] {
+            insert_track(&db, title, album_id, artist_id, None, None);
+            db.execute("UPDATE tracks SET added_at = ? WHERE title = ?", params![added_at, title]).unwrap();
+        }
+
+        let ids = get_recently_added_track_ids(2, &db).unwrap();
+        let titles: Vec<String> = ids.iter().map(|&id| get_track_by_id(id, &db).unwrap().title).collect();
+        assert_eq!(titles, vec!["Newest", "Newer"]);
+    }
+
+    #[test]
+    fn test_most_played_track_ids() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Once", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Twice", album_id, artist_id, None, Some(2));
+        insert_track(&db, "Never", album_id, artist_id, None, Some(3));
+        db.execute("UPDATE tracks SET play_count = NULL WHERE id = 2", ()).unwrap();
+
+        increment_play_count(1, &db).unwrap();
+        increment_play_count(2, &db).unwrap();
+        increment_play_count(2, &db).unwrap();
+
+        let twice = get_track_by_id(2, &db).unwrap();
+        assert_eq!(twice.play_count, 2);
+        assert!(twice.last_played_at.is_some());
+        assert_eq!(get_most_played_track_ids(10, &db).unwrap(), vec![2, 1]);
+        assert_eq!(get_most_played_track_ids(1, &db).unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn test_tracks_without_lyrics() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Another", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Song", album_id, artist_id, None, Some(2));
+        update_track_synced_lyrics(2, "[00:01.00]Line", "Line", None, &db).unwrap();
+
+        let tracks = get_tracks_without_lyrics(&db).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].title, "Song");
+        assert!(tracks[1].lrc_lyrics.is_none() && tracks[1].txt_lyrics.is_none());
+        assert!(get_track_by_id(2, &db).unwrap().lrc_lyrics.is_some());
+
+        // Sorting still sees the stored lyrics, so the synced track comes before the earlier-added one
+        let page = get_tracks_paginated(0, 10, "lyrics_status", "asc", &db).unwrap();
+        assert_eq!(page[0].title, "Song");
+        assert!(page.iter().all(|track| track.lrc_lyrics.is_none() && track.a2_lyrics.is_none()));
+    }
+
+    #[test]
+    fn test_track_year_independent_of_album_year() {
+        let mut db = test_db();
+        let tracks = vec![
+            fs_track::FsTrack::for_test("oldie.mp3", "Oldie", "Compilation", "Various Artists", Some(1972)),
+            fs_track::FsTrack::for_test("newer.mp3", "Newer", "Compilation", "Various Artists", Some(1995)),
+            fs_track::FsTrack::for_test("undated.mp3", "Undated", "Compilation", "Various Artists", None),
+        ];
+        add_tracks(&tracks, &mut db, &mut Default::default(), &mut Default::default()).unwrap();
+
+        let oldie = get_track_by_file_path("oldie.mp3", &db).unwrap();
+        assert_eq!(oldie.year, Some(1972));
+        assert_eq!(get_albums(&db).unwrap()[0].year, Some(1972));
+
+        let retagged = fs_track::FsTrack::for_test("oldie.mp3", "Oldie", "Compilation", "Various Artists", Some(2001));
+        update_track_metadata(oldie.id, &retagged, &mut db).unwrap();
+        assert_eq!(get_track_by_file_path("oldie.mp3", &db).unwrap().year, Some(2001));
+
+        // The summaries the JSON export is built from carry the track year too
+        let summary = get_track_summaries(&db).unwrap()
+            .into_iter()
+            .find(|track| track.title == "Oldie")
+            .unwrap();
+        assert_eq!(summary.year, Some(2001));
+
+        // Tracks without a year of their own sort by the album's
+        let titles: Vec<String> = get_tracks_paginated(0, 10, "year", "asc", &db).unwrap()
+            .into_iter()
+            .map(|track| track.title)
+            .collect();
+        assert_eq!(titles, vec!["Undated", "Newer", "Oldie"]);
+    }
+
+    #[test]
+    fn test_clean_library_resets_init() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Song", album_id, artist_id, None, Some(1));
+        set_init(true, &db).unwrap();
+
+        clean_library(&db).unwrap();
+
+        assert!(!get_init(&db).unwrap());
+        assert!(get_existing_file_paths(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_artist_albums() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let guest_id = add_artist("Guest", &db).unwrap();
+        let own_album = add_album("Own Album", "Artist", None, &db).unwrap();
+        let compilation = add_album("Compilation", "Various Artists", None, &db).unwrap();
+        let other_album = add_album("Other Album", "Guest", None, &db).unwrap();
+        insert_track(&db, "Solo", own_album, artist_id, None, None);
+        insert_track(&db, "Feature", compilation, artist_id, None, None);
+        insert_track(&db, "Guest Song", other_album, guest_id, None, None);
+
+        let album_ids = get_artist_album_ids(artist_id, &db).unwrap();
+        assert_eq!(album_ids.len(), 2);
+        assert!(album_ids.contains(&own_album) && album_ids.contains(&compilation));
+
+        let names: Vec<String> = get_artist_albums(artist_id, &db).unwrap().into_iter().map(|album| album.name).collect();
+        assert_eq!(names, vec!["Compilation", "Own Album"]);
+        assert_eq!(get_artist_album_ids(guest_id, &db).unwrap(), vec![other_album]);
+    }
+
+    #[test]
+    fn test_library_stats_total_duration() {
+        let db = test_db();
+        assert_eq!(get_library_stats(&db).unwrap().total_duration_seconds, 0.0);
+
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        for (title, duration) in [("One", 180.5), ("Two", 240.0), ("Three", 0.0)] {
+            insert_track(&db, title, album_id, artist_id, None, None);
+            db.execute("UPDATE tracks SET duration = ? WHERE title = ?", params![duration, title]).unwrap();
+        }
+
+        let stats = get_library_stats(&db).unwrap();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.total_duration_seconds, 420.5);
+    }
+
+    #[test]
+    fn test_directory_stats() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        for path in ["/music/a.mp3", "/music/sub/b.mp3", "/music2/c.mp3", "/100%_music/d.mp3", "/100x_music/e.mp3"] {
+            db.execute(
+                "INSERT INTO tracks (file_path, file_name, title, title_lower, album_id, artist_id, duration) VALUES (?, ?, ?, ?, ?, ?, 60)",
+                params![path, path, path, path, album_id, artist_id],
+            ).unwrap();
+        }
+
+        assert_eq!(get_directory_stats("/music", &db).unwrap().total, 2);
+        assert_eq!(get_directory_stats("/music/", &db).unwrap().total, 2);
+        assert_eq!(get_directory_stats("/music2", &db).unwrap().total_duration_seconds, 60.0);
+        assert_eq!(get_directory_stats("/100%_music", &db).unwrap().total, 1);
+        assert_eq!(get_directory_stats("/elsewhere", &db).unwrap().total, 0);
+    }
+
+    #[test]
+    fn test_delete_track_by_id() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let single_id = add_album("Single", "Artist", None, &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Single", single_id, artist_id, None, None);
+        insert_track(&db, "Album Song", album_id, artist_id, None, None);
+        let single_track_id: i64 = db.query_row("SELECT id FROM tracks WHERE title = 'Single'", [], |r| r.get(0)).unwrap();
+
+        delete_track_by_id(single_track_id, &db).unwrap();
+        assert_eq!(count_tracks_total(&db).unwrap(), 1);
+        assert!(get_album_by_id(single_id, &db).is_err());
+        assert!(get_album_by_id(album_id, &db).is_ok());
+        assert!(get_artist_by_id(artist_id, &db).is_ok());
+
+        // Deleting a missing track is a no-op
+        delete_track_by_id(single_track_id, &db).unwrap();
+    }
+
+    #[test]
+    fn test_playlist_tracks() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "First", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Second", album_id, artist_id, None, Some(2));
+
+        let playlist_id = create_playlist("Mix", &db).unwrap();
+        add_track_to_playlist(playlist_id, 2, &db).unwrap();
+        add_track_to_playlist(playlist_id, 1, &db).unwrap();
+        add_track_to_playlist(playlist_id, 2, &db).unwrap();
+        assert_eq!(get_playlist_track_ids(playlist_id, &db).unwrap(), vec![2, 1]);
+        assert_eq!(get_playlists(&db).unwrap()[0].tracks_count, 2);
+
+        remove_track_from_playlist(playlist_id, 2, &db).unwrap();
+        assert_eq!(get_playlist_track_ids(playlist_id, &db).unwrap(), vec![1]);
+
+        delete_playlist(playlist_id, &db).unwrap();
+        let remaining: i64 = db
+            .query_row("SELECT COUNT(*) FROM playlist_tracks", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_playlist_survives_clean_library() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "First", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Second", album_id, artist_id, None, Some(2));
+        let playlist_id = create_playlist("Mix", &db).unwrap();
+        add_track_to_playlist(playlist_id, 2, &db).unwrap();
+        add_track_to_playlist(playlist_id, 1, &db).unwrap();
+
+        clean_library(&db).unwrap();
+        assert!(get_playlist_track_ids(playlist_id, &db).unwrap().is_empty());
+        assert_eq!(get_playlists(&db).unwrap()[0].tracks_count, 0);
+
+        // A re-scan gives the same files new IDs
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Second", album_id, artist_id, None, Some(2));
+        insert_track(&db, "First", album_id, artist_id, None, Some(1));
+        let ids = get_playlist_track_ids(playlist_id, &db).unwrap();
+        let titles: Vec<String> = ids.iter().map(|&id| get_track_by_id(id, &db).unwrap().title).collect();
+        assert_eq!(titles, vec!["Second", "First"]);
+    }
+
+    #[test]
+    fn test_lrclib_fallback_instances() {
+        let db = test_db();
+        let mut config = get_config(&db).unwrap();
+        assert!(config.lrclib_fallback_instances.is_empty());
+        assert_eq!(get_all_lrclib_instances(&db).unwrap(), vec![config.lrclib_instance.clone()]);
+
+        config.lrclib_instance = "https://primary.example".to_owned();
+        config.lrclib_fallback_instances = vec!["https://backup.example".to_owned()];
+        set_config(&config, &db).unwrap();
+        assert_eq!(
+            get_all_lrclib_instances(&db).unwrap(),
+            vec!["https://primary.example", "https://backup.example"]
+        );
+    }
+
+    #[test]
+    fn test_last_volume() {
+        let db = test_db();
+        let config = get_config(&db).unwrap();
+        assert_eq!(config.last_volume, 1.0);
+
+        set_last_volume(0.4, &db).unwrap();
+        // Saving the settings page leaves the volume alone
+        set_config(&config, &db).unwrap();
+        assert_eq!(get_config(&db).unwrap().last_volume, 0.4);
+    }
+
+    #[test]
+    fn test_missing_track_is_not_found() {
+        let db = test_db();
+
+        let result = get_track_by_id(42, &db);
+        assert!(matches!(result, Err(DbError::NotFound { ref entity, ref id }) if entity == "track" && id == "42"));
+    }
+
+    #[test]
+    fn test_find_duplicate_tracks() {
+        let db = test_db();
+        let artist_id = add_artist("Artist", &db).unwrap();
+        let album_id = add_album("Album", "Artist", None, &db).unwrap();
+        insert_track(&db, "Original", album_id, artist_id, None, Some(1));
+        insert_track(&db, "Unique", album_id, artist_id, None, Some(2));
+        insert_track(&db, "Copy", album_id, artist_id, None, Some(1));
+        db.execute("UPDATE tracks SET content_hash = 'aa' WHERE title IN ('Original', 'Copy')", []).unwrap();
+        db.execute("UPDATE tracks SET content_hash = 'bb' WHERE title = 'Unique'", []).unwrap();
+
+        let groups = find_duplicate_tracks(&db).unwrap();
+        assert_eq!(groups.len(), 1);
+        let titles: Vec<&str> = groups[0].iter().map(|track| track.title.as_str()).collect();
+        assert_eq!(titles, vec!["Original", "Copy"]);
+    }
+}
@@ -1,21 +1,33 @@
 use crate::db;
+use crate::lyrics::{a2_lyrics_json, is_a2_lrc};
+use crate::utils::{apply_lrc_offset, normalize_isrc};
 use anyhow::Result;
+use data_encoding::HEXLOWER;
 use globwalk::{glob, DirEntry};
 use id3::TagLike;
 use lofty::config::{ParseOptions, ParsingMode};
 use lofty::error::LoftyError;
 use lofty::file::AudioFile;
-use lofty::file::TaggedFileExt;
+use lofty::file::{FileType, TaggedFileExt};
+use lofty::id3::v2::{ChannelType, Frame};
+use lofty::mpeg::MpegFile;
+use lofty::picture::PictureType;
 use lofty::probe::Probe;
 use lofty::tag::Accessor;
 use rayon::prelude::*;
+use ring::digest::{Context, SHA256};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::Path;
 use std::time::Instant;
 use tauri::{AppHandle, Emitter};
 use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, info_span, warn};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FsTrack {
@@ -28,8 +40,16 @@ pub struct FsTrack {
     duration: f64,
     txt_lyrics: Option<String>,
     lrc_lyrics: Option<String>,
+    a2_lyrics: Option<String>,
     track_number: Option<u32>,
     bitrate: Option<u32>,
+    genre: Option<String>,
+    year: Option<u32>,
+    disc_number: Option<u32>,
+    replaygain_track_gain: Option<f32>,
+    content_hash: Option<String>,
+    composer: Option<String>,
+    isrc: Option<String>,
 }
 
 #[derive(Error, Debug)]
@@ -44,6 +64,10 @@ pub enum FsTrackError {
     ArtistNotFound(String),
     #[error("No primary tag was found from track: `{0}`")]
     PrimaryTagNotFound(String),
+    #[error("Library scan was cancelled")]
+    ScanCancelled,
+    #[error("Reading track `{0}` panicked: `{1}`")]
+    Panicked(String, String),
 }
 
 #[derive(Clone, Serialize)]
@@ -54,6 +78,14 @@ struct ScanProgress {
     files_count: Option<usize>,
 }
 
+/// A file the scan found but could not read as a track.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanError {
+    file_path: String,
+    message: String,
+}
+
 impl FsTrack {
     fn new(
         file_path: String,
@@ -78,8 +110,16 @@ impl FsTrack {
             duration,
             txt_lyrics,
             lrc_lyrics,
+            a2_lyrics: None,
             track_number,
             bitrate,
+            genre: None,
+            year: None,
+            disc_number: None,
+            replaygain_track_gain: None,
+            content_hash: None,
+            composer: None,
+            isrc: None,
         }
     }
 
@@ -96,8 +136,8 @@ impl FsTrack {
             Err(lofty_err) => {
                 // Fallback: lofty failed (often due to corrupt APE tags alongside valid ID3v2).
                 // Use id3 crate for tags, lofty with read_tags(false) for audio properties.
-                println!(
-                    "Warning: lofty failed for `{}`: {}. Trying id3 fallback...",
+                warn!(
+                    "Lofty failed for `{}`: {}. Trying id3 fallback...",
                     file_path, lofty_err
                 );
                 Self::from_id3_fallback(path, &file_path, &file_name, lofty_err)
@@ -109,7 +149,7 @@ impl FsTrack {
         tagged_file: lofty::file::TaggedFile,
         file_path: String,
         file_name: String,
-        _path: &Path,
+        path: &Path,
     ) -> Result<FsTrack> {
         let tag = tagged_file
             .primary_tag()
@@ -140,10 +180,21 @@ impl FsTrack {
             file_path, file_name, title, album, artist, album_artist, duration, None, None,
             track_number, bitrate,
         );
-        let (txt, lrc) = track.read_sidecar_lyrics();
-        track.txt_lyrics = txt;
-        track.lrc_lyrics = lrc;
-
+        track.genre = tag.genre().map(|s| s.to_string());
+        track.composer = tag
+            .get_string(&lofty::tag::ItemKey::Composer)
+            .map(|s| s.to_string());
+        track.isrc = tag
+            .get_string(&lofty::tag::ItemKey::Isrc)
+            .and_then(normalize_isrc);
+        track.year = tag.year();
+        track.disc_number = tag.disk();
+        track.replaygain_track_gain = tag
+            .get_string(&lofty::tag::ItemKey::ReplayGainTrackGain)
+            .and_then(parse_replaygain);
+        if track.replaygain_track_gain.is_none() && tagged_file.file_type() == FileType::Mpeg {
+            track.replaygain_track_gain = read_rva2_track_gain(path);
+        }
         Ok(track)
     }
 
@@ -201,11 +252,22 @@ impl FsTrack {
             track_number,
             bitrate,
         );
-        let (txt, lrc) = track.read_sidecar_lyrics();
-        track.txt_lyrics = txt;
-        track.lrc_lyrics = lrc;
-
-        println!("Successfully loaded `{}` via id3 fallback", file_path);
+        track.genre = id3_tag.genre_parsed().map(|s| s.to_string());
+        track.composer = id3_tag
+            .get("TCOM")
+            .and_then(|frame| frame.content().text())
+            .map(|s| s.to_string());
+        track.isrc = id3_tag
+            .get("TSRC")
+            .and_then(|frame| frame.content().text())
+            .and_then(normalize_isrc);
+        track.year = id3_tag.year().and_then(|year| u32::try_from(year).ok());
+        track.disc_number = id3_tag.disc();
+        track.replaygain_track_gain = id3_tag
+            .extended_texts()
+            .find(|text| text.description.eq_ignore_ascii_case("REPLAYGAIN_TRACK_GAIN"))
+            .and_then(|text| parse_replaygain(&text.value));
+        info!("Successfully loaded `{}` via id3 fallback", file_path);
 
         Ok(track)
     }
@@ -246,6 +308,10 @@ impl FsTrack {
         self.lrc_lyrics.as_deref()
     }
 
+    pub fn a2_lyrics(&self) -> Option<&str> {
+        self.a2_lyrics.as_deref()
+    }
+
     pub fn track_number(&self) -> Option<u32> {
         self.track_number
     }
@@ -254,34 +320,186 @@ impl FsTrack {
         self.bitrate
     }
 
-    /// Returns (txt_lyrics, lrc_lyrics) by parsing the path once
-    fn read_sidecar_lyrics(&self) -> (Option<String>, Option<String>) {
+    pub fn genre(&self) -> Option<&str> {
+        self.genre.as_deref()
+    }
+
+    pub fn year(&self) -> Option<u32> {
+        self.year
+    }
+
+    pub fn disc_number(&self) -> Option<u32> {
+        self.disc_number
+    }
+
+    pub fn replaygain_track_gain(&self) -> Option<f32> {
+        self.replaygain_track_gain
+    }
+
+    pub fn composer(&self) -> Option<&str> {
+        self.composer.as_deref()
+    }
+
+    pub fn isrc(&self) -> Option<&str> {
+        self.isrc.as_deref()
+    }
+
+    pub fn content_hash(&self) -> Option<&str> {
+        self.content_hash.as_deref()
+    }
+
+    /// Returns (txt_lyrics, lrc_lyrics) by parsing the path once. Each file is looked for
+    /// next to the track first, then in its `Lyrics/` subdirectory when that is enabled.
+    /// A2 lyrics are kept as written, since an offset would only shift their line timestamps.
+    fn read_sidecar_lyrics(&self, check_lyrics_subdirectory: bool) -> (Option<String>, Option<String>) {
         let path = Path::new(&self.file_path);
         let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
         let parent = path.parent().unwrap_or(Path::new(""));
 
-        let txt_lyrics = std::fs::read_to_string(parent.join(format!("{}.txt", stem))).ok();
-        let lrc_lyrics = std::fs::read_to_string(parent.join(format!("{}.lrc", stem))).ok();
+        let mut lyrics_directories = vec![parent.to_path_buf()];
+        if check_lyrics_subdirectory {
+            lyrics_directories.extend(LYRICS_SUBDIRECTORIES.iter().map(|subdirectory| parent.join(subdirectory)));
+        }
+        let read_sidecar = |extension: &str| {
+            lyrics_directories
+                .iter()
+                .find_map(|directory| std::fs::read_to_string(directory.join(format!("{}.{}", stem, extension))).ok())
+        };
+
+        let txt_lyrics = read_sidecar("txt");
+        let lrc_lyrics = read_sidecar("lrc")
+            .map(|lyrics| {
+                if is_a2_lrc(&lyrics) {
+                    return lyrics;
+                }
+                match apply_lrc_offset(&lyrics) {
+                    Ok(shifted) => shifted,
+                    Err(e) => {
+                        warn!("Cannot apply LRC offset for `{}`: {}", self.file_path, e);
+                        lyrics
+                    }
+                }
+            });
 
         (txt_lyrics, lrc_lyrics)
     }
 }
 
-fn load_tracks_from_entry_batch(entry_batch: &[DirEntry]) -> Result<Vec<FsTrack>> {
-    let track_results: Vec<Result<FsTrack>> = entry_batch
+/// Image bytes of the file's embedded cover art, preferring the front cover over other pictures.
+/// The regular scan skips cover art, so this reads the tags again with pictures enabled.
+pub fn read_cover_art(path: &Path) -> Option<Vec<u8>> {
+    let opts = ParseOptions::new().read_properties(false);
+    let tagged_file = Probe::open(path).and_then(|p| p.options(opts).read()).ok()?;
+
+    tagged_file.tags().iter().find_map(|tag| {
+        let pictures = tag.pictures();
+        pictures
+            .iter()
+            .find(|picture| picture.pic_type() == PictureType::CoverFront)
+            .or_else(|| pictures.first())
+            .map(|picture| picture.data().to_vec())
+    })
+}
+
+/// Parses a ReplayGain value such as `-6.48 dB` into decibels.
+fn parse_replaygain(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Reads the track gain from an ID3v2 RVA2 frame. Lofty keeps RVA2 out of the generic tag,
+/// so the file has to be read again as MPEG to get at it.
+fn read_rva2_track_gain(path: &Path) -> Option<f32> {
+    let mut file = File::open(path).ok()?;
+    let opts = ParseOptions::new().read_cover_art(false).read_properties(false);
+    let mpeg_file = MpegFile::read_from(&mut file, opts).ok()?;
+
+    mpeg_file.id3v2()?.into_iter().find_map(|frame| match frame {
+        Frame::RelativeVolumeAdjustment(rva2) if !rva2.identification.eq_ignore_ascii_case("album") => rva2
+            .channels
+            .get(&ChannelType::MasterVolume)
+            .map(|channel| f32::from(channel.volume_adjustment) / 512.0),
+        _ => None,
+    })
+}
+
+const CONTENT_HASH_BYTES: u64 = 64 * 1024;
+
+/// Hex-encoded SHA-256 of the first 64 KB of the file. Cheap enough to compute during the scan
+/// and good enough to tell copies of the same file apart from different recordings.
+fn compute_content_hash(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let mut buffer = Vec::with_capacity(CONTENT_HASH_BYTES as usize);
+    file.take(CONTENT_HASH_BYTES).read_to_end(&mut buffer).ok()?;
+
+    let mut context = Context::new(&SHA256);
+    context.update(&buffer);
+
+    Some(HEXLOWER.encode(context.finish().as_ref()))
+}
+
+/// macOS resource forks (`._*`) carry the audio extension of the file they belong to, so
+/// `GLOB_PATTERN` picks them up, but they aren't tracks and lofty only fails on them after
+/// opening the file.
+fn is_system_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|file_name| file_name.starts_with("._"))
+}
+
+/// Reads the tags of a single file, along with its sidecar lyrics and content hash.
+pub fn load_track(path: &Path, check_lyrics_subdirectory: bool) -> Result<FsTrack> {
+    FsTrack::new_from_path(path).map(|mut track| {
+        let (txt, lrc) = track.read_sidecar_lyrics(check_lyrics_subdirectory);
+        track.txt_lyrics = txt;
+        track.a2_lyrics = lrc.as_deref().and_then(a2_lyrics_json);
+        track.lrc_lyrics = lrc;
+        track.content_hash = compute_content_hash(path);
+        track
+    })
+}
+
+/// `load_track`, with a panic while reading the file turned into an error, so one malformed
+/// file doesn't take the rest of its batch down with it.
+fn load_track_catching_panics(path: &Path, check_lyrics_subdirectory: bool) -> Result<FsTrack> {
+    panic::catch_unwind(AssertUnwindSafe(|| load_track(path, check_lyrics_subdirectory))).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_owned());
+        Err(FsTrackError::Panicked(path.display().to_string(), message).into())
+    })
+}
+
+fn load_tracks_from_entry_batch(
+    entry_batch: &[DirEntry],
+    check_lyrics_subdirectory: bool,
+    scan_errors: &mut Vec<ScanError>,
+) -> Result<Vec<FsTrack>> {
+    let track_results: Vec<(&DirEntry, Result<FsTrack>)> = entry_batch
         .par_iter()
-        .map(|file| FsTrack::new_from_path(file.path()))
+        .filter(|file| !is_system_file(file.path()))
+        .map(|file| (file, load_track_catching_panics(file.path(), check_lyrics_subdirectory)))
         .collect();
 
     let mut tracks: Vec<FsTrack> = vec![];
 
-    for track_result in track_results {
+    for (file, track_result) in track_results {
         match track_result {
             Ok(track) => {
                 tracks.push(track);
             }
             Err(error) => {
-                println!("{}", error);
+                warn!("{}", error);
+                scan_errors.push(ScanError {
+                    file_path: file.path().display().to_string(),
+                    message: error.to_string(),
+                });
             }
         }
     }
@@ -289,18 +507,36 @@ fn load_tracks_from_entry_batch(entry_batch: &[DirEntry]) -> Result<Vec<FsTrack>
     Ok(tracks)
 }
 
+/// Sends the files that could not be read as a single `scan-errors` event once the scan is done,
+/// so the frontend can summarize them instead of them only reaching the log.
+fn emit_scan_errors(app_handle: &AppHandle, scan_errors: Vec<ScanError>) {
+    if !scan_errors.is_empty() {
+        warn!("{} files could not be read", scan_errors.len());
+    }
+    app_handle.emit("scan-errors", scan_errors).unwrap();
+}
+
+/// Checked in this order after the track's own directory; on case-insensitive file systems
+/// both name the same directory.
+const LYRICS_SUBDIRECTORIES: [&str; 2] = ["Lyrics", "lyrics"];
+
 const GLOB_PATTERN: &str = "/**/*.{mp3,m4a,flac,ogg,opus,wav,MP3,M4A,FLAC,OGG,OPUS,WAV}";
 
+/// Scans `directories` into the database. `cancel_token` is checked between batches, and a
+/// cancelled scan returns `FsTrackError::ScanCancelled` with the batches so far kept.
 pub fn load_tracks_from_directories(
     directories: &Vec<String>,
+    check_lyrics_subdirectory: bool,
     conn: &mut Connection,
     app_handle: AppHandle,
+    cancel_token: &CancellationToken,
 ) -> Result<()> {
     let now = Instant::now();
 
     // Single filesystem scan: collect all entries, then process in batches
     let mut all_entries: Vec<DirEntry> = Vec::new();
     for directory in directories.iter() {
+        let _span = info_span!("scan", directory = %directory).entered();
         let globwalker = glob(format!("{}{}", directory, GLOB_PATTERN))?;
         for item in globwalker {
             all_entries.push(item?);
@@ -308,15 +544,20 @@ pub fn load_tracks_from_directories(
     }
 
     let files_count = all_entries.len();
-    println!("Files count: {}", files_count);
+    info!("Files count: {}", files_count);
     let mut files_scanned: usize = 0;
 
     // Persistent caches across all batches
     let mut artist_cache: HashMap<String, i64> = HashMap::new();
     let mut album_cache: HashMap<(String, String), i64> = HashMap::new();
+    let mut scan_errors: Vec<ScanError> = Vec::new();
 
     for batch in all_entries.chunks(500) {
-        let tracks = load_tracks_from_entry_batch(batch)?;
+        if cancel_token.is_cancelled() {
+            info!("Library scan cancelled after {} files", files_scanned);
+            return Err(FsTrackError::ScanCancelled.into());
+        }
+        let tracks = load_tracks_from_entry_batch(batch, check_lyrics_subdirectory, &mut scan_errors)?;
         db::add_tracks(&tracks, conn, &mut artist_cache, &mut album_cache)?;
         files_scanned += batch.len();
         let progress = if files_count > 0 {
@@ -336,25 +577,29 @@ pub fn load_tracks_from_directories(
             .unwrap();
     }
 
-    println!("==> Scanning tracks take: {}ms", now.elapsed().as_millis());
+    emit_scan_errors(&app_handle, scan_errors);
+    info!("==> Scanning tracks take: {}ms", now.elapsed().as_millis());
 
     Ok(())
 }
 
 pub fn refresh_tracks_from_directories(
     directories: &Vec<String>,
+    check_lyrics_subdirectory: bool,
     conn: &mut Connection,
     app_handle: AppHandle,
+    cancel_token: &CancellationToken,
 ) -> Result<()> {
     let now = Instant::now();
 
     // Get existing file paths from DB
     let existing_paths = db::get_existing_file_paths(conn)?;
-    println!("Existing tracks in DB: {}", existing_paths.len());
+    info!("Existing tracks in DB: {}", existing_paths.len());
 
     // Scan filesystem
     let mut all_entries: Vec<DirEntry> = Vec::new();
     for directory in directories.iter() {
+        let _span = info_span!("scan", directory = %directory).entered();
         let globwalker = glob(format!("{}{}", directory, GLOB_PATTERN))?;
         for item in globwalker {
             all_entries.push(item?);
@@ -373,27 +618,32 @@ pub fn refresh_tracks_from_directories(
     }
 
     let new_count = new_entries.len();
-    println!("New files to add: {}", new_count);
+    info!("New files to add: {}", new_count);
 
     // Delete tracks that are no longer on disk
-    let deleted = db::delete_tracks_not_in(&disk_paths, conn)?;
-    println!("Removed {} tracks no longer on disk", deleted);
+    let deleted = db::delete_tracks_not_in(&disk_paths, &existing_paths, conn)?;
+    info!("Removed {} tracks no longer on disk", deleted);
 
     // Clean up orphaned albums/artists
     if deleted > 0 {
         let orphan_albums = db::delete_orphan_albums(conn)?;
         let orphan_artists = db::delete_orphan_artists(conn)?;
-        println!("Cleaned up {} orphan albums, {} orphan artists", orphan_albums, orphan_artists);
+        info!("Cleaned up {} orphan albums, {} orphan artists", orphan_albums, orphan_artists);
     }
 
     // Insert new tracks in batches
+    let mut scan_errors: Vec<ScanError> = Vec::new();
     if new_count > 0 {
         let mut files_scanned: usize = 0;
         let mut artist_cache: HashMap<String, i64> = HashMap::new();
         let mut album_cache: HashMap<(String, String), i64> = HashMap::new();
 
         for batch in new_entries.chunks(500) {
-            let tracks = load_tracks_from_entry_batch(batch)?;
+            if cancel_token.is_cancelled() {
+                info!("Library refresh cancelled after {} new files", files_scanned);
+                return Err(FsTrackError::ScanCancelled.into());
+            }
+            let tracks = load_tracks_from_entry_batch(batch, check_lyrics_subdirectory, &mut scan_errors)?;
             db::add_tracks(&tracks, conn, &mut artist_cache, &mut album_cache)?;
             files_scanned += batch.len();
             let progress = Some(files_scanned as f64 / new_count as f64);
@@ -410,7 +660,59 @@ pub fn refresh_tracks_from_directories(
         }
     }
 
-    println!("==> Library refresh took: {}ms", now.elapsed().as_millis());
+    emit_scan_errors(&app_handle, scan_errors);
+    info!("==> Library refresh took: {}ms", now.elapsed().as_millis());
 
     Ok(())
 }
+
+#[cfg(test)]
+impl FsTrack {
+    /// A track with only the given tags, for database tests that go through `db::add_tracks`.
+    pub(crate) fn for_test(file_path: &str, title: &str, album: &str, artist: &str, year: Option<u32>) -> FsTrack {
+        let track = FsTrack::new(
+            file_path.to_owned(), file_path.to_owned(), title.to_owned(), album.to_owned(),
+            artist.to_owned(), artist.to_owned(), 0.0, None, None, None, None,
+        );
+        FsTrack { year, ..track }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_system_file, FsTrack};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_is_system_file() {
+        assert!(is_system_file(Path::new("/music/Album/._01 Intro.flac")));
+        assert!(!is_system_file(Path::new("/music/Album/01 Intro.flac")));
+        assert!(!is_system_file(Path::new("/music/._Album/01 Intro.flac")));
+    }
+
+    #[test]
+    fn test_read_sidecar_lyrics_from_subdirectory() {
+        let dir = std::env::temp_dir().join(format!("lrcget-sidecar-{}", std::process::id()));
+        fs::create_dir_all(dir.join("Lyrics")).unwrap();
+        fs::write(dir.join("track.txt"), "Beside").unwrap();
+        fs::write(dir.join("Lyrics").join("track.txt"), "Subdirectory").unwrap();
+        fs::write(dir.join("Lyrics").join("track.lrc"), "[00:01.00]Line").unwrap();
+
+        let track_path = dir.join("track.flac").display().to_string();
+        let track = FsTrack::new(
+            track_path, "track.flac".to_owned(), "Title".to_owned(), "Album".to_owned(),
+            "Artist".to_owned(), "Artist".to_owned(), 0.0, None, None, None, None,
+        );
+
+        let (txt, lrc) = track.read_sidecar_lyrics(false);
+        assert_eq!(txt.as_deref(), Some("Beside"));
+        assert_eq!(lrc, None);
+
+        let (txt, lrc) = track.read_sidecar_lyrics(true);
+        assert_eq!(txt.as_deref(), Some("Beside"));
+        assert_eq!(lrc.as_deref(), Some("[00:01.00]Line"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,49 @@
-use crate::db;
-use crate::fs_track;
-use crate::persistent_entities::{PersistentAlbum, PersistentArtist, PersistentTrack};
-use anyhow::Result;
+use crate::db::{self, DbError};
+use crate::fs_track::{self, FsTrackError};
+use crate::lyrics;
+use crate::persistent_entities::{PersistentAlbum, PersistentArtist, PersistentTrack, PersistentTrackSummary};
+use crate::utils::{atomic_write, normalize_isrc, strip_timestamp, RE_INSTRUMENTAL};
+use anyhow::{anyhow, Result};
+use rusqlite::backup::{Backup, StepResult};
 use rusqlite::Connection;
-use tauri::AppHandle;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
 
-pub fn initialize_library(conn: &mut Connection, app_handle: AppHandle) -> Result<()> {
+const EXPORT_PROGRESS_INTERVAL: usize = 100;
+const BACKUP_PAGES_PER_STEP: i32 = 5;
+const BACKUP_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+pub const ISSUE_MISSING_LRC_FILE: &str = "missing_lrc_file";
+pub const ISSUE_UNTRACKED_LRC_FILE: &str = "untracked_lrc_file";
+pub const ISSUE_MISSING_AUDIO_FILE: &str = "missing_audio_file";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LyricsValidationIssue {
+    pub track_id: i64,
+    pub issue_type: String,
+    pub file_path: String,
+}
+
+#[derive(Clone, Serialize)]
+struct ExportArchiveProgress {
+    written: usize,
+    total: usize,
+}
+
+pub fn initialize_library(
+    conn: &mut Connection,
+    app_handle: AppHandle,
+    cancel_token: &CancellationToken,
+) -> Result<()> {
     let init = db::get_init(conn)?;
     if init {
         return Ok(());
@@ -14,17 +52,24 @@ pub fn initialize_library(conn: &mut Connection, app_handle: AppHandle) -> Resul
     db::clean_library(conn)?;
 
     let directories = db::get_directories(conn)?;
-    let result = fs_track::load_tracks_from_directories(&directories, conn, app_handle);
+    let check_lyrics_subdirectory = db::get_config(conn)?.check_lyrics_subdirectory;
+    let result = fs_track::load_tracks_from_directories(&directories, check_lyrics_subdirectory, conn, app_handle, cancel_token);
 
     match result {
         Ok(()) => {
             db::set_init(true, conn)?;
             Ok(())
         }
+        // Keep what was scanned so far; a refresh picks up the remaining files
+        Err(err) if is_scan_cancelled(&err) => {
+            info!("Keeping the partially scanned library");
+            db::set_init(true, conn)?;
+            Ok(())
+        }
         Err(err) => {
             let uninitialization = uninitialize_library(conn);
             if let Err(uninit_error) = uninitialization {
-                println!(
+                error!(
                     "Uninitialization library errored. Message: {}",
                     uninit_error.to_string()
                 );
@@ -34,58 +79,150 @@ pub fn initialize_library(conn: &mut Connection, app_handle: AppHandle) -> Resul
     }
 }
 
-pub fn refresh_library(conn: &mut Connection, app_handle: AppHandle) -> Result<()> {
+pub fn refresh_library(
+    conn: &mut Connection,
+    app_handle: AppHandle,
+    cancel_token: &CancellationToken,
+) -> Result<()> {
     let directories = db::get_directories(conn)?;
-    let result = fs_track::refresh_tracks_from_directories(&directories, conn, app_handle);
+    let check_lyrics_subdirectory = db::get_config(conn)?.check_lyrics_subdirectory;
+    let result = fs_track::refresh_tracks_from_directories(&directories, check_lyrics_subdirectory, conn, app_handle, cancel_token);
 
     match result {
         Ok(()) => Ok(()),
+        Err(err) if is_scan_cancelled(&err) => Ok(()),
         Err(err) => {
-            println!("Library refresh errored: {}", err);
+            error!("Library refresh errored: {}", err);
             Err(err)
         }
     }
 }
 
+fn is_scan_cancelled(err: &anyhow::Error) -> bool {
+    matches!(err.downcast_ref::<FsTrackError>(), Some(FsTrackError::ScanCancelled))
+}
+
 pub fn uninitialize_library(conn: &Connection) -> Result<()> {
-    db::clean_library(conn)?;
-    db::set_init(false, conn)?;
-    Ok(())
+    db::clean_library(conn)
+}
+
+/// Runs VACUUM and returns how many bytes the database file shrank by.
+pub fn vacuum_database(conn: &Connection) -> Result<i64> {
+    with_file_size_delta(conn, db::vacuum)
+}
+
+/// Runs ANALYZE and returns the change in database file size, which is usually small.
+pub fn analyze_database(conn: &Connection) -> Result<i64> {
+    with_file_size_delta(conn, db::analyze)
+}
+
+fn with_file_size_delta(conn: &Connection, operation: fn(&Connection) -> Result<()>) -> Result<i64> {
+    let size_before = db::get_file_size(conn)?;
+    operation(conn)?;
+    let size_after = db::get_file_size(conn)?;
+
+    Ok(size_before as i64 - size_after as i64)
 }
 
 pub fn get_tracks(conn: &Connection) -> Result<Vec<PersistentTrack>> {
     db::get_tracks(conn)
 }
 
+pub fn get_tracks_without_lyrics(conn: &Connection) -> Result<Vec<PersistentTrack>> {
+    db::get_tracks_without_lyrics(conn)
+}
+
+pub fn get_track_summaries(conn: &Connection) -> Result<Vec<PersistentTrackSummary>> {
+    db::get_track_summaries(conn)
+}
+
+pub fn get_tracks_paginated(offset: usize, limit: usize, sort_by: &str, sort_order: &str, conn: &Connection) -> Result<Vec<PersistentTrack>> {
+    db::get_tracks_paginated(offset, limit, sort_by, sort_order, conn)
+}
+
+pub fn count_tracks_total(conn: &Connection) -> Result<usize> {
+    db::count_tracks_total(conn)
+}
+
 pub fn get_track_ids(
     search_query: Option<String>,
-    synced_lyrics: bool,
-    plain_lyrics: bool,
-    instrumental: bool,
-    no_lyrics: bool,
+    lyrics_filter: db::LyricsFilter,
+    favorites_only: bool,
     sort_by: &str,
     sort_order: &str,
     conn: &Connection
 ) -> Result<Vec<i64>> {
     match search_query {
-        Some(query) => db::get_search_track_ids(&query, synced_lyrics, plain_lyrics, instrumental, no_lyrics, sort_by, sort_order, conn),
-        None => db::get_track_ids(synced_lyrics, plain_lyrics, instrumental, no_lyrics, sort_by, sort_order, conn),
+        Some(query) => {
+            let fuzzy_search_enabled = db::get_config(conn)?.fuzzy_search_enabled;
+            db::get_search_track_ids(&query, lyrics_filter, favorites_only, fuzzy_search_enabled, sort_by, sort_order, conn)
+        }
+        None => db::get_track_ids(lyrics_filter, favorites_only, sort_by, sort_order, conn),
     }
 }
 
-pub fn get_track(id: i64, conn: &Connection) -> Result<PersistentTrack> {
+pub fn set_track_favorite(id: i64, favorite: bool, conn: &Connection) -> Result<()> {
+    db::set_track_favorite(id, favorite, conn)
+}
+
+pub fn get_favorite_track_ids(sort_by: &str, sort_order: &str, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_favorite_track_ids(sort_by, sort_order, conn)
+}
+
+pub fn get_track(id: i64, conn: &Connection) -> Result<PersistentTrack, DbError> {
     db::get_track_by_id(id, conn)
 }
 
+pub fn get_track_by_file_path(file_path: &str, conn: &Connection) -> Result<PersistentTrack, DbError> {
+    db::get_track_by_file_path(file_path, conn)
+}
+
+pub fn get_track_by_isrc(isrc: &str, conn: &Connection) -> Result<Option<PersistentTrack>> {
+    let isrc = normalize_isrc(isrc).ok_or_else(|| anyhow!("Invalid ISRC: `{}`", isrc))?;
+    db::get_track_by_isrc(&isrc, conn)
+}
+
+pub fn get_stale_lyrics_track_ids(older_than_days: u32, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_stale_lyrics_track_ids(older_than_days, conn)
+}
+
+pub fn get_recently_added_track_ids(limit: usize, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_recently_added_track_ids(limit, conn)
+}
+
+pub fn get_most_played_track_ids(limit: usize, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_most_played_track_ids(limit, conn)
+}
+
+pub fn get_most_played_tracks(limit: usize, conn: &Connection) -> Result<Vec<PersistentTrack>> {
+    db::get_most_played_tracks(limit, conn)
+}
+
+pub fn get_genres(conn: &Connection) -> Result<Vec<String>> {
+    db::get_genres(conn)
+}
+
+pub fn get_composers(conn: &Connection) -> Result<Vec<String>> {
+    db::get_composers(conn)
+}
+
+pub fn get_tracks_by_composer(composer: &str, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_tracks_by_composer(composer, conn)
+}
+
+pub fn get_tracks_by_genre(genre: &str, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_tracks_by_genre(genre, conn)
+}
+
 pub fn get_albums(conn: &Connection) -> Result<Vec<PersistentAlbum>> {
     db::get_albums(conn)
 }
 
-pub fn get_album_ids(search_query: Option<&str>, conn: &Connection) -> Result<Vec<i64>> {
-    db::get_album_ids(search_query, conn)
+pub fn get_album_ids(search_query: Option<&str>, sort_by: &str, sort_order: &str, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_album_ids(search_query, sort_by, sort_order, conn)
 }
 
-pub fn get_album(id: i64, conn: &Connection) -> Result<PersistentAlbum> {
+pub fn get_album(id: i64, conn: &Connection) -> Result<PersistentAlbum, DbError> {
     db::get_album_by_id(id, conn)
 }
 
@@ -97,7 +234,7 @@ pub fn get_artist_ids(search_query: Option<&str>, conn: &Connection) -> Result<V
     db::get_artist_ids(search_query, conn)
 }
 
-pub fn get_artist(id: i64, conn: &Connection) -> Result<PersistentArtist> {
+pub fn get_artist(id: i64, conn: &Connection) -> Result<PersistentArtist, DbError> {
     db::get_artist_by_id(id, conn)
 }
 
@@ -117,6 +254,344 @@ pub fn get_artist_track_ids(artist_id: i64, without_plain_lyrics: bool, without_
     db::get_artist_track_ids(artist_id, without_plain_lyrics, without_synced_lyrics, sort_by, sort_order, conn)
 }
 
+pub fn get_artist_albums(artist_id: i64, conn: &Connection) -> Result<Vec<PersistentAlbum>> {
+    db::get_artist_albums(artist_id, conn)
+}
+
+pub fn get_artist_album_ids(artist_id: i64, conn: &Connection) -> Result<Vec<i64>> {
+    db::get_artist_album_ids(artist_id, conn)
+}
+
 pub fn get_init(conn: &Connection) -> Result<bool> {
     db::get_init(conn)
 }
+
+/// Writes the lyrics of the given tracks into a zip archive at `dest_path`, laid out as
+/// `<artist>/<album>/<title>.<lrc|txt>`. `format` is one of `lrc`, `txt` or `both`.
+/// Returns the number of files written to the archive.
+pub fn export_lyrics_archive(
+    tracks: &[PersistentTrack],
+    dest_path: &Path,
+    format: &str,
+    app_handle: &AppHandle,
+) -> Result<u64> {
+    let (include_lrc, include_txt) = match format {
+        "lrc" => (true, false),
+        "txt" => (false, true),
+        "both" => (true, true),
+        _ => return Err(anyhow!("Unknown export format: {}", format)),
+    };
+
+    let tracks: Vec<&PersistentTrack> = tracks
+        .iter()
+        .filter(|track| track.lrc_lyrics.is_some() || track.txt_lyrics.is_some())
+        .collect();
+    let total = tracks.len();
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    let mut used_paths: HashSet<String> = HashSet::new();
+    let mut files_written: u64 = 0;
+
+    for (index, track) in tracks.iter().enumerate() {
+        let base_path = format!(
+            "{}/{}/{}",
+            sanitize_path_component(&track.artist_name),
+            sanitize_path_component(&track.album_name),
+            sanitize_path_component(&track.title)
+        );
+
+        let entries = [
+            (include_lrc, track.lrc_lyrics.as_deref(), "lrc"),
+            (include_txt, track.txt_lyrics.as_deref(), "txt"),
+        ];
+
+        for (included, lyrics, extension) in entries {
+            let Some(lyrics) = lyrics.filter(|_| included) else {
+                continue;
+            };
+
+            let mut path = format!("{}.{}", base_path, extension);
+            let mut duplicate_index = 2;
+            while used_paths.contains(&path) {
+                path = format!("{} ({}).{}", base_path, duplicate_index, extension);
+                duplicate_index += 1;
+            }
+
+            zip.start_file(path.as_str(), options)?;
+            zip.write_all(lyrics.as_bytes())?;
+            used_paths.insert(path);
+            files_written += 1;
+        }
+
+        let written = index + 1;
+        if written % EXPORT_PROGRESS_INTERVAL == 0 || written == total {
+            let _ = app_handle.emit("export-archive-progress", ExportArchiveProgress { written, total });
+        }
+    }
+
+    let archive = zip.finish()?.into_inner();
+    atomic_write(dest_path, &archive)?;
+
+    Ok(files_written)
+}
+
+/// A library track as written by `export_library_to_json`. The lyrics fields are only
+/// present when lyrics were exported.
+#[derive(Serialize)]
+struct ExportedTrack {
+    #[serde(flatten)]
+    track: PersistentTrackSummary,
+    #[serde(flatten)]
+    lyrics: Option<ExportedLyrics>,
+}
+
+#[derive(Serialize)]
+struct ExportedLyrics {
+    lrc_lyrics: Option<String>,
+    txt_lyrics: Option<String>,
+}
+
+/// Writes every track's metadata to `dest_path` as a JSON array, along with its lyrics when
+/// `export_lyrics` is set. Returns the number of tracks exported.
+pub fn export_library_to_json(conn: &Connection, dest_path: &Path, export_lyrics: bool) -> Result<u64> {
+    let tracks = db::get_track_summaries(conn)?;
+    let mut lyrics = if export_lyrics {
+        db::get_all_track_lyrics(conn)?
+    } else {
+        HashMap::new()
+    };
+
+    let exported: Vec<ExportedTrack> = tracks
+        .into_iter()
+        .map(|track| {
+            let track_lyrics = lyrics
+                .remove(&track.id)
+                .map(|(lrc_lyrics, txt_lyrics)| ExportedLyrics { lrc_lyrics, txt_lyrics });
+            ExportedTrack { track, lyrics: track_lyrics }
+        })
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(dest_path)?);
+    serde_json::to_writer(&mut writer, &exported)?;
+    writer.flush()?;
+
+    Ok(exported.len() as u64)
+}
+
+fn sanitize_path_component(component: &str) -> String {
+    let sanitized: String = component
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | '?' | '*' | '<' | '>' | '|' | ':' | '"'))
+        .collect();
+    let sanitized = sanitized.trim().trim_end_matches('.');
+
+    if sanitized.is_empty() {
+        "Unknown".to_owned()
+    } else {
+        sanitized.to_owned()
+    }
+}
+
+/// Compares the lyrics stored in the database with what is actually on disk.
+pub fn validate_lyrics_files(conn: &Connection) -> Result<Vec<LyricsValidationIssue>> {
+    let mut issues = Vec::new();
+
+    for track in db::get_tracks(conn)? {
+        let issue = |issue_type: &str| LyricsValidationIssue {
+            track_id: track.id,
+            issue_type: issue_type.to_owned(),
+            file_path: track.file_path.clone(),
+        };
+
+        if !Path::new(&track.file_path).exists() {
+            issues.push(issue(ISSUE_MISSING_AUDIO_FILE));
+            continue;
+        }
+
+        let lrc_exists = lyrics::build_lrc_path(&track.file_path)?.exists();
+        if track.lrc_lyrics.is_some() && !lrc_exists {
+            issues.push(issue(ISSUE_MISSING_LRC_FILE));
+        } else if lrc_exists
+            && track.lrc_lyrics.is_none()
+            && track.txt_lyrics.is_none()
+            && !track.instrumental
+        {
+            issues.push(issue(ISSUE_UNTRACKED_LRC_FILE));
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Re-syncs the flagged tracks from disk. Tracks whose audio file is gone are removed,
+/// the others get their lyrics re-read from the sidecar files. Returns the affected track IDs.
+pub fn fix_lyrics_discrepancies(issues: &[LyricsValidationIssue], conn: &Connection) -> Result<Vec<i64>> {
+    let mut fixed_track_ids: Vec<i64> = Vec::new();
+
+    for issue in issues {
+        if fixed_track_ids.contains(&issue.track_id) {
+            continue;
+        }
+
+        let track = db::get_track_by_id(issue.track_id, conn)?;
+
+        if !Path::new(&track.file_path).exists() {
+            db::delete_track(track.id, conn)?;
+            fixed_track_ids.push(track.id);
+            continue;
+        }
+
+        let lrc_lyrics = std::fs::read_to_string(lyrics::build_lrc_path(&track.file_path)?).ok();
+        let txt_lyrics = std::fs::read_to_string(lyrics::build_txt_path(&track.file_path)?).ok();
+
+        match (lrc_lyrics, txt_lyrics) {
+            (Some(lrc_lyrics), _) if RE_INSTRUMENTAL.is_match(&lrc_lyrics) => {
+                db::update_track_instrumental(track.id, conn)?;
+            }
+            (Some(lrc_lyrics), txt_lyrics) => {
+                let plain_lyrics = txt_lyrics.unwrap_or_else(|| strip_timestamp(&lrc_lyrics));
+                db::update_track_synced_lyrics(track.id, &lrc_lyrics, &plain_lyrics, None, conn)?;
+            }
+            (None, Some(txt_lyrics)) => {
+                db::update_track_plain_lyrics(track.id, &txt_lyrics, None, conn)?;
+            }
+            (None, None) => {
+                db::update_track_null_lyrics(track.id, conn)?;
+            }
+        }
+
+        fixed_track_ids.push(track.id);
+    }
+
+    db::delete_orphan_albums(conn)?;
+    db::delete_orphan_artists(conn)?;
+
+    Ok(fixed_track_ids)
+}
+
+pub fn get_duplicate_tracks(conn: &Connection) -> Result<Vec<Vec<PersistentTrack>>> {
+    db::find_duplicate_tracks(conn)
+}
+
+/// Removes the track from the library. The audio file is never touched; with `with_files`
+/// the sidecar `.txt` and `.lrc` lyrics next to it are deleted as well.
+pub fn delete_track(track_id: i64, with_files: bool, conn: &Connection) -> Result<()> {
+    if with_files {
+        let track = db::get_track_by_id(track_id, conn)?;
+        remove_sidecar_lyrics(&track.file_path)?;
+    }
+
+    db::delete_track_by_id(track_id, conn)
+}
+
+/// Re-reads the track's file tags into the library and returns the updated track.
+pub fn rescan_single_track(track_id: i64, conn: &mut Connection) -> Result<PersistentTrack> {
+    let track = db::get_track_by_id(track_id, conn)?;
+    let check_lyrics_subdirectory = db::get_config(conn)?.check_lyrics_subdirectory;
+    let fs_track = fs_track::load_track(Path::new(&track.file_path), check_lyrics_subdirectory)?;
+    db::update_track_metadata(track_id, &fs_track, conn)?;
+
+    Ok(db::get_track_by_id(track_id, conn)?)
+}
+
+/// Deletes the `.txt` and `.lrc` lyrics files next to the audio file, where they exist.
+fn remove_sidecar_lyrics(file_path: &str) -> Result<()> {
+    for sidecar_path in [lyrics::build_txt_path(file_path)?, lyrics::build_lrc_path(file_path)?] {
+        if sidecar_path.exists() {
+            std::fs::remove_file(&sidecar_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes every track of a duplicate group except the first one, along with its sidecar lyrics files.
+/// The audio files themselves are left untouched. Returns the IDs of the removed tracks.
+pub fn delete_duplicate_keeping_first(group: &[i64], conn: &Connection) -> Result<Vec<i64>> {
+    let mut deleted_track_ids: Vec<i64> = Vec::new();
+
+    for track_id in group.iter().skip(1) {
+        let track = db::get_track_by_id(*track_id, conn)?;
+        remove_sidecar_lyrics(&track.file_path)?;
+
+        db::delete_track(track.id, conn)?;
+        deleted_track_ids.push(track.id);
+    }
+
+    db::delete_orphan_albums(conn)?;
+    db::delete_orphan_artists(conn)?;
+
+    Ok(deleted_track_ids)
+}
+
+/// Writes the embedded cover art of each album's first track to `{app_data_dir}/covers/{album_id}.jpg`
+/// and stores the path on the album. Returns how many albums got a cover.
+pub fn extract_album_art(conn: &Connection, app_handle: &AppHandle) -> Result<u32> {
+    let covers_dir = app_handle.path().app_data_dir()?.join("covers");
+    std::fs::create_dir_all(&covers_dir)?;
+
+    let mut updated = 0;
+    for (album_id, file_path) in db::get_album_cover_sources(conn)? {
+        let Some(image) = fs_track::read_cover_art(Path::new(&file_path)) else {
+            continue;
+        };
+
+        let image_path = covers_dir.join(format!("{}.jpg", album_id));
+        if let Err(e) = atomic_write(&image_path, &image) {
+            error!("Cannot write cover art for album {}: {}", album_id, e);
+            continue;
+        }
+        db::update_album_image_path(album_id, Some(&image_path.display().to_string()), conn)?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
+pub fn get_album_art(album_id: i64, conn: &Connection) -> Result<Option<String>> {
+    db::get_album_image_path(album_id, conn)
+}
+
+/// Copies the live database to `dest_path` with the SQLite online backup API, emitting
+/// `backup-progress` with the completed fraction after every step.
+pub fn backup_database(conn: &Connection, dest_path: &Path, app_handle: &AppHandle) -> Result<()> {
+    if let Some(live_path) = conn.path().filter(|path| !path.is_empty()) {
+        if resolve_path(Path::new(live_path)) == resolve_path(dest_path) {
+            return Err(anyhow!("The backup destination cannot be the live database file"));
+        }
+    }
+
+    let mut dest_conn = Connection::open(dest_path)?;
+    let backup = Backup::new(conn, &mut dest_conn)?;
+
+    loop {
+        match backup.step(BACKUP_PAGES_PER_STEP)? {
+            StepResult::Done => break,
+            StepResult::Busy | StepResult::Locked => std::thread::sleep(BACKUP_RETRY_DELAY),
+            _ => {}
+        }
+
+        let progress = backup.progress();
+        if progress.pagecount > 0 {
+            let fraction = (progress.pagecount - progress.remaining) as f64 / progress.pagecount as f64;
+            let _ = app_handle.emit("backup-progress", fraction);
+        }
+    }
+
+    let _ = app_handle.emit("backup-progress", 1.0);
+
+    Ok(())
+}
+
+/// Canonicalizes a path that may not exist yet by resolving its parent directory instead.
+fn resolve_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+
+    match (path.parent().and_then(|parent| parent.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(file_name)) => parent.join(file_name),
+        _ => path.to_path_buf(),
+    }
+}
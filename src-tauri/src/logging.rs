@@ -0,0 +1,29 @@
+use anyhow::Result;
+use tracing::Level;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry};
+
+const DEFAULT_LOG_FILTER: &str = "info";
+
+pub type LogFilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Installs the global subscriber. `RUST_LOG` takes precedence over the default `info` level,
+/// and the returned handle allows changing the filter at runtime.
+pub fn init() -> LogFilterHandle {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .init();
+
+    handle
+}
+
+pub fn set_level(handle: &LogFilterHandle, level: &str) -> Result<()> {
+    let level: Level = level.parse()?;
+    handle.reload(EnvFilter::default().add_directive(LevelFilter::from_level(level).into()))?;
+
+    Ok(())
+}
@@ -1,5 +1,24 @@
 use data_encoding::HEXUPPER;
+use rayon::prelude::*;
 use ring::digest::{Context, SHA256};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+const CANCEL_CHECK_INTERVAL: u64 = 10_000;
+const PROGRESS_INTERVAL: u64 = 50_000;
+
+#[derive(Error, Debug)]
+pub enum SolverError {
+    #[error("Invalid challenge target: {0}")]
+    InvalidTarget(#[from] data_encoding::DecodeError),
+    #[error("Challenge solving was cancelled")]
+    Cancelled,
+    #[error("Challenge solving timed out after {} seconds", .0.as_secs())]
+    Timeout(Duration),
+}
 
 fn verify_nonce(result: &[u8], target: &[u8]) -> bool {
     if result.len() != target.len() {
@@ -17,24 +36,120 @@ fn verify_nonce(result: &[u8], target: &[u8]) -> bool {
     true
 }
 
-pub fn solve_challenge(prefix: &str, target_hex: &str) -> String {
-    let mut nonce = 0;
-    let mut hashed;
-    let target = HEXUPPER.decode(target_hex.as_bytes()).unwrap();
+/// Searches for the smallest nonce whose hash with `prefix` is at or below the target, spread
+/// across the rayon thread pool. Worker `i` of `n` tries nonces `i`, `i + n`, `i + 2n`, ... and
+/// stops once it passes the smallest nonce found so far. Each worker checks `cancel` and the
+/// timeout every 10,000 nonces, and the number tried so far across all workers is reported on
+/// `progress_tx` every 50,000.
+pub fn solve_challenge(
+    prefix: &str,
+    target_hex: &str,
+    cancel: Arc<AtomicBool>,
+    progress_tx: Option<Sender<u64>>,
+    timeout: Duration,
+) -> Result<String, SolverError> {
+    let target = HEXUPPER.decode(target_hex.as_bytes())?;
+    let started = Instant::now();
+    let workers = rayon::current_num_threads().max(1) as u64;
+    let found = AtomicU64::new(u64::MAX);
+    let tried = AtomicU64::new(0);
 
-    loop {
-        let mut context = Context::new(&SHA256);
-        let input = format!("{}{}", prefix, nonce);
-        context.update(input.as_bytes());
-        hashed = context.finish().as_ref().to_vec();
+    (0..workers).into_par_iter().try_for_each(|worker| {
+        let mut nonce = worker;
+        let mut tried_here: u64 = 0;
 
-        let result = verify_nonce(&hashed, &target);
-        if result {
-            break;
-        } else {
-            nonce += 1;
+        while nonce < found.load(Ordering::Relaxed) {
+            let mut context = Context::new(&SHA256);
+            let input = format!("{}{}", prefix, nonce);
+            context.update(input.as_bytes());
+
+            if verify_nonce(context.finish().as_ref(), &target) {
+                found.fetch_min(nonce, Ordering::Relaxed);
+                break;
+            }
+
+            nonce += workers;
+            tried_here += 1;
+
+            if tried_here % CANCEL_CHECK_INTERVAL == 0 {
+                if cancel.load(Ordering::Relaxed) {
+                    return Err(SolverError::Cancelled);
+                }
+                if started.elapsed() > timeout {
+                    return Err(SolverError::Timeout(timeout));
+                }
+
+                let total = tried.fetch_add(CANCEL_CHECK_INTERVAL, Ordering::Relaxed) + CANCEL_CHECK_INTERVAL;
+                if total % PROGRESS_INTERVAL == 0 {
+                    if let Some(tx) = &progress_tx {
+                        // The receiver going away only means nobody is watching any more
+                        let _ = tx.send(total);
+                    }
+                }
+            }
         }
+
+        Ok(())
+    })?;
+
+    Ok(found.into_inner().to_string())
+}
+
+/// The token LRCLIB expects back for a solved challenge.
+pub fn format_token(prefix: &str, nonce: &str) -> String {
+    format!("{}:{}", prefix, nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_token, solve_challenge, verify_nonce, SolverError};
+    use data_encoding::HEXUPPER;
+    use ring::digest::{digest, SHA256};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    // A target this low can't be met, so the solver only stops when told to
+    const IMPOSSIBLE_TARGET: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn test_solve_challenge_easy_target() {
+        let target = "F".repeat(64);
+        let nonce = solve_challenge("prefix", &target, Arc::new(AtomicBool::new(false)), None, Duration::from_secs(5));
+        assert_eq!(nonce.unwrap(), "0");
+    }
+
+    #[test]
+    fn test_solve_challenge_finds_smallest_nonce() {
+        let target = format!("00{}", "F".repeat(62));
+        let target_bytes = HEXUPPER.decode(target.as_bytes()).unwrap();
+        let expected = (0u64..)
+            .find(|nonce| verify_nonce(digest(&SHA256, format!("prefix{}", nonce).as_bytes()).as_ref(), &target_bytes))
+            .unwrap();
+
+        let nonce = solve_challenge("prefix", &target, Arc::new(AtomicBool::new(false)), None, Duration::from_secs(5));
+        assert_eq!(nonce.unwrap(), expected.to_string());
+    }
+
+    #[test]
+    fn test_format_token() {
+        assert_eq!(format_token("VXMwW2qPfW2gkCNSl1i708NJkDghtAyU", "1234"), "VXMwW2qPfW2gkCNSl1i708NJkDghtAyU:1234");
     }
 
-    nonce.to_string()
+    #[test]
+    fn test_solve_challenge_cancelled() {
+        let (tx, rx) = mpsc::channel();
+        let result = solve_challenge("prefix", IMPOSSIBLE_TARGET, Arc::new(AtomicBool::new(true)), Some(tx), Duration::from_secs(60));
+        assert!(matches!(result, Err(SolverError::Cancelled)));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_solve_challenge_timeout() {
+        let (tx, rx) = mpsc::channel();
+        let result = solve_challenge("prefix", IMPOSSIBLE_TARGET, Arc::new(AtomicBool::new(false)), Some(tx), Duration::ZERO);
+        assert!(matches!(result, Err(SolverError::Timeout(_))));
+        assert!(rx.try_recv().is_err());
+    }
 }
@@ -1,7 +1,11 @@
 use anyhow::Result;
 use serde::Serialize;
+use thiserror::Error;
 
-use super::{post_with_retry, ResponseError, HTTP_CLIENT};
+use super::{post_with_retry, ResponseError};
+
+/// Shortest flag reason accepted, in characters, so a reason says at least something.
+const MIN_REASON_LENGTH: usize = 5;
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,12 +14,36 @@ pub struct Request {
     reason: String,
 }
 
+/// Flag reasons rejected before the flag challenge is solved.
+#[derive(Error, Debug, PartialEq)]
+pub enum FlagValidationError {
+    #[error("Flag reason cannot be empty")]
+    EmptyReason,
+    #[error("Flag reason must be at least {MIN_REASON_LENGTH} characters long")]
+    ReasonTooShort,
+}
+
+pub fn validate_flag_reason(reason: &str) -> Result<(), FlagValidationError> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(FlagValidationError::EmptyReason);
+    }
+    if reason.chars().count() < MIN_REASON_LENGTH {
+        return Err(FlagValidationError::ReasonTooShort);
+    }
+
+    Ok(())
+}
+
 pub async fn request(
     track_id: i64,
     reason: &str,
     publish_token: &str,
     lrclib_instance: &str,
+    client: &reqwest::Client,
 ) -> Result<()> {
+    validate_flag_reason(reason)?;
+
     let data = Request {
         track_id,
         reason: reason.to_owned(),
@@ -24,7 +52,7 @@ pub async fn request(
     let api_endpoint = format!("{}/api/flag", lrclib_instance.trim_end_matches('/'));
     let url = reqwest::Url::parse(&api_endpoint)?;
     let res = post_with_retry(
-        HTTP_CLIENT
+        client
             .post(url)
             .header("X-Publish-Token", publish_token)
             .json(&data),
@@ -49,3 +77,16 @@ pub async fn request(
         .into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_flag_reason, FlagValidationError};
+
+    #[test]
+    fn test_validate_flag_reason() {
+        assert_eq!(validate_flag_reason("Wrong song"), Ok(()));
+        assert_eq!(validate_flag_reason(""), Err(FlagValidationError::EmptyReason));
+        assert_eq!(validate_flag_reason(" \n "), Err(FlagValidationError::EmptyReason));
+        assert_eq!(validate_flag_reason("bad "), Err(FlagValidationError::ReasonTooShort));
+    }
+}
@@ -2,7 +2,7 @@ use crate::utils::strip_timestamp;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use super::{ResponseError, get_with_retry};
+use super::{ResponseError, get_with_instance_fallback};
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -20,13 +20,42 @@ pub struct RawResponse {
     duration: Option<f64>,
 }
 
-#[derive(Serialize)]
-#[serde(tag = "type", content = "lyrics")]
+impl RawResponse {
+    /// Builds a response carrying only the lyrics, e.g. to hand a search result back to `apply_lyrics`.
+    pub fn from_response(response: Response) -> RawResponse {
+        let (plain_lyrics, synced_lyrics, instrumental) = match response {
+            Response::SyncedLyrics(synced, plain) => (Some(plain), Some(synced), false),
+            Response::UnsyncedLyrics(plain) => (Some(plain), None, false),
+            Response::IsInstrumental => (None, None, true),
+            Response::None(_) => (None, None, false),
+        };
+
+        RawResponse {
+            plain_lyrics,
+            synced_lyrics,
+            instrumental,
+            lang: None,
+            isrc: None,
+            spotify_id: None,
+            name: None,
+            album_name: None,
+            artist_name: None,
+            release_date: None,
+            duration: None,
+        }
+    }
+}
+
+/// Serialized with the variant name under `type` and its lyrics, if any, under `data`,
+/// e.g. `{ "type": "None", "data": null }` or `{ "type": "UnsyncedLyrics", "data": "..." }`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "data")]
 pub enum Response {
     SyncedLyrics(String, String),
     UnsyncedLyrics(String),
     IsInstrumental,
-    None,
+    /// No lyrics, with a short reason when the lookup knows why.
+    None(Option<String>),
 }
 
 impl Response {
@@ -45,7 +74,7 @@ impl Response {
                     if lrclib_response.instrumental {
                         Response::IsInstrumental
                     } else {
-                        Response::None
+                        Response::None(Some("entry has no lyrics".to_owned()))
                     }
                 }
             },
@@ -58,7 +87,8 @@ async fn make_request(
     album_name: &str,
     artist_name: &str,
     duration: f64,
-    lrclib_instance: &str,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
 ) -> Result<reqwest::Response> {
     let params: Vec<(String, String)> = vec![
         ("artist_name".to_owned(), artist_name.to_owned()),
@@ -67,9 +97,15 @@ async fn make_request(
         ("duration".to_owned(), duration.round().to_string()),
     ];
 
-    let api_endpoint = format!("{}/api/get", lrclib_instance.trim_end_matches('/'));
-    let url = reqwest::Url::parse_with_params(&api_endpoint, &params)?;
-    Ok(get_with_retry(url).await?)
+    get_with_instance_fallback(
+        lrclib_instances,
+        |lrclib_instance| {
+            let api_endpoint = format!("{}/api/get", lrclib_instance);
+            Ok(reqwest::Url::parse_with_params(&api_endpoint, &params)?)
+        },
+        client,
+    )
+    .await
 }
 
 pub async fn request_raw(
@@ -77,9 +113,10 @@ pub async fn request_raw(
     album_name: &str,
     artist_name: &str,
     duration: f64,
-    lrclib_instance: &str,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
 ) -> Result<RawResponse> {
-    let res = make_request(title, album_name, artist_name, duration, lrclib_instance).await?;
+    let res = make_request(title, album_name, artist_name, duration, lrclib_instances, client).await?;
 
     match res.status() {
         reqwest::StatusCode::OK => {
@@ -125,9 +162,10 @@ pub async fn request(
     album_name: &str,
     artist_name: &str,
     duration: f64,
-    lrclib_instance: &str,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
 ) -> Result<Response> {
-    let res = make_request(title, album_name, artist_name, duration, lrclib_instance).await?;
+    let res = make_request(title, album_name, artist_name, duration, lrclib_instances, client).await?;
 
     match res.status() {
         reqwest::StatusCode::OK => {
@@ -136,7 +174,7 @@ pub async fn request(
             Ok(Response::from_raw_response(lrclib_response))
         }
 
-        reqwest::StatusCode::NOT_FOUND => Ok(Response::None),
+        reqwest::StatusCode::NOT_FOUND => Ok(Response::None(Some("exact match found no entry".to_owned()))),
 
         reqwest::StatusCode::BAD_REQUEST
         | reqwest::StatusCode::SERVICE_UNAVAILABLE
@@ -153,3 +191,28 @@ pub async fn request(
         .into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Response;
+
+    #[test]
+    fn test_response_serialization() {
+        assert_eq!(serde_json::to_string(&Response::None(None)).unwrap(), r#"{"type":"None","data":null}"#);
+        assert_eq!(
+            serde_json::to_string(&Response::None(Some("entry has no lyrics".to_owned()))).unwrap(),
+            r#"{"type":"None","data":"entry has no lyrics"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Response::UnsyncedLyrics("Line".to_owned())).unwrap(),
+            r#"{"type":"UnsyncedLyrics","data":"Line"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Response::SyncedLyrics("[00:01.00]Line".to_owned(), "Line".to_owned())).unwrap(),
+            r#"{"type":"SyncedLyrics","data":["[00:01.00]Line","Line"]}"#
+        );
+
+        let parsed: Response = serde_json::from_str(r#"{"type":"IsInstrumental"}"#).unwrap();
+        assert!(matches!(parsed, Response::IsInstrumental));
+    }
+}
@@ -4,14 +4,14 @@ pub use super::get::RawResponse;
 pub use super::get::Response;
 use super::{ResponseError, get_with_retry};
 
-async fn make_request(id: i64, lrclib_instance: &str) -> Result<reqwest::Response> {
+async fn make_request(id: i64, lrclib_instance: &str, client: &reqwest::Client) -> Result<reqwest::Response> {
     let api_endpoint = format!("{}/api/get/{}", lrclib_instance.trim_end_matches('/'), id);
     let url = reqwest::Url::parse(&api_endpoint)?;
-    Ok(get_with_retry(url).await?)
+    Ok(get_with_retry(url, client).await?)
 }
 
-pub async fn request_raw(id: i64, lrclib_instance: &str) -> Result<RawResponse> {
-    let res = make_request(id, lrclib_instance).await?;
+pub async fn request_raw(id: i64, lrclib_instance: &str, client: &reqwest::Client) -> Result<RawResponse> {
+    let res = make_request(id, lrclib_instance, client).await?;
 
     match res.status() {
         reqwest::StatusCode::OK => {
@@ -55,8 +55,8 @@ pub async fn request_raw(id: i64, lrclib_instance: &str) -> Result<RawResponse>
     }
 }
 
-pub async fn request(id: i64, lrclib_instance: &str) -> Result<Response> {
-    let res = make_request(id, lrclib_instance).await?;
+pub async fn request(id: i64, lrclib_instance: &str, client: &reqwest::Client) -> Result<Response> {
+    let res = make_request(id, lrclib_instance, client).await?;
 
     match res.status() {
         reqwest::StatusCode::OK => {
@@ -65,7 +65,7 @@ pub async fn request(id: i64, lrclib_instance: &str) -> Result<Response> {
             Ok(Response::from_raw_response(lrclib_response))
         }
 
-        reqwest::StatusCode::NOT_FOUND => Ok(Response::None),
+        reqwest::StatusCode::NOT_FOUND => Ok(Response::None(Some("no entry with this ID".to_owned()))),
 
         reqwest::StatusCode::BAD_REQUEST
         | reqwest::StatusCode::SERVICE_UNAVAILABLE
@@ -1,7 +1,8 @@
 use anyhow::Result;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use super::{post_with_retry, ResponseError, HTTP_CLIENT};
+use super::{post_with_retry, ResponseError};
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,6 +15,37 @@ pub struct Request {
     synced_lyrics: String,
 }
 
+#[derive(Deserialize)]
+pub struct Response {
+    pub id: i64,
+}
+
+/// Publish parameters LRCLIB would reject, caught before the publish challenge is solved.
+#[derive(Error, Debug, PartialEq)]
+pub enum PublishValidationError {
+    #[error("Duration must be greater than 0 seconds")]
+    InvalidDuration,
+    #[error("Track title must not be empty")]
+    EmptyTitle,
+    #[error("Artist name must not be empty")]
+    EmptyArtistName,
+}
+
+pub fn validate_publish_params(title: &str, artist_name: &str, duration: f64) -> Result<(), PublishValidationError> {
+    if !duration.is_finite() || duration <= 0.0 {
+        return Err(PublishValidationError::InvalidDuration);
+    }
+    if title.trim().is_empty() {
+        return Err(PublishValidationError::EmptyTitle);
+    }
+    if artist_name.trim().is_empty() {
+        return Err(PublishValidationError::EmptyArtistName);
+    }
+
+    Ok(())
+}
+
+/// Publishes the lyrics and returns the ID LRCLIB assigned to the new entry.
 pub async fn request(
     title: &str,
     album_name: &str,
@@ -23,7 +55,10 @@ pub async fn request(
     synced_lyrics: &str,
     publish_token: &str,
     lrclib_instance: &str,
-) -> Result<()> {
+    client: &reqwest::Client,
+) -> Result<i64> {
+    validate_publish_params(title, artist_name, duration)?;
+
     let data = Request {
         artist_name: artist_name.to_owned(),
         track_name: title.to_owned(),
@@ -36,7 +71,7 @@ pub async fn request(
     let api_endpoint = format!("{}/api/publish", lrclib_instance.trim_end_matches('/'));
     let url = reqwest::Url::parse(&api_endpoint)?;
     let res = post_with_retry(
-        HTTP_CLIENT
+        client
             .post(url)
             .header("X-Publish-Token", publish_token)
             .json(&data),
@@ -44,7 +79,10 @@ pub async fn request(
     .await?;
 
     match res.status() {
-        reqwest::StatusCode::CREATED => Ok(()),
+        reqwest::StatusCode::CREATED => {
+            let response = res.json::<Response>().await?;
+            Ok(response.id)
+        }
 
         reqwest::StatusCode::BAD_REQUEST
         | reqwest::StatusCode::SERVICE_UNAVAILABLE
@@ -61,3 +99,17 @@ pub async fn request(
         .into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_publish_params, PublishValidationError};
+
+    #[test]
+    fn test_validate_publish_params() {
+        assert_eq!(validate_publish_params("Song", "Artist", 180.0), Ok(()));
+        assert_eq!(validate_publish_params("Song", "Artist", 0.0), Err(PublishValidationError::InvalidDuration));
+        assert_eq!(validate_publish_params("Song", "Artist", f64::NAN), Err(PublishValidationError::InvalidDuration));
+        assert_eq!(validate_publish_params("  ", "Artist", 180.0), Err(PublishValidationError::EmptyTitle));
+        assert_eq!(validate_publish_params("Song", "", 180.0), Err(PublishValidationError::EmptyArtistName));
+    }
+}
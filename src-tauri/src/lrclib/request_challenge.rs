@@ -1,7 +1,7 @@
 use anyhow::Result;
 use serde::Deserialize;
 
-use super::{post_with_retry, ResponseError, HTTP_CLIENT};
+use super::{post_with_retry, ResponseError};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -10,13 +10,13 @@ pub struct Response {
     pub target: String,
 }
 
-pub async fn request(lrclib_instance: &str) -> Result<Response> {
+pub async fn request(lrclib_instance: &str, client: &reqwest::Client) -> Result<Response> {
     let api_endpoint = format!(
         "{}/api/request-challenge",
         lrclib_instance.trim_end_matches('/')
     );
     let url = reqwest::Url::parse(&api_endpoint)?;
-    let res = post_with_retry(HTTP_CLIENT.post(url)).await?;
+    let res = post_with_retry(client.post(url)).await?;
 
     match res.status() {
         reqwest::StatusCode::OK => {
@@ -1,7 +1,12 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 
-use super::{ResponseError, get_with_retry};
+use super::{ResponseError, get_with_instance_fallback};
+use crate::utils::levenshtein_similarity;
+
+/// Duration difference in seconds at which a result no longer earns any duration score.
+const DURATION_SCORE_RANGE: f64 = 10.0;
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,17 +19,62 @@ pub struct SearchItem {
     pub instrumental: bool,
     pub plain_lyrics: Option<String>,
     pub synced_lyrics: Option<String>,
+    /// ISO 639-1 code of the lyrics, when LRCLIB knows it.
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
+/// One page of results. LRCLIB currently answers with a bare array, which leaves `total` unknown;
+/// a `{ "items": [...], "total": n }` object is accepted as well.
 #[derive(Deserialize, Serialize)]
-pub struct Response(pub Vec<SearchItem>);
+#[serde(from = "Page<T>")]
+pub struct PaginatedResponse<T> {
+    pub items: Vec<T>,
+    pub total: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Page<T> {
+    Items(Vec<T>),
+    Paginated { items: Vec<T>, total: Option<u64> },
+}
+
+impl<T> From<Page<T>> for PaginatedResponse<T> {
+    fn from(page: Page<T>) -> Self {
+        match page {
+            Page::Items(items) => PaginatedResponse { items, total: None },
+            Page::Paginated { items, total } => PaginatedResponse { items, total },
+        }
+    }
+}
+
+impl<T> IntoIterator for PaginatedResponse<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+pub type Response = PaginatedResponse<SearchItem>;
+
+/// Which page of results to ask for. Unset values are left to the server's defaults.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Paging {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
 
 pub async fn request(
     title: &str,
     album_name: &str,
     artist_name: &str,
     q: &str,
-    lrclib_instance: &str,
+    paging: Paging,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
 ) -> Result<Response> {
     let mut params: Vec<(String, String)> = Vec::new();
     if !title.is_empty() {
@@ -39,10 +89,22 @@ pub async fn request(
     if !q.is_empty() {
         params.push(("q".to_owned(), q.to_owned()));
     }
+    if let Some(limit) = paging.limit {
+        params.push(("limit".to_owned(), limit.to_string()));
+    }
+    if let Some(offset) = paging.offset {
+        params.push(("offset".to_owned(), offset.to_string()));
+    }
 
-    let api_endpoint = format!("{}/api/search", lrclib_instance.trim_end_matches('/'));
-    let url = reqwest::Url::parse_with_params(&api_endpoint, &params)?;
-    let res = get_with_retry(url).await?;
+    let res = get_with_instance_fallback(
+        lrclib_instances,
+        |lrclib_instance| {
+            let api_endpoint = format!("{}/api/search", lrclib_instance);
+            Ok(reqwest::Url::parse_with_params(&api_endpoint, &params)?)
+        },
+        client,
+    )
+    .await?;
 
     match res.status() {
         reqwest::StatusCode::OK => {
@@ -65,3 +127,182 @@ pub async fn request(
         .into()),
     }
 }
+
+/// Runs a search and pairs each result with how well it matches the track, best first. With a
+/// `q` the search runs on the query alone, and `title` is only used for scoring.
+pub async fn request_ranked(
+    title: &str,
+    album_name: &str,
+    artist_name: &str,
+    q: &str,
+    duration: f64,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
+) -> Result<Vec<(SearchItem, f64)>> {
+    let results = if q.is_empty() {
+        request(title, album_name, artist_name, "", Paging::default(), lrclib_instances, client).await?
+    } else {
+        request("", "", "", q, Paging::default(), lrclib_instances, client).await?
+    };
+
+    Ok(rank(results, title, duration))
+}
+
+/// Scores results from 0 to 1, averaging how close the duration is and how similar the title
+/// is, and sorts them best first.
+fn rank(items: impl IntoIterator<Item = SearchItem>, title: &str, duration: f64) -> Vec<(SearchItem, f64)> {
+    let mut ranked: Vec<(SearchItem, f64)> = items
+        .into_iter()
+        .map(|item| {
+            let duration_score = item.duration
+                .map(|d| (1.0 - (d - duration).abs() / DURATION_SCORE_RANGE).max(0.0))
+                .unwrap_or(0.0);
+            let title_score = item.name.as_deref()
+                .map(|name| title_similarity(title, name))
+                .unwrap_or(0.0);
+            let score = (duration_score + title_score) / 2.0;
+            (item, score)
+        })
+        .collect();
+    ranked.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    ranked
+}
+
+fn normalize_text(s: &str) -> String {
+    s.to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn text_similarity(a: &str, b: &str) -> f64 {
+    let a_norm = normalize_text(a);
+    let b_norm = normalize_text(b);
+
+    if a_norm.is_empty() && b_norm.is_empty() {
+        return 1.0;
+    }
+    if a_norm.is_empty() || b_norm.is_empty() {
+        return 0.0;
+    }
+    if a_norm == b_norm {
+        return 1.0;
+    }
+
+    let a_words: HashSet<&str> = a_norm.split_whitespace().collect();
+    let b_words: HashSet<&str> = b_norm.split_whitespace().collect();
+
+    // Word overlap says little when a title is a single word ("Iris" vs "Iris Live"),
+    // so compare character pairs instead
+    if a_words.len() < 2 || b_words.len() < 2 {
+        return jaccard(&char_bigrams(&a_norm), &char_bigrams(&b_norm));
+    }
+
+    jaccard(&a_words, &b_words)
+}
+
+/// The better of the word overlap and edit distance scores, so either a reworded title or
+/// a typo can qualify a candidate.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    text_similarity(a, b).max(levenshtein_similarity(&normalize_text(a), &normalize_text(b)))
+}
+
+/// Overlapping two-character pairs within each word of `s`.
+fn char_bigrams(s: &str) -> HashSet<(char, char)> {
+    s.split_whitespace()
+        .flat_map(|word| {
+            let chars: Vec<char> = word.chars().collect();
+            chars.windows(2).map(|pair| (pair[0], pair[1])).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn jaccard<T: Eq + std::hash::Hash>(a: &HashSet<T>, b: &HashSet<T>) -> f64 {
+    let intersection = a.intersection(b).count();
+    let union = a.union(b).count();
+
+    if union == 0 { 0.0 } else { intersection as f64 / union as f64 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank, text_similarity, title_similarity, Response, SearchItem};
+
+    fn search_item(id: i64, name: &str, duration: f64) -> SearchItem {
+        SearchItem {
+            id,
+            name: Some(name.to_owned()),
+            artist_name: None,
+            album_name: None,
+            duration: Some(duration),
+            instrumental: false,
+            plain_lyrics: None,
+            synced_lyrics: None,
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_response_accepts_bare_array_and_paginated_object() {
+        let item = r#"{"id":1,"name":"Song","artistName":null,"albumName":null,"duration":180.0,"instrumental":false,"plainLyrics":null,"syncedLyrics":null}"#;
+
+        let bare: Response = serde_json::from_str(&format!("[{}]", item)).unwrap();
+        assert_eq!(bare.total, None);
+        assert_eq!(bare.into_iter().map(|item| item.id).collect::<Vec<_>>(), vec![1]);
+
+        let paginated: Response = serde_json::from_str(&format!(r#"{{"items":[{}],"total":40}}"#, item)).unwrap();
+        assert_eq!(paginated.total, Some(40));
+        assert_eq!(paginated.items.len(), 1);
+    }
+
+    #[test]
+    fn test_search_item_language() {
+        let item = r#"{"id":1,"name":"Song","artistName":null,"albumName":null,"duration":180.0,"instrumental":false,"plainLyrics":null,"syncedLyrics":null,"language":"de"}"#;
+        let response: Response = serde_json::from_str(&format!("[{}]", item)).unwrap();
+        assert_eq!(response.items[0].language.as_deref(), Some("de"));
+
+        let without_language: Response = serde_json::from_str(r#"[{"id":2,"name":null,"artistName":null,"albumName":null,"duration":null,"instrumental":false,"plainLyrics":null,"syncedLyrics":null}]"#).unwrap();
+        assert_eq!(without_language.items[0].language, None);
+    }
+
+    #[test]
+    fn test_text_similarity() {
+        assert_eq!(text_similarity("Iris", "iris"), 1.0);
+        assert!(text_similarity("Iris", "Irises") > 0.5);
+        assert_eq!(text_similarity("Iris", "Yellow"), 0.0);
+        assert!(text_similarity("Iris", "Iris (Live)") >= 0.5);
+
+        assert_eq!(text_similarity("Hello World", "world, hello!"), 1.0);
+        assert_eq!(text_similarity("Hello There", "Goodbye World"), 0.0);
+        assert!((text_similarity("Bohemian Rhapsody", "Bohemian Rhapsody Remastered") - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_title_similarity() {
+        // Typos in every word leave no word overlap, but the edit distance is small
+        assert_eq!(text_similarity("Hello There", "Helo Ther"), 0.0);
+        assert!(title_similarity("Hello There", "Helo Ther") > 0.8);
+        // Reordered words keep the word overlap score
+        assert_eq!(title_similarity("Hello World", "world, hello!"), 1.0);
+        assert_eq!(title_similarity("Iris", "Yellow"), 0.0);
+    }
+
+    #[test]
+    fn test_rank() {
+        let items = vec![
+            search_item(1, "Yellow", 200.0),
+            search_item(2, "Iris", 205.0),
+            search_item(3, "Iris", 201.0),
+        ];
+
+        let ranked = rank(items, "Iris", 200.0);
+        assert_eq!(ranked.iter().map(|(item, _)| item.id).collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert!((ranked[0].1 - 0.95).abs() < 1e-9);
+        assert_eq!(ranked[1].1, 0.75);
+        assert_eq!(ranked[2].1, 0.5);
+    }
+}
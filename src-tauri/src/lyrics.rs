@@ -1,8 +1,8 @@
 use crate::lrclib::get::{request, Response};
-use crate::utils::strip_timestamp;
+use crate::utils::{atomic_write, strip_timestamp, RE_INSTRUMENTAL};
 use crate::lrclib::search;
-use crate::persistent_entities::PersistentTrack;
-use anyhow::Result;
+use crate::persistent_entities::{PersistentConfig, PersistentTrack};
+use anyhow::{Context, Result};
 use lofty::{
     config::{ParseOptions, WriteOptions},
     file::AudioFile,
@@ -11,21 +11,67 @@ use lofty::{
         BinaryFrame, Frame, FrameId, Id3v2Tag, SyncTextContentType, SynchronizedTextFrame,
         TimestampFormat, UnsynchronizedTextFrame,
     },
+    mp4::{Atom, AtomData, AtomIdent, Mp4File},
     mpeg::MpegFile,
+    ogg::{OpusFile, VorbisComments, VorbisFile},
     TextEncoding,
 };
-use lrc::Lyrics;
-use std::collections::HashSet;
-use std::fs::{remove_file, write, OpenOptions};
+use lrc::{Lyrics, TimeTag};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::{remove_file, OpenOptions};
 use std::io::Seek;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::LazyLock;
 use thiserror::Error;
+use tracing::warn;
 
 #[derive(Error, Clone, Debug)]
 pub enum GetLyricsError {
-    #[error("This track does not exist in LRCLIB database")]
-    NotFound,
+    #[error("This track does not exist in LRCLIB database{}", format_reason(.0))]
+    NotFound(Option<String>),
+    #[error(transparent)]
+    Path(#[from] LyricsPathError),
+}
+
+/// Reasons a sidecar `.txt`/`.lrc` path cannot be derived from a track path.
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum LyricsPathError {
+    #[error("Track path has no file name: {0}")]
+    NoFileStem(String),
+    #[error("Track path is not valid UTF-8: {}", .0.display())]
+    NonUtf8Path(PathBuf),
+}
+
+fn format_reason(reason: &Option<String>) -> String {
+    reason.as_ref().map(|reason| format!(" ({})", reason)).unwrap_or_default()
+}
+
+#[derive(Error, Debug)]
+pub enum ApplyLyricsError {
+    #[error("Lyrics were saved but could not be embedded: {0}")]
+    EmbedFailed(String),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+/// Whether saving lyrics may replace sidecar files that already exist on disk.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaveMode {
+    Overwrite,
+    /// Leaves existing sidecar files alone, e.g. `.lrc` files the user edited by hand.
+    KeepExisting,
+}
+
+#[derive(Error, Clone, Debug, PartialEq)]
+pub enum LrcValidationError {
+    #[error("Synced lyrics could not be parsed: {0}")]
+    ParseError(String),
+    #[error("Synced lyrics do not contain any timestamped lines")]
+    NoTimedLines,
+    #[error("Synced lyrics timestamps are not in chronological order")]
+    NonMonotonicTimestamps,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -36,116 +82,141 @@ pub enum MatchSource {
     None,
 }
 
+impl MatchSource {
+    /// Value stored in `tracks.lyrics_match_source`, `None` when nothing matched.
+    pub fn as_db_value(&self) -> Option<&'static str> {
+        match self {
+            MatchSource::Exact => Some("exact"),
+            MatchSource::DurationFallback => Some("duration_fallback"),
+            MatchSource::FuzzyFallback => Some("fuzzy_fallback"),
+            MatchSource::None => None,
+        }
+    }
+}
+
 const MIN_TITLE_SIMILARITY: f64 = 0.3;
+/// Duration tolerance search, then fuzzy search.
+const DEFAULT_MAX_FALLBACK_ATTEMPTS: u32 = 2;
+
+/// Controls how `download_lyrics_for_track` looks up lyrics and what it does with the result.
+#[derive(Clone, Debug)]
+pub struct DownloadOptions {
+    /// Replace lyrics even when the track already has synced lyrics.
+    pub force: bool,
+    /// Only look the lyrics up, without writing sidecar files or embedding.
+    pub dry_run: bool,
+    pub is_try_embed_lyrics: bool,
+    /// Primary LRCLIB instance first, then the fallbacks.
+    pub lrclib_instances: Vec<String>,
+    pub duration_tolerance: f64,
+    pub fuzzy_search_enabled: bool,
+    /// ISO 639-1 code of the language fallback searches prefer lyrics in.
+    pub preferred_lyrics_language: Option<String>,
+    /// How many fallback searches may run after the exact lookup finds nothing.
+    pub max_fallback_attempts: u32,
+}
+
+impl DownloadOptions {
+    pub fn from_config(config: &PersistentConfig) -> DownloadOptions {
+        DownloadOptions {
+            force: false,
+            dry_run: false,
+            is_try_embed_lyrics: config.try_embed_lyrics,
+            lrclib_instances: config.lrclib_instances(),
+            duration_tolerance: config.duration_tolerance,
+            fuzzy_search_enabled: config.fuzzy_search_enabled,
+            preferred_lyrics_language: config.preferred_lyrics_language.clone(),
+            max_fallback_attempts: DEFAULT_MAX_FALLBACK_ATTEMPTS,
+        }
+    }
+}
 
 pub async fn download_lyrics_for_track(
     track: PersistentTrack,
-    is_try_embed_lyrics: bool,
-    lrclib_instance: &str,
-    duration_tolerance: f64,
-    fuzzy_search_enabled: bool,
+    options: &DownloadOptions,
+    client: &reqwest::Client,
 ) -> Result<(Response, MatchSource)> {
-    let lyrics = request(
+    let (lyrics, source) = find_lyrics(
         &track.title,
         &track.album_name,
         &track.artist_name,
         track.duration,
-        lrclib_instance,
+        options,
+        client,
     )
     .await?;
 
+    let response = apply_downloaded_lyrics(track, lyrics, options).await?;
+    Ok((response, source))
+}
+
+/// Looks the lyrics up with the exact-match endpoint first, then with the fallback searches
+/// `options` allows, without applying anything.
+pub async fn find_lyrics(
+    title: &str,
+    album_name: &str,
+    artist_name: &str,
+    duration: f64,
+    options: &DownloadOptions,
+    client: &reqwest::Client,
+) -> Result<(Response, MatchSource)> {
+    let lrclib_instances: Vec<&str> = options.lrclib_instances.iter().map(String::as_str).collect();
+
+    let lyrics = request(title, album_name, artist_name, duration, &lrclib_instances, client).await?;
+
     // If exact match found, use it
-    if !matches!(lyrics, Response::None) {
-        let response = apply_lyrics_for_track(track, lyrics, is_try_embed_lyrics).await?;
-        return Ok((response, MatchSource::Exact));
+    if !matches!(lyrics, Response::None(_)) {
+        return Ok((lyrics, MatchSource::Exact));
     }
 
-    // Skip fallback searches if tolerance is 0
-    if duration_tolerance <= 0.0 {
-        let response = apply_lyrics_for_track(track, Response::None, is_try_embed_lyrics).await?;
-        return Ok((response, MatchSource::None));
+    // Skip fallback searches if tolerance is 0 or no fallbacks are allowed
+    if options.duration_tolerance <= 0.0 || options.max_fallback_attempts < 1 {
+        return Ok((lyrics, MatchSource::None));
     }
 
+    // The reason reported if nothing matches is the one from the last search that ran
+    let mut not_found = lyrics;
+
     // Fallback 1: field-based search with duration tolerance
     let fallback = search_with_duration_tolerance(
-        &track.title,
-        &track.album_name,
-        &track.artist_name,
-        track.duration,
-        duration_tolerance,
-        lrclib_instance,
+        title,
+        album_name,
+        artist_name,
+        duration,
+        options,
+        &lrclib_instances,
+        client,
     )
     .await;
 
-    if let Ok(ref lyrics) = fallback {
-        if !matches!(lyrics, Response::None) {
-            let response = apply_lyrics_for_track(track, fallback.unwrap(), is_try_embed_lyrics).await?;
-            return Ok((response, MatchSource::DurationFallback));
-        }
+    match fallback {
+        Ok(none @ Response::None(_)) => not_found = none,
+        Ok(lyrics) => return Ok((lyrics, MatchSource::DurationFallback)),
+        Err(_) => {}
     }
 
-    if !fuzzy_search_enabled {
-        let response = apply_lyrics_for_track(track, Response::None, is_try_embed_lyrics).await?;
-        return Ok((response, MatchSource::None));
+    if !options.fuzzy_search_enabled || options.max_fallback_attempts < 2 {
+        return Ok((not_found, MatchSource::None));
     }
 
     // Fallback 2: fuzzy q-based search with text similarity validation
     let fuzzy = search_fuzzy_fallback(
-        &track.title,
-        &track.artist_name,
-        track.duration,
-        duration_tolerance,
-        lrclib_instance,
+        title,
+        artist_name,
+        duration,
+        options,
+        &lrclib_instances,
+        client,
     )
     .await;
 
     match fuzzy {
-        Ok(lyrics) => {
-            let source = if matches!(lyrics, Response::None) {
-                MatchSource::None
-            } else {
-                MatchSource::FuzzyFallback
-            };
-            let response = apply_lyrics_for_track(track, lyrics, is_try_embed_lyrics).await?;
-            Ok((response, source))
-        }
-        Err(_) => {
-            let response = apply_lyrics_for_track(track, Response::None, is_try_embed_lyrics).await?;
-            Ok((response, MatchSource::None))
-        }
+        Ok(none @ Response::None(_)) => Ok((none, MatchSource::None)),
+        Err(_) => Ok((not_found, MatchSource::None)),
+        Ok(lyrics) => Ok((lyrics, MatchSource::FuzzyFallback)),
     }
 }
 
-fn normalize_text(s: &str) -> String {
-    s.to_lowercase()
-        .chars()
-        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
-        .collect::<String>()
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
-}
-
-fn text_similarity(a: &str, b: &str) -> f64 {
-    let a_norm = normalize_text(a);
-    let b_norm = normalize_text(b);
-
-    if a_norm.is_empty() && b_norm.is_empty() {
-        return 1.0;
-    }
-    if a_norm.is_empty() || b_norm.is_empty() {
-        return 0.0;
-    }
-
-    let a_words: HashSet<&str> = a_norm.split_whitespace().collect();
-    let b_words: HashSet<&str> = b_norm.split_whitespace().collect();
-
-    let intersection = a_words.intersection(&b_words).count();
-    let union = a_words.union(&b_words).count();
-
-    if union == 0 { 0.0 } else { intersection as f64 / union as f64 }
-}
-
 fn search_item_to_response(item: search::SearchItem) -> Response {
     match item.synced_lyrics {
         Some(synced) => {
@@ -158,40 +229,46 @@ fn search_item_to_response(item: search::SearchItem) -> Response {
                 if item.instrumental {
                     Response::IsInstrumental
                 } else {
-                    Response::None
+                    Response::None(Some("matched entry has no lyrics".to_owned()))
                 }
             }
         },
     }
 }
 
+/// The candidate within the duration tolerance with the most complete lyrics. Among equally
+/// complete ones, lyrics in `preferred_language` win, then the higher ranking score.
 fn pick_best_match(
-    results: impl IntoIterator<Item = search::SearchItem>,
+    ranked: impl IntoIterator<Item = (search::SearchItem, f64)>,
     duration: f64,
     duration_tolerance: f64,
+    preferred_language: Option<&str>,
 ) -> Option<search::SearchItem> {
-    results
+    ranked
         .into_iter()
-        .filter(|item| {
+        .filter(|(item, _)| {
             item.duration
                 .map(|d| (d - duration).abs() <= duration_tolerance)
                 .unwrap_or(false)
         })
-        .min_by(|a, b| {
-            let score = |item: &search::SearchItem| -> i32 {
+        .min_by(|(a, a_score), (b, b_score)| {
+            let lyrics_rank = |item: &search::SearchItem| -> i32 {
                 if item.synced_lyrics.is_some() { 0 }
                 else if item.plain_lyrics.is_some() { 1 }
                 else if item.instrumental { 2 }
                 else { 3 }
             };
-            let score_cmp = score(a).cmp(&score(b));
-            if score_cmp != std::cmp::Ordering::Equal {
-                return score_cmp;
-            }
-            let da = a.duration.map(|d| (d - duration).abs()).unwrap_or(f64::MAX);
-            let db = b.duration.map(|d| (d - duration).abs()).unwrap_or(f64::MAX);
-            da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+            let in_preferred_language = |item: &search::SearchItem| -> bool {
+                preferred_language.is_some_and(|preferred| {
+                    item.language.as_deref().is_some_and(|language| language.eq_ignore_ascii_case(preferred))
+                })
+            };
+            lyrics_rank(a)
+                .cmp(&lyrics_rank(b))
+                .then_with(|| in_preferred_language(b).cmp(&in_preferred_language(a)))
+                .then_with(|| b_score.partial_cmp(a_score).unwrap_or(std::cmp::Ordering::Equal))
         })
+        .map(|(item, _)| item)
 }
 
 async fn search_with_duration_tolerance(
@@ -199,14 +276,18 @@ async fn search_with_duration_tolerance(
     album_name: &str,
     artist_name: &str,
     duration: f64,
-    duration_tolerance: f64,
-    lrclib_instance: &str,
+    options: &DownloadOptions,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
 ) -> Result<Response> {
-    let results = search::request(title, album_name, artist_name, "", lrclib_instance).await?;
+    let ranked = search::request_ranked(title, album_name, artist_name, "", duration, lrclib_instances, client).await?;
+    if ranked.is_empty() {
+        return Ok(Response::None(Some("search returned 0 results".to_owned())));
+    }
 
-    match pick_best_match(results.0, duration, duration_tolerance) {
+    match pick_best_match(ranked, duration, options.duration_tolerance, options.preferred_lyrics_language.as_deref()) {
         Some(item) => Ok(search_item_to_response(item)),
-        None => Ok(Response::None),
+        None => Ok(Response::None(Some("all candidates exceeded duration tolerance".to_owned()))),
     }
 }
 
@@ -214,24 +295,31 @@ async fn search_fuzzy_fallback(
     title: &str,
     artist_name: &str,
     duration: f64,
-    duration_tolerance: f64,
-    lrclib_instance: &str,
+    options: &DownloadOptions,
+    lrclib_instances: &[&str],
+    client: &reqwest::Client,
 ) -> Result<Response> {
     let q = format!("{} {}", title, artist_name);
-    let results = search::request("", "", "", &q, lrclib_instance).await?;
+    let ranked = search::request_ranked(title, "", "", &q, duration, lrclib_instances, client).await?;
+    if ranked.is_empty() {
+        return Ok(Response::None(Some("search returned 0 results".to_owned())));
+    }
 
-    let candidates: Vec<_> = results.0.into_iter()
-        .filter(|item| {
+    let candidates: Vec<_> = ranked.into_iter()
+        .filter(|(item, _)| {
             let title_sim = item.name.as_deref()
-                .map(|n| text_similarity(title, n))
+                .map(|n| search::title_similarity(title, n))
                 .unwrap_or(0.0);
             title_sim >= MIN_TITLE_SIMILARITY
         })
         .collect();
+    if candidates.is_empty() {
+        return Ok(Response::None(Some("no search result had a similar title".to_owned())));
+    }
 
-    match pick_best_match(candidates, duration, duration_tolerance) {
+    match pick_best_match(candidates, duration, options.duration_tolerance, options.preferred_lyrics_language.as_deref()) {
         Some(item) => Ok(search_item_to_response(item)),
-        None => Ok(Response::None),
+        None => Ok(Response::None(Some("all candidates exceeded duration tolerance".to_owned()))),
     }
 }
 
@@ -240,12 +328,13 @@ pub async fn apply_string_lyrics_for_track(
     plain_lyrics: &str,
     synced_lyrics: &str,
     is_try_embed_lyrics: bool,
-) -> Result<()> {
-    save_plain_lyrics(&track.file_path, plain_lyrics)?;
-    save_synced_lyrics(&track.file_path, synced_lyrics)?;
+) -> Result<(), ApplyLyricsError> {
+    save_plain_lyrics(&track.file_path, plain_lyrics, SaveMode::Overwrite)?;
+    save_synced_lyrics(&track.file_path, synced_lyrics, SaveMode::Overwrite)?;
 
     if is_try_embed_lyrics {
-        embed_lyrics(&track.file_path, &plain_lyrics, &synced_lyrics);
+        embed_lyrics(&track.file_path, plain_lyrics, synced_lyrics)
+            .map_err(|err| ApplyLyricsError::EmbedFailed(err.to_string()))?;
     }
 
     Ok(())
@@ -255,99 +344,347 @@ pub async fn apply_lyrics_for_track(
     track: PersistentTrack,
     lyrics: Response,
     is_try_embed_lyrics: bool,
-) -> Result<Response> {
+    save_mode: SaveMode,
+) -> Result<Response, ApplyLyricsError> {
     match &lyrics {
         Response::SyncedLyrics(synced_lyrics, plain_lyrics) => {
-            save_synced_lyrics(&track.file_path, &synced_lyrics)?;
+            save_synced_lyrics(&track.file_path, synced_lyrics, save_mode)?;
             if is_try_embed_lyrics {
-                embed_lyrics(&track.file_path, &plain_lyrics, &synced_lyrics);
+                embed_lyrics(&track.file_path, plain_lyrics, synced_lyrics)
+                    .map_err(|err| ApplyLyricsError::EmbedFailed(err.to_string()))?;
             }
             Ok(lyrics)
         }
         Response::UnsyncedLyrics(plain_lyrics) => {
-            save_plain_lyrics(&track.file_path, &plain_lyrics)?;
+            save_plain_lyrics(&track.file_path, plain_lyrics, save_mode)?;
             if is_try_embed_lyrics {
-                embed_lyrics(&track.file_path, &plain_lyrics, "");
+                embed_lyrics(&track.file_path, plain_lyrics, "")
+                    .map_err(|err| ApplyLyricsError::EmbedFailed(err.to_string()))?;
             }
             Ok(lyrics)
         }
         Response::IsInstrumental => {
-            save_instrumental(&track.file_path)?;
+            save_instrumental(&track.file_path, save_mode)?;
             Ok(lyrics)
         }
         _ => Ok(lyrics),
     }
 }
 
-fn save_plain_lyrics(track_path: &str, lyrics: &str) -> Result<()> {
+/// Same as `apply_lyrics_for_track`, but never replaces sidecar files that already exist.
+/// Meant for downloads the user did not explicitly ask for, such as downloading on scan.
+pub async fn apply_lyrics_without_overwrite(
+    track: PersistentTrack,
+    lyrics: Response,
+    is_try_embed_lyrics: bool,
+) -> Result<Response, ApplyLyricsError> {
+    apply_lyrics_for_track(track, lyrics, is_try_embed_lyrics, SaveMode::KeepExisting).await
+}
+
+/// Used by the bulk download path, where a failed embed should not stop the download:
+/// the sidecar file is already written, so the failure is only logged.
+async fn apply_downloaded_lyrics(
+    track: PersistentTrack,
+    lyrics: Response,
+    options: &DownloadOptions,
+) -> Result<Response> {
+    // Synced lyrics are already the best result, so only a forced download replaces them
+    if options.dry_run || (!options.force && has_synced_lyrics(&track)) {
+        return Ok(lyrics);
+    }
+
+    let file_path = track.file_path.clone();
+
+    match apply_lyrics_for_track(track, lyrics.clone(), options.is_try_embed_lyrics, SaveMode::Overwrite).await {
+        Ok(response) => Ok(response),
+        Err(ApplyLyricsError::EmbedFailed(message)) => {
+            warn!("Cannot embed lyrics in `{}`: {}", file_path, message);
+            Ok(lyrics)
+        }
+        Err(ApplyLyricsError::Other(err)) => Err(err),
+    }
+}
+
+/// Whether the track has real synced lyrics, as opposed to the instrumental marker.
+pub fn has_synced_lyrics(track: &PersistentTrack) -> bool {
+    track.lrc_lyrics.as_ref().is_some_and(|lyrics| !RE_INSTRUMENTAL.is_match(lyrics))
+}
+
+fn save_plain_lyrics(track_path: &str, lyrics: &str, save_mode: SaveMode) -> Result<()> {
     let txt_path = build_txt_path(track_path)?;
     let lrc_path = build_lrc_path(track_path)?;
 
+    // Saving plain lyrics removes the .lrc file, so an existing one also counts as taken
+    if save_mode == SaveMode::KeepExisting && (txt_path.exists() || lrc_path.exists()) {
+        return Ok(());
+    }
+
     let _ = remove_file(lrc_path);
 
     if lyrics.is_empty() {
         let _ = remove_file(txt_path);
     } else {
-        write(txt_path, lyrics)?;
+        atomic_write(&txt_path, lyrics.as_bytes())?;
     }
     Ok(())
 }
 
-fn save_synced_lyrics(track_path: &str, lyrics: &str) -> Result<()> {
+fn save_synced_lyrics(track_path: &str, lyrics: &str, save_mode: SaveMode) -> Result<()> {
     let txt_path = build_txt_path(track_path)?;
     let lrc_path = build_lrc_path(track_path)?;
+
+    if save_mode == SaveMode::KeepExisting && lrc_path.exists() {
+        return Ok(());
+    }
     if lyrics.is_empty() {
         let _ = remove_file(lrc_path);
     } else {
+        if !RE_INSTRUMENTAL.is_match(lyrics) {
+            validate_lrc(lyrics)?;
+        }
         let _ = remove_file(txt_path);
-        write(lrc_path, lyrics)?;
+        atomic_write(&lrc_path, lyrics.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Checks that synced lyrics parse as LRC, contain timed lines, and that the lines are
+/// written in chronological order.
+pub fn validate_lrc(lyrics: &str) -> Result<(), LrcValidationError> {
+    let parsed = Lyrics::from_str(lyrics).map_err(|err| LrcValidationError::ParseError(err.to_string()))?;
+
+    if parsed.get_timed_lines().is_empty() {
+        return Err(LrcValidationError::NoTimedLines);
+    }
+
+    // The lrc crate sorts timed lines internally, so the order has to be checked on the raw text
+    let mut previous_timestamp = i64::MIN;
+    for line in lyrics.lines() {
+        let line = line.trim();
+        let Some(end) = line.find(']').filter(|_| line.starts_with('[')) else {
+            continue;
+        };
+
+        if let Ok(time_tag) = TimeTag::from_str(&line[..=end]) {
+            let timestamp = time_tag.get_timestamp();
+            if timestamp < previous_timestamp {
+                return Err(LrcValidationError::NonMonotonicTimestamps);
+            }
+            previous_timestamp = timestamp;
+        }
     }
+
     Ok(())
 }
 
-fn save_instrumental(track_path: &str) -> Result<()> {
+static RE_A2_LINE_TIMESTAMP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[(\d+):(\d+(?:\.\d+)?)\]").unwrap());
+static RE_A2_WORD_TIMESTAMP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(\d+):(\d+(?:\.\d+)?)>").unwrap());
+
+/// A word of A2 extended LRC together with the time it starts being sung.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct A2Word {
+    pub timestamp_ms: u32,
+    pub word: String,
+}
+
+/// A timed line of A2 extended LRC, split into its words.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct A2Line {
+    pub timestamp_ms: u32,
+    pub words: Vec<A2Word>,
+}
+
+fn a2_timestamp_ms(minutes: &str, seconds: &str) -> Option<u32> {
+    let minutes: u32 = minutes.parse().ok()?;
+    let seconds: f64 = seconds.parse().ok()?;
+    minutes.checked_mul(60_000)?.checked_add((seconds * 1000.0).round() as u32)
+}
+
+/// Whether synced lyrics use the A2 extension, where words carry inline `<mm:ss.xx>` timestamps.
+pub fn is_a2_lrc(lyrics: &str) -> bool {
+    lyrics
+        .lines()
+        .any(|line| RE_A2_LINE_TIMESTAMP.is_match(line.trim()) && RE_A2_WORD_TIMESTAMP.is_match(line))
+}
+
+/// Parses A2 extended LRC into timed lines of timed words. Text before the first word
+/// timestamp of a line starts at the line's own timestamp; lines without one are skipped.
+pub fn parse_a2_lrc(lyrics: &str) -> Vec<A2Line> {
+    lyrics
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let captures = RE_A2_LINE_TIMESTAMP.captures(line)?;
+            let line_timestamp_ms = a2_timestamp_ms(&captures[1], &captures[2])?;
+            let text = &line[captures[0].len()..];
+
+            let mut words = vec![];
+            let mut push_word = |timestamp_ms: u32, word: &str| {
+                let word = word.trim();
+                if !word.is_empty() {
+                    words.push(A2Word { timestamp_ms, word: word.to_owned() });
+                }
+            };
+
+            let mut word_timestamp_ms = line_timestamp_ms;
+            let mut word_start = 0;
+            for word_captures in RE_A2_WORD_TIMESTAMP.captures_iter(text) {
+                let tag = word_captures.get(0).unwrap();
+                push_word(word_timestamp_ms, &text[word_start..tag.start()]);
+                word_timestamp_ms = a2_timestamp_ms(&word_captures[1], &word_captures[2]).unwrap_or(word_timestamp_ms);
+                word_start = tag.end();
+            }
+            push_word(word_timestamp_ms, &text[word_start..]);
+
+            Some(A2Line { timestamp_ms: line_timestamp_ms, words })
+        })
+        .collect()
+}
+
+/// The parsed A2 lines as JSON, for storing next to the raw LRC. `None` unless the lyrics use A2.
+pub fn a2_lyrics_json(lyrics: &str) -> Option<String> {
+    if !is_a2_lrc(lyrics) {
+        return None;
+    }
+
+    serde_json::to_string(&parse_a2_lrc(lyrics)).ok()
+}
+
+/// How long the last line stays on screen when converting to subtitles, in milliseconds.
+const LAST_CUE_DURATION_MS: i64 = 3000;
+
+/// Converts synced lyrics to SRT subtitles. Each line ends when the next one starts.
+pub fn lrc_to_srt(synced_lyrics: &str) -> Result<String> {
+    let srt = lrc_to_cues(synced_lyrics)?
+        .iter()
+        .enumerate()
+        .map(|(index, (start, end, text))| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                index + 1,
+                format_cue_timestamp(*start, ','),
+                format_cue_timestamp(*end, ','),
+                text
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    Ok(srt)
+}
+
+/// Converts synced lyrics to WebVTT subtitles. Each line ends when the next one starts.
+pub fn lrc_to_vtt(synced_lyrics: &str) -> Result<String> {
+    let mut vtt = String::from("WEBVTT\n");
+
+    for (start, end, text) in lrc_to_cues(synced_lyrics)? {
+        vtt.push_str(&format!(
+            "\n{} --> {}\n{}\n",
+            format_cue_timestamp(start, '.'),
+            format_cue_timestamp(end, '.'),
+            text
+        ));
+    }
+
+    Ok(vtt)
+}
+
+/// Turns timed lines into `(start, end, text)` cues. Blank lines only mark where the previous
+/// line ends and don't produce a cue of their own.
+fn lrc_to_cues(synced_lyrics: &str) -> Result<Vec<(i64, i64, String)>> {
+    let lyrics = Lyrics::from_str(synced_lyrics)?;
+    let timed_lines = lyrics.get_timed_lines();
+
+    let cues = timed_lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, text))| !text.trim().is_empty())
+        .map(|(index, (time_tag, text))| {
+            let start = time_tag.get_timestamp().max(0);
+            let end = timed_lines
+                .get(index + 1)
+                .map(|(next_time_tag, _)| next_time_tag.get_timestamp())
+                .unwrap_or(start + LAST_CUE_DURATION_MS);
+            (start, end, text.trim().to_string())
+        })
+        .collect();
+
+    Ok(cues)
+}
+
+fn format_cue_timestamp(milliseconds: i64, fraction_separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        milliseconds / 3_600_000,
+        milliseconds / 60_000 % 60,
+        milliseconds / 1000 % 60,
+        fraction_separator,
+        milliseconds % 1000
+    )
+}
+
+fn save_instrumental(track_path: &str, save_mode: SaveMode) -> Result<()> {
     let txt_path = build_txt_path(track_path)?;
     let lrc_path = build_lrc_path(track_path)?;
 
+    if save_mode == SaveMode::KeepExisting && lrc_path.exists() {
+        return Ok(());
+    }
+
     let _ = remove_file(&lrc_path);
     let _ = remove_file(txt_path);
 
-    write(lrc_path, "[au: instrumental]")?;
+    atomic_write(&lrc_path, b"[au: instrumental]")?;
 
     Ok(())
 }
 
-fn build_txt_path(track_path: &str) -> Result<PathBuf> {
-    let path = Path::new(track_path);
-    let parent_path = path.parent().unwrap();
-    let file_name_without_extension = path.file_stem().unwrap().to_str().unwrap();
-    let txt_path =
-        Path::new(parent_path).join(format!("{}.{}", file_name_without_extension, "txt"));
+pub fn build_txt_path(track_path: &str) -> Result<PathBuf> {
+    Ok(build_sidecar_path(track_path, "txt")?)
+}
 
-    Ok(txt_path)
+pub fn build_lrc_path(track_path: &str) -> Result<PathBuf> {
+    Ok(build_sidecar_path(track_path, "lrc")?)
 }
 
-fn build_lrc_path(track_path: &str) -> Result<PathBuf> {
+fn build_sidecar_path(track_path: &str, extension: &str) -> Result<PathBuf, LyricsPathError> {
     let path = Path::new(track_path);
-    let parent_path = path.parent().unwrap();
-    let file_name_without_extension = path.file_stem().unwrap().to_str().unwrap();
-    let lrc_path =
-        Path::new(parent_path).join(format!("{}.{}", file_name_without_extension, "lrc"));
+    let file_name_without_extension = path
+        .file_stem()
+        .ok_or_else(|| LyricsPathError::NoFileStem(track_path.to_owned()))?
+        .to_str()
+        .ok_or_else(|| LyricsPathError::NonUtf8Path(path.to_path_buf()))?;
+    let parent_path = path
+        .parent()
+        .ok_or_else(|| LyricsPathError::NoFileStem(track_path.to_owned()))?;
 
-    Ok(lrc_path)
+    Ok(parent_path.join(format!("{}.{}", file_name_without_extension, extension)))
 }
 
-fn embed_lyrics(track_path: &str, plain_lyrics: &str, synced_lyrics: &str) {
-    if track_path.to_lowercase().ends_with(".mp3") {
-        match embed_lyrics_mp3(track_path, plain_lyrics, synced_lyrics) {
-            Ok(_) => (),
-            Err(e) => println!("Error embedding lyrics in MP3: {}", e),
-        }
-    } else if track_path.to_lowercase().ends_with(".flac") {
-        match embed_lyrics_flac(track_path, plain_lyrics, synced_lyrics) {
-            Ok(_) => (),
-            Err(e) => println!("Error embedding lyrics in FLAC: {}", e),
-        }
+fn embed_lyrics(track_path: &str, plain_lyrics: &str, synced_lyrics: &str) -> Result<()> {
+    let lowercase_path = track_path.to_lowercase();
+
+    if lowercase_path.ends_with(".mp3") {
+        embed_lyrics_mp3(track_path, plain_lyrics, synced_lyrics).context("Error embedding lyrics in MP3")
+    } else if lowercase_path.ends_with(".flac") {
+        embed_lyrics_flac(track_path, plain_lyrics, synced_lyrics).context("Error embedding lyrics in FLAC")
+    } else if lowercase_path.ends_with(".m4a") || lowercase_path.ends_with(".m4b") {
+        // MP4 has no standard atom for synced lyrics, so only plain lyrics are embedded
+        let plain_lyrics = if plain_lyrics.is_empty() && !synced_lyrics.is_empty() {
+            strip_timestamp(synced_lyrics)
+        } else {
+            plain_lyrics.to_owned()
+        };
+
+        embed_lyrics_m4a(track_path, &plain_lyrics).context("Error embedding lyrics in M4A")
+    } else if lowercase_path.ends_with(".ogg") {
+        embed_lyrics_ogg(track_path, plain_lyrics, synced_lyrics).context("Error embedding lyrics in OGG")
+    } else if lowercase_path.ends_with(".opus") {
+        embed_lyrics_opus(track_path, plain_lyrics, synced_lyrics).context("Error embedding lyrics in Opus")
+    } else {
+        Ok(())
     }
 }
 
@@ -355,21 +692,72 @@ fn embed_lyrics_flac(track_path: &str, plain_lyrics: &str, synced_lyrics: &str)
     let mut file_content = OpenOptions::new().read(true).write(true).open(track_path)?;
     let mut flac_file = FlacFile::read_from(&mut file_content, ParseOptions::new())?;
 
-    if let Some(vorbis_comments) = flac_file.vorbis_comments_mut() {
-        if !plain_lyrics.is_empty() {
-            vorbis_comments.insert("UNSYNCEDLYRICS".to_string(), plain_lyrics.to_string());
-        } else {
-            let _ = vorbis_comments.remove("UNSYNCEDLYRICS");
-        }
+    // A FLAC file without a comment block is still valid, so start from an empty one
+    let mut vorbis_comments = flac_file.remove_vorbis_comments().unwrap_or_default();
+    set_vorbis_comments_lyrics(&mut vorbis_comments, plain_lyrics, synced_lyrics);
+    flac_file.set_vorbis_comments(vorbis_comments);
+
+    file_content.seek(std::io::SeekFrom::Start(0))?;
+    flac_file.save_to(&mut file_content, WriteOptions::default())?;
+
+    Ok(())
+}
+
+fn embed_lyrics_ogg(track_path: &str, plain_lyrics: &str, synced_lyrics: &str) -> Result<()> {
+    embed_via_vorbis_comments(track_path, plain_lyrics, synced_lyrics, VorbisFile::vorbis_comments_mut)
+}
+
+fn embed_lyrics_opus(track_path: &str, plain_lyrics: &str, synced_lyrics: &str) -> Result<()> {
+    embed_via_vorbis_comments(track_path, plain_lyrics, synced_lyrics, OpusFile::vorbis_comments_mut)
+}
+
+/// Embeds lyrics in an OGG container whose Vorbis comments are always present.
+fn embed_via_vorbis_comments<F: AudioFile>(
+    track_path: &str,
+    plain_lyrics: &str,
+    synced_lyrics: &str,
+    vorbis_comments_mut: fn(&mut F) -> &mut VorbisComments,
+) -> Result<()> {
+    let mut file_content = OpenOptions::new().read(true).write(true).open(track_path)?;
+    let mut ogg_file = F::read_from(&mut file_content, ParseOptions::new())?;
+
+    set_vorbis_comments_lyrics(vorbis_comments_mut(&mut ogg_file), plain_lyrics, synced_lyrics);
+
+    file_content.seek(std::io::SeekFrom::Start(0))?;
+    ogg_file.save_to(&mut file_content, WriteOptions::default())?;
+
+    Ok(())
+}
+
+fn set_vorbis_comments_lyrics(vorbis_comments: &mut VorbisComments, plain_lyrics: &str, synced_lyrics: &str) {
+    if !plain_lyrics.is_empty() {
+        vorbis_comments.insert("UNSYNCEDLYRICS".to_string(), plain_lyrics.to_string());
+    } else {
+        let _ = vorbis_comments.remove("UNSYNCEDLYRICS");
+    }
 
-        if !synced_lyrics.is_empty() {
-            vorbis_comments.insert("LYRICS".to_string(), synced_lyrics.to_string());
+    if !synced_lyrics.is_empty() {
+        vorbis_comments.insert("LYRICS".to_string(), synced_lyrics.to_string());
+    } else {
+        let _ = vorbis_comments.remove("LYRICS");
+    }
+}
+
+fn embed_lyrics_m4a(track_path: &str, plain_lyrics: &str) -> Result<()> {
+    const LYRICS_ATOM: AtomIdent = AtomIdent::Fourcc(*b"\xa9lyr");
+
+    let mut file_content = OpenOptions::new().read(true).write(true).open(track_path)?;
+    let mut mp4_file = Mp4File::read_from(&mut file_content, ParseOptions::new())?;
+
+    if let Some(ilst) = mp4_file.ilst_mut() {
+        if !plain_lyrics.is_empty() {
+            ilst.replace_atom(Atom::new(LYRICS_ATOM, AtomData::UTF8(plain_lyrics.to_string())));
         } else {
-            let _ = vorbis_comments.remove("LYRICS");
+            let _ = ilst.remove(&LYRICS_ATOM);
         }
 
         file_content.seek(std::io::SeekFrom::Start(0))?;
-        flac_file.save_to(&mut file_content, WriteOptions::default())?;
+        mp4_file.save_to(&mut file_content, WriteOptions::default())?;
     }
 
     Ok(())
@@ -379,13 +767,14 @@ fn embed_lyrics_mp3(track_path: &str, plain_lyrics: &str, synced_lyrics: &str) -
     let mut file_content = OpenOptions::new().read(true).write(true).open(track_path)?;
     let mut mp3_file = MpegFile::read_from(&mut file_content, ParseOptions::new())?;
 
-    if let Some(id3v2) = mp3_file.id3v2_mut() {
-        insert_id3v2_uslt_frame(id3v2, plain_lyrics)?;
-        insert_id3v2_sylt_frame(id3v2, synced_lyrics)?;
+    // MP3s encoded without any ID3 tag are common, so create one instead of skipping the file
+    let mut id3v2 = mp3_file.remove_id3v2().unwrap_or_default();
+    insert_id3v2_uslt_frame(&mut id3v2, plain_lyrics)?;
+    insert_id3v2_sylt_frame(&mut id3v2, synced_lyrics)?;
+    mp3_file.set_id3v2(id3v2);
 
-        file_content.seek(std::io::SeekFrom::Start(0))?;
-        mp3_file.save_to(&mut file_content, WriteOptions::default())?;
-    }
+    file_content.seek(std::io::SeekFrom::Start(0))?;
+    mp3_file.save_to(&mut file_content, WriteOptions::default())?;
 
     Ok(())
 }
@@ -443,3 +832,194 @@ fn synced_lyrics_to_sylt_vec(synced_lyrics: &str) -> Result<Vec<(u32, String)>>
 
     Ok(converted_lyrics)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_lrc_path, build_sidecar_path, build_txt_path, embed_lyrics_flac, embed_lyrics_mp3, is_a2_lrc,
+        lrc_to_srt, lrc_to_vtt, parse_a2_lrc, pick_best_match, validate_lrc, A2Word, LrcValidationError,
+        LyricsPathError,
+    };
+    use crate::lrclib::search::SearchItem;
+    use lofty::config::ParseOptions;
+    use lofty::file::AudioFile;
+    use lofty::flac::FlacFile;
+    use lofty::id3::v2::FrameId;
+    use lofty::mpeg::MpegFile;
+    use std::fs::{self, File};
+    use std::path::PathBuf;
+
+    const SNIPPET: &str = "[00:01.00]First line\n[00:03.50]Second line\n[00:05.00]\n[01:02.25]Last line";
+
+    #[test]
+    fn test_validate_lrc_valid() {
+        let lyrics = "[ar:Artist]\n[00:01.00]First line\n[00:02.50]Second line\n[00:04.00]";
+        assert_eq!(validate_lrc(lyrics), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_lrc_syntax_error() {
+        let lyrics = "[00:01.00]First line\n[00:02.00]Second [ar:oops] line";
+        assert!(matches!(validate_lrc(lyrics), Err(LrcValidationError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_validate_lrc_no_timed_lines() {
+        assert_eq!(validate_lrc("Just some plain text"), Err(LrcValidationError::NoTimedLines));
+    }
+
+    #[test]
+    fn test_parse_a2_lrc() {
+        let lyrics = "[ar:Artist]\n[00:01.00]<00:01.00>Hello <00:01.50>big <00:02.25>world\n[00:03.00]Plain line\n[01:04.10]Oh <01:05.00>yeah <01:05.50>";
+        assert!(is_a2_lrc(lyrics));
+        assert!(!is_a2_lrc(SNIPPET));
+
+        let lines = parse_a2_lrc(lyrics);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].timestamp_ms, 1000);
+        assert_eq!(
+            lines[0].words,
+            vec![
+                A2Word { timestamp_ms: 1000, word: "Hello".to_owned() },
+                A2Word { timestamp_ms: 1500, word: "big".to_owned() },
+                A2Word { timestamp_ms: 2250, word: "world".to_owned() },
+            ]
+        );
+        assert_eq!(lines[1].words, vec![A2Word { timestamp_ms: 3000, word: "Plain line".to_owned() }]);
+        assert_eq!(lines[2].timestamp_ms, 64100);
+        assert_eq!(
+            lines[2].words,
+            vec![
+                A2Word { timestamp_ms: 64100, word: "Oh".to_owned() },
+                A2Word { timestamp_ms: 65000, word: "yeah".to_owned() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_a2_lrc_oversized_minutes() {
+        // Timestamps past the u32 millisecond range are dropped instead of overflowing
+        let lyrics = "[99999:00.00]<00:01.00>Skipped\n[00:02.00]<00:02.00>Kept <99999:00>word";
+        let lines = parse_a2_lrc(lyrics);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(
+            lines[0].words,
+            vec![
+                A2Word { timestamp_ms: 2000, word: "Kept".to_owned() },
+                A2Word { timestamp_ms: 2000, word: "word".to_owned() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_lrc_out_of_order() {
+        let lyrics = "[00:05.00]First line\n[00:02.00]Second line";
+        assert_eq!(validate_lrc(lyrics), Err(LrcValidationError::NonMonotonicTimestamps));
+    }
+
+    #[test]
+    fn test_pick_best_match_prefers_language() {
+        let candidate = |id: i64, language: Option<&str>, score: f64| {
+            let item = SearchItem {
+                id,
+                name: Some("Song".to_owned()),
+                artist_name: None,
+                album_name: None,
+                duration: Some(200.0),
+                instrumental: false,
+                plain_lyrics: None,
+                synced_lyrics: Some("[00:01.00]Line".to_owned()),
+                language: language.map(str::to_owned),
+            };
+            (item, score)
+        };
+        let candidates = || vec![candidate(1, Some("en"), 0.9), candidate(2, Some("de"), 0.8), candidate(3, None, 0.7)];
+
+        assert_eq!(pick_best_match(candidates(), 200.0, 3.0, None).map(|item| item.id), Some(1));
+        assert_eq!(pick_best_match(candidates(), 200.0, 3.0, Some("de")).map(|item| item.id), Some(2));
+        assert_eq!(pick_best_match(candidates(), 200.0, 3.0, Some("ja")).map(|item| item.id), Some(1));
+    }
+
+    #[test]
+    fn test_lrc_to_srt() {
+        let expected = "1\n00:00:01,000 --> 00:00:03,500\nFirst line\n\n\
+                        2\n00:00:03,500 --> 00:00:05,000\nSecond line\n\n\
+                        3\n00:01:02,250 --> 00:01:05,250\nLast line\n";
+        assert_eq!(lrc_to_srt(SNIPPET).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_lrc_to_vtt() {
+        let expected = "WEBVTT\n\n\
+                        00:00:01.000 --> 00:00:03.500\nFirst line\n\n\
+                        00:00:03.500 --> 00:00:05.000\nSecond line\n\n\
+                        00:01:02.250 --> 00:01:05.250\nLast line\n";
+        assert_eq!(lrc_to_vtt(SNIPPET).unwrap(), expected);
+    }
+
+    /// A few silent MPEG-1 Layer III frames with no tags at all.
+    fn tagless_mp3() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for _ in 0..20 {
+            bytes.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            bytes.extend_from_slice(&[0; 413]);
+        }
+        bytes
+    }
+
+    /// A FLAC stream with only STREAMINFO and PADDING blocks.
+    fn tagless_flac() -> Vec<u8> {
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend_from_slice(&[0x00, 0, 0, 34]);
+        bytes.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x0A, 0xC4, 0x42, 0xF0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&[0x81, 0, 0, 16]);
+        bytes.extend_from_slice(&[0; 16]);
+        bytes
+    }
+
+    fn fixture_path(file_name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("lrcget-{}-{}", std::process::id(), file_name))
+    }
+
+    #[test]
+    fn test_embed_lyrics_mp3_without_id3v2() {
+        let path = fixture_path("tagless.mp3");
+        fs::write(&path, tagless_mp3()).unwrap();
+
+        embed_lyrics_mp3(path.to_str().unwrap(), "First line", SNIPPET).unwrap();
+
+        let mp3_file = MpegFile::read_from(&mut File::open(&path).unwrap(), ParseOptions::new()).unwrap();
+        let id3v2 = mp3_file.id3v2().expect("ID3v2 tag should have been created");
+        assert!(id3v2.get(&FrameId::new("USLT").unwrap()).is_some());
+        assert!(id3v2.get(&FrameId::new("SYLT").unwrap()).is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_embed_lyrics_flac_without_vorbis_comments() {
+        let path = fixture_path("tagless.flac");
+        fs::write(&path, tagless_flac()).unwrap();
+
+        embed_lyrics_flac(path.to_str().unwrap(), "First line", SNIPPET).unwrap();
+
+        let flac_file = FlacFile::read_from(&mut File::open(&path).unwrap(), ParseOptions::new()).unwrap();
+        let vorbis_comments = flac_file.vorbis_comments().expect("Vorbis comments should have been created");
+        assert_eq!(vorbis_comments.get("UNSYNCEDLYRICS"), Some("First line"));
+        assert_eq!(vorbis_comments.get("LYRICS"), Some(SNIPPET));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_build_sidecar_paths() {
+        assert_eq!(build_lrc_path("/music/song.flac").unwrap(), PathBuf::from("/music/song.lrc"));
+        assert_eq!(build_txt_path("song.mp3").unwrap(), PathBuf::from("song.txt"));
+        assert_eq!(
+            build_sidecar_path("/", "lrc"),
+            Err(LyricsPathError::NoFileStem("/".to_owned()))
+        );
+    }
+}
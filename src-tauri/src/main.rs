@@ -7,6 +7,7 @@ pub mod commands;
 pub mod db;
 pub mod fs_track;
 pub mod library;
+pub mod logging;
 pub mod lrclib;
 pub mod lyrics;
 pub mod persistent_entities;
@@ -14,10 +15,11 @@ pub mod player;
 pub mod state;
 pub mod utils;
 
-use commands::{library_cmd, lyrics_cmd, player_cmd};
+use commands::{library_cmd, lyrics_cmd, player_cmd, playlist_cmd};
 use player::Player;
-use state::{AppState, Notify, NotifyType};
+use state::{AppState, Notify, NotifyType, ServiceAccess};
 use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::error;
 
 #[tauri::command]
 fn open_devtools(app_handle: AppHandle) {
@@ -28,6 +30,8 @@ fn open_devtools(app_handle: AppHandle) {
 
 #[tokio::main]
 async fn main() {
+    let log_filter = logging::init();
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
@@ -35,30 +39,44 @@ async fn main() {
         .plugin(tauri_plugin_os::init())
         .manage(AppState {
             db: Default::default(),
+            track_cache: Default::default(),
+            http_client: std::sync::RwLock::new(lrclib::HTTP_CLIENT.clone()),
             player: Default::default(),
+            log_filter,
+            challenge_cancels: Default::default(),
+            scan_cancel: Default::default(),
         })
         .setup(|app| {
             let handle = app.handle();
 
             let app_state: State<AppState> = handle.state();
             let db = db::initialize_database(&handle).expect("Database initialize should succeed");
-            *app_state.db.lock().expect("Database mutex poisoned during setup") = Some(db);
+            let config = db
+                .get()
+                .map_err(anyhow::Error::from)
+                .and_then(|conn| db::get_config(&conn))
+                .expect("Config should be readable after migrations");
+            *app_state.http_client.write().expect("HTTP client lock poisoned during setup") =
+                lrclib::build_http_client(config.http_proxy.as_deref());
+            *app_state.db.write().expect("Database lock poisoned during setup") = Some(db);
 
-            let maybe_player = Player::new();
+            let maybe_player = Player::new(
+                config.audio_output_device.as_deref(),
+                config.playback_speed,
+                config.last_volume,
+            );
             match maybe_player {
-                Ok(player) => {
+                Ok(mut player) => {
+                    player.set_replaygain_enabled(config.enable_replaygain);
                     *app_state.player.lock().expect("Player mutex poisoned during setup") = Some(player);
                 }
                 Err(e) => {
-                    eprintln!("Failed to initialize audio player: {}", e);
+                    error!("Failed to initialize audio player: {}", e);
                     let handle_for_notify = handle.clone();
                     let msg = format!("Failed to initialize audio player: {}", e);
                     tokio::spawn(async move {
                         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-                        let _ = handle_for_notify.emit("app-notification", Notify {
-                            message: msg,
-                            notify_type: NotifyType::Error,
-                        });
+                        let _ = handle_for_notify.emit("app-notification", Notify::new(msg, NotifyType::Error));
                     });
                 }
             }
@@ -76,17 +94,45 @@ async fn main() {
                         match player_guard {
                             Ok(mut player_guard) => {
                                 if let Some(ref mut player) = *player_guard {
-                                    player.renew_state();
+                                    let next_track_id = player.renew_state();
+
+                                    if let Some(ended_track_id) = player.take_ended_track_id() {
+                                        let _ = handle_clone.emit("track-ended", ended_track_id);
+                                    }
+
+                                    if let Some(next_track_id) = next_track_id {
+                                        let play_next = handle_clone
+                                            .cached_track(next_track_id)
+                                            .and_then(|track| player.play(track));
+
+                                        match play_next {
+                                            Ok(()) => {
+                                                if let Err(e) = handle_clone.db_write_track(next_track_id, |db| db::increment_play_count(next_track_id, db)) {
+                                                    error!("Failed to update play count: {}", e);
+                                                }
+                                            }
+                                            Err(e) => error!("Failed to play next track: {}", e),
+                                        }
+                                    }
+
+                                    if player.take_sleep_timer_expired() {
+                                        let _ = handle_clone.emit(
+                                            "app-notification",
+                                            Notify::new("Sleep timer expired", NotifyType::Warning),
+                                        );
+                                    }
 
-                                    let emit_player_state =
-                                        handle_clone.emit("player-state", &player);
+                                    if player.take_state_change() {
+                                        let emit_player_state =
+                                            handle_clone.emit("player-state", &player);
 
-                                    if let Err(e) = emit_player_state {
-                                        eprintln!("Failed to emit player state: {}", e);
+                                        if let Err(e) = emit_player_state {
+                                            error!("Failed to emit player state: {}", e);
+                                        }
                                     }
                                 }
                             }
-                            Err(e) => eprintln!("Failed to lock player: {}", e),
+                            Err(e) => error!("Failed to lock player: {}", e),
                         }
                     }
                 }
@@ -103,21 +149,56 @@ async fn main() {
             library_cmd::initialize_library,
             library_cmd::uninitialize_library,
             library_cmd::refresh_library,
+            library_cmd::cancel_library_scan,
             library_cmd::get_tracks,
+            library_cmd::get_track_summaries,
+            library_cmd::get_tracks_paginated,
+            library_cmd::count_tracks_total,
             library_cmd::get_track_ids,
             library_cmd::get_track,
+            library_cmd::get_track_by_file_path,
+            library_cmd::get_track_by_isrc,
+            library_cmd::set_track_favorite,
+            library_cmd::get_favorite_track_ids,
+            library_cmd::get_recently_added_tracks,
+            library_cmd::get_tracks_with_stale_lyrics,
+            library_cmd::get_most_played_track_ids,
+            library_cmd::get_most_played_tracks,
+            library_cmd::get_genres,
+            library_cmd::get_tracks_by_genre,
+            library_cmd::get_composers,
+            library_cmd::get_tracks_by_composer,
             library_cmd::get_albums,
             library_cmd::get_album_ids,
             library_cmd::get_album,
             library_cmd::get_artists,
             library_cmd::get_artist_ids,
             library_cmd::get_artist,
+            library_cmd::get_artist_albums,
+            library_cmd::get_artist_album_ids,
             library_cmd::get_album_tracks,
             library_cmd::get_artist_tracks,
             library_cmd::get_album_track_ids,
             library_cmd::get_artist_track_ids,
             library_cmd::get_library_stats,
+            library_cmd::get_directory_stats,
+            library_cmd::export_lyrics_archive,
+            library_cmd::export_library_to_json,
+            library_cmd::validate_lyrics_files,
+            library_cmd::fix_lyrics_discrepancies,
+            library_cmd::get_duplicate_tracks,
+            library_cmd::delete_duplicate_keeping_first,
+            library_cmd::delete_track,
+            library_cmd::rescan_single_track,
+            library_cmd::set_log_level,
+            library_cmd::vacuum_database,
+            library_cmd::analyze_database,
+            library_cmd::backup_database,
+            library_cmd::extract_album_art,
+            library_cmd::get_album_art,
             lyrics_cmd::download_lyrics,
+            lyrics_cmd::batch_download_lyrics,
+            lyrics_cmd::preview_lyrics,
             lyrics_cmd::apply_lyrics,
             lyrics_cmd::retrieve_lyrics,
             lyrics_cmd::retrieve_lyrics_by_id,
@@ -125,12 +206,39 @@ async fn main() {
             lyrics_cmd::save_lyrics,
             lyrics_cmd::publish_lyrics,
             lyrics_cmd::flag_lyrics,
+            lyrics_cmd::cancel_challenge,
+            lyrics_cmd::validate_lrclib_instance,
+            lyrics_cmd::convert_lrc_to_srt,
+            lyrics_cmd::convert_lrc_to_vtt,
+            lyrics_cmd::adjust_lrc_offset,
+            lyrics_cmd::get_lrc_metadata,
             player_cmd::play_track,
             player_cmd::pause_track,
             player_cmd::resume_track,
             player_cmd::seek_track,
             player_cmd::stop_track,
+            player_cmd::next_track,
+            player_cmd::prev_track,
             player_cmd::set_volume,
+            player_cmd::set_playback_mode,
+            player_cmd::set_playback_speed,
+            player_cmd::set_sleep_timer,
+            player_cmd::cancel_sleep_timer,
+            player_cmd::get_sleep_timer_remaining,
+            player_cmd::enqueue_tracks,
+            player_cmd::enqueue_track,
+            player_cmd::dequeue_track,
+            player_cmd::clear_queue,
+            player_cmd::get_queue,
+            player_cmd::get_output_devices,
+            player_cmd::set_output_device,
+            playlist_cmd::get_playlists,
+            playlist_cmd::create_playlist,
+            playlist_cmd::rename_playlist,
+            playlist_cmd::delete_playlist,
+            playlist_cmd::add_track_to_playlist,
+            playlist_cmd::remove_track_from_playlist,
+            playlist_cmd::get_playlist_track_ids,
             open_devtools,
         ])
         .run(tauri::generate_context!())
@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
 pub struct LibraryStats {
@@ -7,9 +7,16 @@ pub struct LibraryStats {
     pub synced: i64,
     pub plain_only: i64,
     pub missing: i64,
+    pub total_duration_seconds: f64,
 }
 
 #[derive(Serialize)]
+pub struct DirectoryStats {
+    pub directory: String,
+    pub stats: LibraryStats,
+}
+
+#[derive(Clone, Serialize)]
 pub struct PersistentTrack {
     pub id: i64,
     pub file_path: String,
@@ -27,6 +34,62 @@ pub struct PersistentTrack {
     pub duration: f64,
     pub instrumental: bool,
     pub bitrate: Option<i64>,
+    pub genre: Option<String>,
+    pub disc_number: Option<i64>,
+    pub play_count: i64,
+    pub last_played_at: Option<i64>,
+    pub is_favorite: bool,
+    pub replaygain_track_gain: Option<f64>,
+    pub composer: Option<String>,
+    pub lyrics_match_source: Option<String>,
+    /// Unix timestamp of when the track was added to the library, or 0 for tracks
+    /// scanned before this was tracked.
+    pub added_at: i64,
+    /// International Standard Recording Code, stored in its compact 12-character form.
+    pub isrc: Option<String>,
+    /// Unix timestamp of when the app last wrote this track's lyrics, if it ever has.
+    pub lyrics_last_updated: Option<i64>,
+    /// Word-level timings parsed from `lrc_lyrics` as JSON, when those use the A2 extension.
+    pub a2_lyrics: Option<String>,
+    /// Release year from the track's own tags, which may differ from its album's year on compilations.
+    pub year: Option<i64>,
+}
+
+/// `PersistentTrack` without the lyrics text, for list views where loading every
+/// track's lyrics would waste memory. Fetch the full track to read its lyrics.
+#[derive(Clone, Serialize)]
+pub struct PersistentTrackSummary {
+    pub id: i64,
+    pub file_path: String,
+    pub file_name: String,
+    pub title: String,
+    pub album_name: String,
+    pub album_artist_name: Option<String>,
+    pub album_id: i64,
+    pub artist_name: String,
+    pub artist_id: i64,
+    pub image_path: Option<String>,
+    pub track_number: Option<i64>,
+    pub duration: f64,
+    pub instrumental: bool,
+    pub bitrate: Option<i64>,
+    pub genre: Option<String>,
+    pub disc_number: Option<i64>,
+    pub play_count: i64,
+    pub last_played_at: Option<i64>,
+    pub is_favorite: bool,
+    pub replaygain_track_gain: Option<f64>,
+    pub composer: Option<String>,
+    pub lyrics_match_source: Option<String>,
+    /// Unix timestamp of when the track was added to the library, or 0 for tracks
+    /// scanned before this was tracked.
+    pub added_at: i64,
+    /// International Standard Recording Code, stored in its compact 12-character form.
+    pub isrc: Option<String>,
+    /// Unix timestamp of when the app last wrote this track's lyrics, if it ever has.
+    pub lyrics_last_updated: Option<i64>,
+    /// Release year from the track's own tags, which may differ from its album's year on compilations.
+    pub year: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -37,6 +100,7 @@ pub struct PersistentAlbum {
     pub artist_name: String,
     pub album_artist_name: Option<String>,
     pub tracks_count: i64,
+    pub year: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -48,6 +112,14 @@ pub struct PersistentArtist {
 }
 
 #[derive(Serialize)]
+pub struct PersistentPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+    pub tracks_count: i64,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct PersistentConfig {
     pub skip_tracks_with_synced_lyrics: bool,
     pub skip_tracks_with_plain_lyrics: bool,
@@ -55,7 +127,47 @@ pub struct PersistentConfig {
     pub try_embed_lyrics: bool,
     pub theme_mode: String,
     pub lrclib_instance: String,
+    /// Tried in order when `lrclib_instance` can't be reached.
+    #[serde(default)]
+    pub lrclib_fallback_instances: Vec<String>,
     pub lyrics_type_preference: String,
     pub duration_tolerance: f64,
     pub fuzzy_search_enabled: bool,
+    pub http_proxy: Option<String>,
+    pub audio_output_device: Option<String>,
+    pub enable_replaygain: bool,
+    pub playback_speed: f32,
+    /// Also read sidecar lyrics from a `Lyrics/` subdirectory next to the track when scanning.
+    #[serde(default)]
+    pub check_lyrics_subdirectory: bool,
+    /// How many lyrics downloads a batch may run at once, kept within
+    /// `MIN_CONCURRENT_DOWNLOADS..=MAX_CONCURRENT_DOWNLOADS`.
+    #[serde(default = "default_max_concurrent_downloads")]
+    pub max_concurrent_downloads: u32,
+    /// ISO 639-1 code of the language fallback searches prefer lyrics in, if any.
+    #[serde(default)]
+    pub preferred_lyrics_language: Option<String>,
+    /// Saved by the `set_volume` command rather than `set_config`.
+    #[serde(default = "default_volume")]
+    pub last_volume: f64,
+}
+
+fn default_volume() -> f64 {
+    1.0
+}
+
+fn default_max_concurrent_downloads() -> u32 {
+    3
+}
+
+impl PersistentConfig {
+    pub const MIN_CONCURRENT_DOWNLOADS: u32 = 1;
+    pub const MAX_CONCURRENT_DOWNLOADS: u32 = 20;
+
+    /// The primary LRCLIB instance followed by the fallback instances.
+    pub fn lrclib_instances(&self) -> Vec<String> {
+        std::iter::once(self.lrclib_instance.clone())
+            .chain(self.lrclib_fallback_instances.iter().cloned())
+            .collect()
+    }
 }
@@ -1,9 +1,15 @@
-use anyhow::Result;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use cpal::traits::{DeviceTrait, HostTrait};
 use kira::{
     AudioManager,
     AudioManagerSettings,
     Decibels,
     DefaultBackend,
+    backend::cpal::CpalBackendSettings,
     sound::{
         streaming::{StreamingSoundData, StreamingSoundHandle},
         FromFileError, PlaybackState,
@@ -13,8 +19,24 @@ use kira::{
 
 use crate::persistent_entities::PersistentTrack;
 use serde::Serialize;
+use tracing::warn;
+
+const HISTORY_LIMIT: usize = 50;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+/// Going back within this many seconds of a track's start skips to the previous track
+/// instead of restarting the current one
+const RESTART_THRESHOLD_SECS: f64 = 3.0;
+/// Position changes smaller than this don't count as a state change worth emitting
+const PROGRESS_JITTER_SECS: f64 = 0.01;
 
 #[derive(Serialize)]
+pub struct AudioDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum PlayerStatus {
     Playing,
@@ -22,6 +44,60 @@ pub enum PlayerStatus {
     Stopped,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackMode {
+    Normal,
+    RepeatOne,
+    RepeatAll,
+    Shuffle,
+}
+
+impl FromStr for PlaybackMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> Result<Self> {
+        match mode {
+            "normal" => Ok(PlaybackMode::Normal),
+            "repeat_one" => Ok(PlaybackMode::RepeatOne),
+            "repeat_all" => Ok(PlaybackMode::RepeatAll),
+            "shuffle" => Ok(PlaybackMode::Shuffle),
+            _ => Err(anyhow!("Unknown playback mode: {}", mode)),
+        }
+    }
+}
+
+/// Snapshot of the serialized player fields, kept to tell whether the next `player-state`
+/// event would carry anything new.
+#[derive(Clone)]
+struct SerializedPlayerState {
+    current_track_id: Option<i64>,
+    status: PlayerStatus,
+    progress: f64,
+    duration: f64,
+    volume: f64,
+    speed: f32,
+    mode: PlaybackMode,
+    queue: VecDeque<i64>,
+    history: VecDeque<i64>,
+    sleep_timer_remaining_secs: Option<u64>,
+}
+
+impl PartialEq for SerializedPlayerState {
+    fn eq(&self, other: &Self) -> bool {
+        (self.progress - other.progress).abs() < PROGRESS_JITTER_SECS
+            && self.current_track_id == other.current_track_id
+            && self.status == other.status
+            && self.duration == other.duration
+            && self.volume == other.volume
+            && self.speed == other.speed
+            && self.mode == other.mode
+            && self.queue == other.queue
+            && self.history == other.history
+            && self.sleep_timer_remaining_secs == other.sleep_timer_remaining_secs
+    }
+}
+
 #[derive(Serialize)]
 pub struct Player {
     #[serde(skip)]
@@ -30,28 +106,142 @@ pub struct Player {
     sound_handle: Option<StreamingSoundHandle<FromFileError>>,
     #[serde(skip)]
     pub track: Option<PersistentTrack>,
+    /// ID of `track`, sent along with the state so listeners can follow queue advances
+    pub current_track_id: Option<i64>,
     pub status: PlayerStatus,
     pub progress: f64,
     pub duration: f64,
     pub volume: f64,
+    pub speed: f32,
+    #[serde(skip)]
+    replaygain_enabled: bool,
+    /// Linear gain applied on top of `volume` for the current track
+    #[serde(skip)]
+    track_gain: f64,
+    pub mode: PlaybackMode,
+    pub queue: VecDeque<i64>,
+    /// Recently played track IDs, most recent last
+    pub history: VecDeque<i64>,
+    #[serde(skip)]
+    sleep_deadline: Option<Instant>,
+    #[serde(skip)]
+    sleep_timer_expired: bool,
+    /// Whether the current track had already played to the end at the last `renew_state`
+    #[serde(skip)]
+    track_finished: bool,
+    /// Track that played to the end since the last `take_ended_track_id`
+    #[serde(skip)]
+    ended_track_id: Option<i64>,
+    pub sleep_timer_remaining_secs: Option<u64>,
+    #[serde(skip)]
+    last_emitted_state: Option<SerializedPlayerState>,
 }
 
 impl Player {
-    pub fn new() -> Result<Player> {
-        let manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())?;
+    pub fn new(output_device: Option<&str>, speed: f32, volume: f64) -> Result<Player> {
+        let manager = Self::create_manager(output_device)?;
 
         Ok(Player {
             manager,
             sound_handle: None,
             track: None,
+            current_track_id: None,
             status: PlayerStatus::Stopped,
             progress: 0.0,
             duration: 0.0,
-            volume: 1.0,
+            volume: Self::clamp_volume(volume).unwrap_or(1.0),
+            speed: Self::clamp_speed(speed).unwrap_or(1.0),
+            replaygain_enabled: false,
+            track_gain: 1.0,
+            mode: PlaybackMode::Normal,
+            queue: VecDeque::new(),
+            history: VecDeque::new(),
+            sleep_deadline: None,
+            sleep_timer_expired: false,
+            track_finished: false,
+            ended_track_id: None,
+            sleep_timer_remaining_secs: None,
+            last_emitted_state: None,
         })
     }
 
-    pub fn renew_state(&mut self) {
+    /// Output devices available on the default audio host.
+    pub fn output_devices() -> Result<Vec<AudioDevice>> {
+        let host = cpal::default_host();
+        let default_name = host.default_output_device().and_then(|device| device.name().ok());
+        let devices = host
+            .output_devices()?
+            .filter_map(|device| device.name().ok())
+            .map(|name| AudioDevice {
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                name,
+            })
+            .collect();
+
+        Ok(devices)
+    }
+
+    /// Creates an audio manager that outputs to the named device, or to the default device
+    /// when no name is given or the named device can't be found.
+    fn create_manager(output_device: Option<&str>) -> Result<AudioManager> {
+        let device = output_device.and_then(|name| {
+            let device = cpal::default_host()
+                .output_devices()
+                .ok()?
+                .find(|device| device.name().is_ok_and(|device_name| device_name == name));
+
+            if device.is_none() {
+                warn!("Output device {} not found, falling back to the default device", name);
+            }
+
+            device
+        });
+
+        let settings = AudioManagerSettings {
+            backend_settings: CpalBackendSettings {
+                device,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        Ok(AudioManager::<DefaultBackend>::new(settings)?)
+    }
+
+    /// Switches playback to another output device, resuming the current track at the same position.
+    pub fn set_output_device(&mut self, output_device: Option<&str>) -> Result<()> {
+        let manager = Self::create_manager(output_device)?;
+        let track = self.track.clone();
+        let progress = self.progress;
+        let is_paused = matches!(self.status, PlayerStatus::Paused);
+
+        self.stop();
+        self.manager = manager;
+
+        if let Some(track) = track {
+            self.play(track)?;
+            if let Some(ref mut sound_handle) = self.sound_handle {
+                sound_handle.seek_to(progress);
+                if is_paused {
+                    sound_handle.pause(Tween::default());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Refreshes status and progress from the sound handle. When the current track has just
+    /// played to the end, records it as ended and returns the ID of the track that should be
+    /// played next, if any.
+    pub fn renew_state(&mut self) -> Option<i64> {
+        if self.sleep_deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            self.stop();
+            self.sleep_deadline = None;
+            self.sleep_timer_expired = true;
+        }
+        self.sleep_timer_remaining_secs = self.sleep_timer_remaining();
+
         if let Some(ref mut sound_handle) = self.sound_handle {
             match sound_handle.state() {
                 PlaybackState::Playing => self.status = PlayerStatus::Playing,
@@ -72,21 +262,74 @@ impl Player {
             }
             None => {}
         }
+
+        // `stop()` drops the sound handle, so a stopped handle means the track ran to the end
+        let finished = self.status == PlayerStatus::Stopped && self.sound_handle.is_some();
+        let just_finished = finished && !self.track_finished;
+        self.track_finished = finished;
+        if !just_finished {
+            return None;
+        }
+
+        let current_track_id = self.track.as_ref().map(|track| track.id);
+        self.ended_track_id = current_track_id;
+        next_track_id(&mut self.queue, self.mode, current_track_id)
+    }
+
+    /// Returns the track that played to the end since the last call, if any.
+    pub fn take_ended_track_id(&mut self) -> Option<i64> {
+        self.ended_track_id.take()
+    }
+
+    /// Whether the serialized state differs from what was last reported, remembering the
+    /// current state as reported when it does.
+    pub fn take_state_change(&mut self) -> bool {
+        let state = self.snapshot();
+        if self.last_emitted_state.as_ref() == Some(&state) {
+            return false;
+        }
+
+        self.last_emitted_state = Some(state);
+        true
+    }
+
+    fn snapshot(&self) -> SerializedPlayerState {
+        SerializedPlayerState {
+            current_track_id: self.current_track_id,
+            status: self.status,
+            progress: self.progress,
+            duration: self.duration,
+            volume: self.volume,
+            speed: self.speed,
+            mode: self.mode,
+            queue: self.queue.clone(),
+            history: self.history.clone(),
+            sleep_timer_remaining_secs: self.sleep_timer_remaining_secs,
+        }
     }
 
     pub fn play(&mut self, track: PersistentTrack) -> Result<()> {
+        if let Some(ref current) = self.track {
+            self.history.push_back(current.id);
+            if self.history.len() > HISTORY_LIMIT {
+                self.history.pop_front();
+            }
+        }
         let _ = self.stop();
+        self.current_track_id = Some(track.id);
         self.track = Some(track);
 
         if let Some(ref mut track) = self.track {
-            let sound_data = StreamingSoundData::from_file(&track.file_path)?;
+            let sound_data = StreamingSoundData::from_file(&track.file_path)?
+                .playback_rate(self.speed as f64);
 
+            self.track_gain = Self::replaygain_amplitude(track, self.replaygain_enabled);
             self.duration = sound_data.duration().as_secs_f64();
             self.sound_handle = Some(self.manager.play(sound_data)?);
             self.sound_handle
                 .as_mut()
                 .unwrap()
-                .set_volume(Self::volume_as_decibels(self.volume), Tween::default());
+                .set_volume(Self::volume_as_decibels(self.volume * self.track_gain), Tween::default());
         }
 
         Ok(())
@@ -104,7 +347,13 @@ impl Player {
         }
     }
 
+    /// Seeks within the current track, clamping `position` to its duration. Non-finite positions are ignored.
     pub fn seek(&mut self, position: f64) {
+        if !position.is_finite() {
+            return;
+        }
+        let position = position.clamp(0.0, self.duration);
+
         if let Some(ref mut sound_handle) = self.sound_handle {
             match sound_handle.state() {
                 PlaybackState::Playing => sound_handle.seek_to(position),
@@ -121,6 +370,7 @@ impl Player {
             sound_handle.stop(Tween::default());
             self.sound_handle = None;
             self.track = None;
+            self.current_track_id = None;
             self.duration = 0.0;
             self.progress = 0.0;
             self.status = PlayerStatus::Stopped;
@@ -145,18 +395,152 @@ impl Player {
         }
     }
 
+    /// The linear gain to apply to `track`, 1.0 when ReplayGain is off or the track has none.
+    fn replaygain_amplitude(track: &PersistentTrack, replaygain_enabled: bool) -> f64 {
+        match track.replaygain_track_gain {
+            Some(gain) if replaygain_enabled => Self::gain_as_amplitude(gain),
+            _ => 1.0,
+        }
+    }
+
+    /// Turns ReplayGain on or off, applying the change to the current track right away.
+    pub fn set_replaygain_enabled(&mut self, enabled: bool) {
+        self.replaygain_enabled = enabled;
+        self.track_gain = self.track.as_ref().map_or(1.0, |track| Self::replaygain_amplitude(track, enabled));
+
+        if let Some(ref mut sound_handle) = self.sound_handle {
+            sound_handle.set_volume(Self::volume_as_decibels(self.volume * self.track_gain), Tween::default());
+        }
+    }
+
+    /// Converts a ReplayGain value in dB into a linear multiplier: amp = 10 ^ (db / 20)
+    pub(crate) fn gain_as_amplitude(gain: f64) -> f64 {
+        10f64.powf(gain / 20.0)
+    }
+
+    /// Limits a requested volume to `[0.0, 1.0]`, rejecting NaN and infinity.
+    pub fn clamp_volume(volume: f64) -> Option<f64> {
+        volume.is_finite().then(|| volume.clamp(0.0, 1.0))
+    }
+
+    /// Sets the volume, clamped to `[0.0, 1.0]`. Non-finite values are ignored.
     pub fn set_volume(&mut self, volume: f64) {
+        let Some(volume) = Self::clamp_volume(volume) else {
+            return;
+        };
+
         if let Some(ref mut sound_handle) = self.sound_handle {
-            sound_handle.set_volume(Self::volume_as_decibels(volume), Tween::default());
+            sound_handle.set_volume(Self::volume_as_decibels(volume * self.track_gain), Tween::default());
         }
         self.volume = volume;
     }
+
+    /// Limits a requested playback speed to the supported range, rejecting NaN and infinity.
+    pub fn clamp_speed(speed: f32) -> Option<f32> {
+        speed.is_finite().then(|| speed.clamp(MIN_SPEED, MAX_SPEED))
+    }
+
+    /// Changes the playback rate. Kira resamples rather than time-stretches, so pitch follows speed.
+    pub fn set_speed(&mut self, speed: f32) {
+        if let Some(ref mut sound_handle) = self.sound_handle {
+            sound_handle.set_playback_rate(speed as f64, Tween::default());
+        }
+        self.speed = speed;
+    }
+
+    pub fn set_sleep_timer(&mut self, seconds: u64) {
+        self.sleep_deadline = Some(Instant::now() + Duration::from_secs(seconds));
+        self.sleep_timer_remaining_secs = self.sleep_timer_remaining();
+    }
+
+    pub fn cancel_sleep_timer(&mut self) {
+        self.sleep_deadline = None;
+        self.sleep_timer_remaining_secs = None;
+    }
+
+    /// Whole seconds left until the sleep timer stops playback, rounded up.
+    pub fn sleep_timer_remaining(&self) -> Option<u64> {
+        self.sleep_deadline.map(|deadline| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0)
+        })
+    }
+
+    /// Returns whether the sleep timer stopped playback since the last call.
+    pub fn take_sleep_timer_expired(&mut self) -> bool {
+        std::mem::take(&mut self.sleep_timer_expired)
+    }
+
+    pub fn set_mode(&mut self, mode: PlaybackMode) {
+        self.mode = mode;
+    }
+
+    pub fn enqueue(&mut self, track_ids: Vec<i64>) {
+        self.queue.extend(track_ids);
+    }
+
+    /// Removes the queued track at `index`, returning its ID if the index was in range.
+    pub fn dequeue(&mut self, index: usize) -> Option<i64> {
+        self.queue.remove(index)
+    }
+
+    pub fn clear_queue(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Takes the track to skip to. Unlike the end of a track, skipping never repeats the current one.
+    pub fn skip_next(&mut self) -> Option<i64> {
+        let mode = match self.mode {
+            PlaybackMode::RepeatOne => PlaybackMode::Normal,
+            mode => mode,
+        };
+        let current_track_id = self.track.as_ref().map(|track| track.id);
+        next_track_id(&mut self.queue, mode, current_track_id)
+    }
+
+    /// Takes the track to go back to, or `None` if the current track should just restart.
+    /// The current track is put back at the front of the queue so skipping forward returns to it.
+    pub fn skip_prev(&mut self) -> Option<i64> {
+        if self.progress > RESTART_THRESHOLD_SECS {
+            return None;
+        }
+
+        let previous_track_id = self.history.pop_back()?;
+        if let Some(current) = self.track.take() {
+            self.current_track_id = None;
+            self.queue.push_front(current.id);
+        }
+        Some(previous_track_id)
+    }
+}
+
+fn next_track_id(queue: &mut VecDeque<i64>, mode: PlaybackMode, current_track_id: Option<i64>) -> Option<i64> {
+    match mode {
+        PlaybackMode::Normal => queue.pop_front(),
+        PlaybackMode::RepeatOne => current_track_id,
+        PlaybackMode::RepeatAll => {
+            // The finished track goes to the back of the queue so the whole queue keeps cycling
+            if let Some(id) = current_track_id {
+                queue.push_back(id);
+            }
+            queue.pop_front()
+        }
+        PlaybackMode::Shuffle => {
+            if queue.is_empty() {
+                None
+            } else {
+                queue.remove(fastrand::usize(..queue.len()))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::VecDeque;
+
     use kira::Decibels;
-    use super::Player;
+    use super::{next_track_id, PlaybackMode, Player, PlayerStatus, SerializedPlayerState};
 
     #[test]
     fn test_volume_as_decibels() {
@@ -173,4 +557,59 @@ mod tests {
             assert!((db_expected.0 - db_actual.0) < 1e-5, "{} != {}", db_expected.0, db_actual.0);
         }
     }
+
+    #[test]
+    fn test_serialized_player_state_ignores_progress_jitter() {
+        let state = SerializedPlayerState {
+            current_track_id: Some(1),
+            status: PlayerStatus::Paused,
+            progress: 12.0,
+            duration: 180.0,
+            volume: 1.0,
+            speed: 1.0,
+            mode: PlaybackMode::Normal,
+            queue: VecDeque::from([2, 3]),
+            history: VecDeque::new(),
+            sleep_timer_remaining_secs: None,
+        };
+
+        assert!(state == SerializedPlayerState { progress: 12.005, ..state.clone() });
+        assert!(state != SerializedPlayerState { progress: 12.04, ..state.clone() });
+        assert!(state != SerializedPlayerState { status: PlayerStatus::Playing, ..state.clone() });
+        assert!(state != SerializedPlayerState { queue: VecDeque::from([3]), ..state.clone() });
+    }
+
+    #[test]
+    fn test_clamp_volume() {
+        assert_eq!(Player::clamp_volume(0.5), Some(0.5));
+        assert_eq!(Player::clamp_volume(-0.2), Some(0.0));
+        assert_eq!(Player::clamp_volume(3.0), Some(1.0));
+        assert_eq!(Player::clamp_volume(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_clamp_speed() {
+        assert_eq!(Player::clamp_speed(1.5), Some(1.5));
+        assert_eq!(Player::clamp_speed(0.1), Some(0.25));
+        assert_eq!(Player::clamp_speed(10.0), Some(4.0));
+        assert_eq!(Player::clamp_speed(f32::NAN), None);
+        assert_eq!(Player::clamp_speed(f32::INFINITY), None);
+    }
+
+    #[test]
+    fn test_next_track_id() {
+        let mut queue = VecDeque::from([2, 3]);
+        assert_eq!(next_track_id(&mut queue, PlaybackMode::RepeatOne, Some(1)), Some(1));
+        assert_eq!(next_track_id(&mut queue, PlaybackMode::RepeatAll, Some(1)), Some(2));
+        assert_eq!(queue, VecDeque::from([3, 1]));
+        assert_eq!(next_track_id(&mut queue, PlaybackMode::Normal, Some(2)), Some(3));
+        assert_eq!(next_track_id(&mut queue, PlaybackMode::Normal, Some(3)), Some(1));
+        assert_eq!(next_track_id(&mut queue, PlaybackMode::Normal, Some(1)), None);
+        assert_eq!(next_track_id(&mut queue, PlaybackMode::Shuffle, Some(1)), None);
+
+        let mut queue = VecDeque::from([4, 5, 6]);
+        let next = next_track_id(&mut queue, PlaybackMode::Shuffle, None).unwrap();
+        assert!([4, 5, 6].contains(&next));
+        assert_eq!(queue.len(), 2);
+    }
 }
@@ -1,10 +1,22 @@
+use std::num::NonZeroUsize;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use anyhow::Result;
+use lru::LruCache;
 use rusqlite::Connection;
 use tauri::{AppHandle, Manager, State};
+use tokio_util::sync::CancellationToken;
 
+use crate::db::{self, DbConnection, DbPool};
+use crate::logging::LogFilterHandle;
+use crate::persistent_entities::PersistentTrack;
 use crate::player::Player;
 
 use serde::Serialize;
 
+const TRACK_CACHE_CAPACITY: usize = 256;
+
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum NotifyType {
@@ -21,50 +33,179 @@ pub enum NotifyType {
 #[derive(Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Notify {
+    /// Unique per notification, so the frontend can tell repeated deliveries apart.
+    pub id: String,
     pub message: String,
     #[serde(rename = "type")]
     pub notify_type: NotifyType,
 }
 
+impl Notify {
+    pub fn new(message: impl Into<String>, notify_type: NotifyType) -> Self {
+        Notify {
+            id: uuid::Uuid::new_v4().to_string(),
+            message: message.into(),
+            notify_type,
+        }
+    }
+}
+
+/// Recently used tracks, keyed by track ID.
+pub struct TrackCache(LruCache<i64, PersistentTrack>);
+
+impl Default for TrackCache {
+    fn default() -> Self {
+        let capacity = NonZeroUsize::new(TRACK_CACHE_CAPACITY).expect("Cache capacity is non-zero");
+        TrackCache(LruCache::new(capacity))
+    }
+}
+
+impl TrackCache {
+    pub fn get(&mut self, id: i64) -> Option<PersistentTrack> {
+        self.0.get(&id).cloned()
+    }
+
+    pub fn insert(&mut self, track: PersistentTrack) {
+        self.0.put(track.id, track);
+    }
+
+    pub fn invalidate(&mut self, id: i64) {
+        self.0.pop(&id);
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
 pub struct AppState {
-    pub db: std::sync::Mutex<Option<Connection>>,
+    pub db: std::sync::RwLock<Option<DbPool>>,
+    pub track_cache: std::sync::Mutex<TrackCache>,
+    pub http_client: std::sync::RwLock<reqwest::Client>,
     pub player: std::sync::Mutex<Option<Player>>,
+    pub log_filter: LogFilterHandle,
+    /// Cancellation flags of the challenges being solved for publish or flag requests, by challenge ID.
+    pub challenge_cancels: std::sync::Mutex<std::collections::HashMap<String, Arc<AtomicBool>>>,
+    /// Cancellation token of the running library scan or refresh, if any.
+    pub scan_cancel: std::sync::Mutex<Option<CancellationToken>>,
+}
+
+impl AppState {
+    /// Checks a connection out of the pool. Each caller gets its own connection, so reads
+    /// no longer wait behind a long-running scan or download.
+    pub fn db_connection(&self) -> Result<DbConnection, String> {
+        let pool = self.db.read()
+            .map_err(|e| format!("Database lock error: {}", e))?
+            .clone()
+            .ok_or("Database not initialized")?;
+
+        pool.get().map_err(|e| format!("Database pool error: {}", e))
+    }
 }
 
 pub trait ServiceAccess {
-    fn db<F, TResult>(&self, operation: F) -> TResult
+    fn db_read<F, TResult>(&self, operation: F) -> TResult
     where
         F: FnOnce(&Connection) -> TResult;
 
-    fn db_mut<F, TResult>(&self, operation: F) -> TResult
+    fn db_write<F, TResult>(&self, operation: F) -> TResult
+    where
+        F: FnOnce(&mut Connection) -> TResult;
+
+    fn db_write_track<F, TResult>(&self, id: i64, operation: F) -> TResult
     where
         F: FnOnce(&mut Connection) -> TResult;
+
+    fn db_write_tracks<F, E>(&self, operation: F) -> std::result::Result<Vec<i64>, E>
+    where
+        F: FnOnce(&mut Connection) -> std::result::Result<Vec<i64>, E>;
+
+    fn cached_track(&self, id: i64) -> Result<PersistentTrack>;
+
+    fn http_client(&self) -> reqwest::Client;
 }
 
 impl ServiceAccess for AppHandle {
-    fn db<F, TResult>(&self, operation: F) -> TResult
+    fn db_read<F, TResult>(&self, operation: F) -> TResult
     where
         F: FnOnce(&Connection) -> TResult,
     {
         let app_state: State<AppState> = self.state();
-        let db_connection_guard = app_state.db.lock()
-            .expect("Database mutex poisoned");
-        let db = db_connection_guard.as_ref()
-            .expect("Database not initialized");
+        let db = app_state.db_connection()
+            .expect("Database connection should be available");
 
-        operation(db)
+        operation(&db)
     }
 
-    fn db_mut<F, TResult>(&self, operation: F) -> TResult
+    fn db_write<F, TResult>(&self, operation: F) -> TResult
     where
         F: FnOnce(&mut Connection) -> TResult,
     {
         let app_state: State<AppState> = self.state();
-        let mut db_connection_guard = app_state.db.lock()
-            .expect("Database mutex poisoned");
-        let db = db_connection_guard.as_mut()
-            .expect("Database not initialized");
+        let mut db = app_state.db_connection()
+            .expect("Database connection should be available");
+
+        operation(&mut db)
+    }
+
+    /// Runs a write that changes track `id`, then drops the track from the track cache so
+    /// `cached_track` doesn't serve the old row. Track writes go through here, not `db_write`.
+    fn db_write_track<F, TResult>(&self, id: i64, operation: F) -> TResult
+    where
+        F: FnOnce(&mut Connection) -> TResult,
+    {
+        let result = self.db_write(operation);
+        invalidate_cached_track(self, id);
+
+        result
+    }
+
+    /// Like `db_write_track`, for writes that only know which tracks they changed once they ran.
+    /// The operation returns the changed track IDs, and each of them is dropped from the cache.
+    fn db_write_tracks<F, E>(&self, operation: F) -> std::result::Result<Vec<i64>, E>
+    where
+        F: FnOnce(&mut Connection) -> std::result::Result<Vec<i64>, E>,
+    {
+        let track_ids = self.db_write(operation)?;
+        for track_id in track_ids.iter() {
+            invalidate_cached_track(self, *track_id);
+        }
+
+        Ok(track_ids)
+    }
+
+    /// Looks the track up in the track cache first and only hits the database on a miss.
+    fn cached_track(&self, id: i64) -> Result<PersistentTrack> {
+        let app_state: State<AppState> = self.state();
+
+        if let Some(track) = app_state.track_cache.lock()
+            .expect("Track cache mutex poisoned")
+            .get(id)
+        {
+            return Ok(track);
+        }
+
+        let track = self.db_read(|db| db::get_track_by_id(id, db))?;
+        app_state.track_cache.lock()
+            .expect("Track cache mutex poisoned")
+            .insert(track.clone());
 
-        operation(db)
+        Ok(track)
     }
+
+    fn http_client(&self) -> reqwest::Client {
+        let app_state: State<AppState> = self.state();
+        let client = app_state.http_client.read()
+            .expect("HTTP client lock poisoned")
+            .clone();
+
+        client
+    }
+}
+
+fn invalidate_cached_track(app_handle: &AppHandle, id: i64) {
+    let app_state: State<AppState> = app_handle.state();
+    app_state.track_cache.lock()
+        .expect("Track cache mutex poisoned")
+        .invalidate(id);
 }
@@ -1,14 +1,24 @@
+use anyhow::Result;
 use collapse::collapse;
+use lrc::{Lyrics, TimeTag};
 use regex::Regex;
 use secular::lower_lay_string;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
+use tracing::warn;
 
+// ASCII only, so CJK punctuation and ideographs pass through untouched
 static RE_PUNCTUATION: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"[`~!@#$%^&*()_|+\-=?;:",.<>\{\}\[\]\\\/]"#).unwrap());
 static RE_QUOTES: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"['']"#).unwrap());
 static RE_TIMESTAMP: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"(?m)^\[[^\]]*\] *").unwrap());
+static RE_EXTRA_BLANK_LINES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\r?\n){3,}").unwrap());
 pub static RE_INSTRUMENTAL: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"\[au:\s*instrumental\]").unwrap());
 
@@ -24,7 +34,262 @@ pub fn prepare_input(input: &str) -> String {
     prepared_input
 }
 
+/// Validates an ISRC written as `CC-XXX-YY-NNNNN`, with or without the hyphens, and
+/// returns its compact uppercase form.
+pub fn normalize_isrc(input: &str) -> Option<String> {
+    let isrc: String = input.trim().split('-').collect::<String>().to_uppercase();
+    let bytes = isrc.as_bytes();
+
+    let valid = bytes.len() == 12
+        && bytes[..2].iter().all(u8::is_ascii_alphabetic)
+        && bytes[2..5].iter().all(u8::is_ascii_alphanumeric)
+        && bytes[5..].iter().all(u8::is_ascii_digit);
+
+    valid.then_some(isrc)
+}
+
+/// Escapes `%`, `_` and `escape_char` itself so the input matches literally in a
+/// `LIKE ... ESCAPE` clause.
+pub fn escape_like(input: &str, escape_char: char) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if c == '%' || c == '_' || c == escape_char {
+            escaped.push(escape_char);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Edit distance between the strings scaled to 0.0-1.0, where 1.0 means identical. Catches
+/// typos and small spelling differences that word overlap misses.
+pub fn levenshtein_similarity(a: &str, b: &str) -> f64 {
+    strsim::normalized_levenshtein(a, b)
+}
+
+/// Wraps the input in double quotes, doubling any inside, so FTS5 reads it as a plain string
+/// rather than query syntax.
+pub fn fts5_quote(input: &str) -> String {
+    format!("\"{}\"", input.replace('"', "\"\""))
+}
+
+/// Removes the timestamps of synced lyrics. Blank timed lines separating sections are kept as a
+/// single empty line, however many of them there were.
 pub fn strip_timestamp(synced_lyrics: &str) -> String {
     let plain_lyrics = RE_TIMESTAMP.replace_all(synced_lyrics, "");
-    plain_lyrics.to_string()
+    RE_EXTRA_BLANK_LINES.replace_all(&plain_lyrics, "$1$1").to_string()
+}
+
+/// Writes `content` to a sibling `.tmp` file, then renames it over `path` so the target is always
+/// either the old or the new complete file. Falls back to remove + write when the rename cannot
+/// replace an existing file (seen on some FAT32 volumes).
+pub fn atomic_write(path: &Path, content: &[u8]) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    {
+        let mut tmp_file = File::create(&tmp_path)?;
+        tmp_file.write_all(content)?;
+        tmp_file.sync_all()?;
+    }
+
+    if let Err(rename_err) = fs::rename(&tmp_path, path) {
+        warn!(
+            "Cannot rename `{}` over `{}`: {}. Falling back to direct write...",
+            tmp_path.display(),
+            path.display(),
+            rename_err
+        );
+        let _ = fs::remove_file(path);
+        let write_result = fs::write(path, content);
+        let _ = fs::remove_file(&tmp_path);
+        write_result?;
+    }
+
+    Ok(())
+}
+
+/// Shifts every timed line by `offset_ms`, clamping negative results to zero.
+/// Metadata tags are kept as they are.
+pub fn lrc_shift_timestamps(lyrics: &str, offset_ms: i64) -> Result<String> {
+    let parsed = Lyrics::from_str(lyrics)?;
+    Ok(shift_timed_lines(&parsed, offset_ms)?.to_string())
+}
+
+fn shift_timed_lines(parsed: &Lyrics, offset_ms: i64) -> Result<Lyrics> {
+    let mut shifted = Lyrics::new();
+    shifted.metadata = parsed.metadata.clone();
+
+    for (time_tag, line) in parsed.get_timed_lines() {
+        // LRC timestamps only hold centiseconds, so round here rather than let the
+        // formatter round 995 ms up to a three digit fraction
+        let timestamp = (time_tag.get_timestamp() + offset_ms).max(0);
+        let timestamp = (timestamp + 5) / 10 * 10;
+        shifted.add_timed_line(TimeTag::new(timestamp), line.to_string())?;
+    }
+
+    Ok(shifted)
+}
+
+/// Header tags such as `ar`, `al`, `ti`, `by` and `offset`, keyed by lowercase label.
+pub fn parse_lrc_metadata(lyrics: &str) -> Result<HashMap<String, String>> {
+    let parsed = Lyrics::from_str(lyrics)?;
+    let metadata = parsed
+        .metadata
+        .iter()
+        .filter_map(|tag| {
+            // IDTag only exposes its label and text through Display, as `[label: text]`
+            let tag = tag.to_string();
+            let (label, text) = tag.strip_prefix('[')?.strip_suffix(']')?.split_once(':')?;
+            Some((label.trim().to_lowercase(), text.trim().to_owned()))
+        })
+        .collect();
+
+    Ok(metadata)
+}
+
+/// The `[offset:]` tag in milliseconds, if the lyrics have a valid one.
+pub fn parse_lrc_offset(synced_lyrics: &str) -> Option<i64> {
+    parse_lrc_metadata(synced_lyrics).ok()?.get("offset")?.parse().ok()
+}
+
+/// Applies the `[offset:]` tag to the timestamps and drops the tag, so players that ignore it
+/// still show each line on time. As in the LRC format, a positive offset makes lines appear sooner.
+/// Lyrics without an offset are returned unchanged.
+pub fn apply_lrc_offset(synced_lyrics: &str) -> Result<String> {
+    let offset = match parse_lrc_offset(synced_lyrics) {
+        Some(offset) if offset != 0 => offset,
+        _ => return Ok(synced_lyrics.to_owned()),
+    };
+
+    let parsed = Lyrics::from_str(synced_lyrics)?;
+    let mut shifted = shift_timed_lines(&parsed, -offset)?;
+    shifted
+        .metadata
+        .retain(|tag| !tag.to_string().to_lowercase().starts_with("[offset:"));
+
+    Ok(shifted.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_lrc_offset, atomic_write, escape_like, fts5_quote, levenshtein_similarity, lrc_shift_timestamps, normalize_isrc,
+        parse_lrc_offset, prepare_input, strip_timestamp,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_strip_timestamp_keeps_section_breaks() {
+        let synced = "[00:01.00]First verse\n[00:02.00]Still first\n[00:03.00]\n[00:04.00]Second verse\n[00:05.00]\n[00:05.50]\n[00:06.00]Chorus";
+        assert_eq!(strip_timestamp(synced), "First verse\nStill first\n\nSecond verse\n\nChorus");
+
+        let separators = "[00:00.00]\n[00:00.00]\n[00:01.00]Line\n[00:00.00] \n[00:02.00]Line";
+        assert_eq!(strip_timestamp(separators), "\n\nLine\n\nLine");
+
+        let crlf = "[00:01.00]One\r\n[00:02.00]\r\n[00:03.00]\r\n[00:04.00]Two";
+        assert_eq!(strip_timestamp(crlf), "One\r\n\r\nTwo");
+    }
+
+    #[test]
+    fn test_prepare_input_keeps_cjk() {
+        assert_eq!(prepare_input("千本桜"), "千本桜");
+        assert_eq!(prepare_input("방탄소년단"), "방탄소년단");
+        assert_eq!(prepare_input("七里香"), "七里香");
+        assert_eq!(prepare_input("「夜に駆ける」、YOASOBI"), "「夜に駆ける」、yoasobi");
+    }
+
+    #[test]
+    fn test_normalize_isrc() {
+        assert_eq!(normalize_isrc("US-RC1-76-07839").as_deref(), Some("USRC17607839"));
+        assert_eq!(normalize_isrc("usrc17607839").as_deref(), Some("USRC17607839"));
+        assert_eq!(normalize_isrc(" GBAYE0601498 ").as_deref(), Some("GBAYE0601498"));
+        assert_eq!(normalize_isrc("US-RC1-76-0783"), None);
+        assert_eq!(normalize_isrc("1SRC17607839"), None);
+        assert_eq!(normalize_isrc("USRC1760783X"), None);
+        assert_eq!(normalize_isrc(""), None);
+    }
+
+    #[test]
+    fn test_levenshtein_similarity() {
+        assert_eq!(levenshtein_similarity("yesterday", "yesterday"), 1.0);
+        assert!((levenshtein_similarity("yesterday", "yesturday") - 8.0 / 9.0).abs() < 1e-9);
+        assert_eq!(levenshtein_similarity("abc", "xyz"), 0.0);
+        assert_eq!(levenshtein_similarity("", ""), 1.0);
+    }
+
+    #[test]
+    fn test_fts5_quote() {
+        assert_eq!(fts5_quote("bjork"), "\"bjork\"");
+        assert_eq!(fts5_quote("a\" OR b"), "\"a\"\" OR b\"");
+        assert_eq!(fts5_quote("title:x NEAR(y)"), "\"title:x NEAR(y)\"");
+    }
+
+    #[test]
+    fn test_atomic_write_never_leaves_truncated_file() {
+        let dir = std::env::temp_dir().join(format!("lrcget-atomic-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("track.lrc");
+        let tmp = dir.join("track.lrc.tmp");
+
+        atomic_write(&target, b"[00:01.00] old").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "[00:01.00] old");
+        assert!(!tmp.exists());
+
+        // An interrupted write only ever touches the temporary file
+        fs::write(&tmp, b"[00:01.00] ne").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "[00:01.00] old");
+
+        atomic_write(&target, b"[00:01.00] new").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "[00:01.00] new");
+        assert!(!tmp.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("plain text", '\\'), "plain text");
+        assert_eq!(escape_like("100% pure", '\\'), "100\\% pure");
+        assert_eq!(escape_like("snake_case", '\\'), "snake\\_case");
+        assert_eq!(escape_like("back\\slash", '\\'), "back\\\\slash");
+        assert_eq!(escape_like("%_\\", '\\'), "\\%\\_\\\\");
+        assert_eq!(escape_like("50%_off!", '!'), "50!%!_off!!");
+        assert_eq!(escape_like("", '\\'), "");
+    }
+
+    #[test]
+    fn test_lrc_shift_timestamps() {
+        let lyrics = "[ar:Artist]\n[00:01.00]First\n[00:02.50]Second";
+
+        assert_eq!(
+            lrc_shift_timestamps(lyrics, 1500).unwrap(),
+            "[ar: Artist]\n\n[00:02.50]First\n[00:04.00]Second"
+        );
+        assert_eq!(
+            lrc_shift_timestamps(lyrics, -2000).unwrap(),
+            "[ar: Artist]\n\n[00:00.00]First\n[00:00.50]Second"
+        );
+    }
+
+    #[test]
+    fn test_parse_lrc_offset() {
+        assert_eq!(parse_lrc_offset("[offset:500]\n[00:01.00]Line"), Some(500));
+        assert_eq!(parse_lrc_offset("[offset: -250]\n[00:01.00]Line"), Some(-250));
+        assert_eq!(parse_lrc_offset("[ar:Artist]\n[00:01.00]Line"), None);
+        assert_eq!(parse_lrc_offset("[offset:soon]\n[00:01.00]Line"), None);
+    }
+
+    #[test]
+    fn test_apply_lrc_offset() {
+        let lyrics = "[ar:Artist]\n[offset:500]\n[00:01.00]First\n[00:02.00]Second";
+        assert_eq!(
+            apply_lrc_offset(lyrics).unwrap(),
+            "[ar: Artist]\n\n[00:00.50]First\n[00:01.50]Second"
+        );
+
+        let without_offset = "[00:01.00]First";
+        assert_eq!(apply_lrc_offset(without_offset).unwrap(), without_offset);
+    }
 }
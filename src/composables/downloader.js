@@ -46,7 +46,7 @@ const downloadLyrics = async (track) => {
       return
     }
 
-    const isNotFound = error === NOT_FOUND_MESSAGE
+    const isNotFound = typeof error === 'string' && error.startsWith(NOT_FOUND_MESSAGE)
     addLog({ status: isNotFound ? 'not_found' : 'failure', title: track.title, artistName: track.artist_name, message: error })
     if (isNotFound) {
       notFoundCount.value++
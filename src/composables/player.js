@@ -7,12 +7,24 @@ const status = ref('stopped')
 const duration = ref(null)
 const progress = ref(null)
 const volume = ref(1.0)
+let loadingTrackId = null
 
 listen('player-state', async (event) => {
   duration.value = event.payload.duration
   progress.value = event.payload.progress
   status.value = event.payload.status
   volume.value = event.payload.volume
+
+  // Follow the player when it moves on by itself, e.g. when the queue advances
+  const currentTrackId = event.payload.current_track_id
+  if (currentTrackId && playingTrack.value?.id !== currentTrackId && loadingTrackId !== currentTrackId) {
+    loadingTrackId = currentTrackId
+    try {
+      playingTrack.value = await invoke('get_track', { trackId: currentTrackId })
+    } finally {
+      loadingTrackId = null
+    }
+  }
 })
 
 listen('reload-track-id', async (event) => {
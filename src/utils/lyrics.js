@@ -3,6 +3,20 @@ export const isSynchronizedLyrics = (lyrics) => {
   return /^\[(?:\d{2}:\d{2}[.:]\d{2,3}|[a-z]+:.+?)\]/.test(lyrics)
 }
 
+// Describes the lyrics response `apply_lyrics` resolves with
+export const appliedLyricsMessage = (response) => {
+  switch (response.type) {
+    case 'SyncedLyrics':
+      return 'Synced lyrics downloaded'
+    case 'UnsyncedLyrics':
+      return 'Plain lyrics downloaded'
+    case 'IsInstrumental':
+      return 'Marked track as instrumental'
+    default:
+      return 'No lyrics applied'
+  }
+}
+
 export const detectStandard = (lyrics) => {
   // Define format patterns
   const formats = [